@@ -0,0 +1,128 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate exonum;
+extern crate exonum_btc_anchoring;
+
+extern crate reqwest;
+#[macro_use]
+extern crate serde_json;
+extern crate structopt;
+extern crate toml;
+#[macro_use]
+extern crate failure;
+
+use exonum::helpers::ValidatorId;
+use exonum::messages::{to_hex_string, Message};
+use exonum::node::NodeConfig;
+use structopt::StructOpt;
+
+use std::fs;
+use std::path::PathBuf;
+
+use exonum_btc_anchoring::blockchain::transactions::TxFundingProposal;
+use exonum_btc_anchoring::btc::{Address, Transaction};
+use exonum_btc_anchoring::{BTC_ANCHORING_SERVICE_ID, BTC_ANCHORING_SERVICE_NAME};
+
+/// BTC anchoring funding proposal tool
+///
+/// Allows validators to top up the anchoring wallet without the configuration
+/// change. Signs the vote for the funding transaction output with the service
+/// key of the validator and submits it to the node.
+#[derive(StructOpt)]
+struct Opts {
+    /// Url of the node public API.
+    #[structopt(long = "node-url", default_value = "http://127.0.0.1:8200")]
+    node_url: String,
+    /// Path to the validator node configuration file with the service keys.
+    #[structopt(long = "node-config", parse(from_os_str))]
+    node_config: PathBuf,
+    /// Index of the funding transaction output, the first output to the actual
+    /// anchoring address is used if omitted.
+    #[structopt(long = "output")]
+    output: Option<u32>,
+    /// Raw funding transaction in the hex encoding.
+    transaction: Transaction,
+}
+
+fn actual_address(node_url: &str) -> Result<Address, failure::Error> {
+    let url = format!(
+        "{}/api/services/{}/v1/address/actual",
+        node_url, BTC_ANCHORING_SERVICE_NAME
+    );
+    let mut response = reqwest::get(&url)?;
+    ensure!(
+        response.status().is_success(),
+        "Node {} failed with {}: {}",
+        url,
+        response.status(),
+        response.text()?
+    );
+    Ok(response.json()?)
+}
+
+fn main() -> Result<(), failure::Error> {
+    let opts = Opts::from_args();
+    let node_url = opts.node_url.trim_end_matches('/');
+    let node_config: NodeConfig = toml::from_str(&fs::read_to_string(opts.node_config)?)?;
+
+    let validator = node_config
+        .genesis
+        .validator_keys
+        .iter()
+        .position(|keys| keys.service_key == node_config.service_public_key)
+        .map(|id| ValidatorId(id as u16))
+        .ok_or_else(|| format_err!("Given node is not a validator"))?;
+    let output = match opts.output {
+        Some(output) => output,
+        None => {
+            let address = actual_address(node_url)?;
+            let (output, _) = opts
+                .transaction
+                .find_out(&address.0.script_pubkey())
+                .ok_or_else(|| format_err!("Transaction doesn't pay to the address {}", address))?;
+            output as u32
+        }
+    };
+
+    let message = Message::sign_transaction(
+        TxFundingProposal {
+            validator,
+            transaction: opts.transaction,
+            output,
+        },
+        BTC_ANCHORING_SERVICE_ID,
+        node_config.service_public_key,
+        &node_config.service_secret_key,
+    );
+
+    let url = format!("{}/api/explorer/v1/transactions", node_url);
+    let mut response = reqwest::Client::new()
+        .post(&url)
+        .json(&json!({ "tx_body": to_hex_string(&message) }))
+        .send()?;
+    ensure!(
+        response.status().is_success(),
+        "Node {} failed with {}: {}",
+        url,
+        response.status(),
+        response.text()?
+    );
+    println!(
+        "Submitted funding proposal for the output {}: {}",
+        output,
+        message.hash()
+    );
+    Ok(())
+}
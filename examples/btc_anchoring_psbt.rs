@@ -0,0 +1,185 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate exonum;
+extern crate exonum_btc_anchoring;
+
+extern crate reqwest;
+#[macro_use]
+extern crate serde_json;
+extern crate structopt;
+extern crate toml;
+#[macro_use]
+extern crate failure;
+
+use exonum::helpers::ValidatorId;
+use exonum::messages::{to_hex_string, Message};
+use exonum::node::NodeConfig;
+use structopt::StructOpt;
+
+use std::fs;
+use std::path::PathBuf;
+
+use exonum_btc_anchoring::blockchain::transactions::TxSignature;
+use exonum_btc_anchoring::btc::{Psbt, PublicKey};
+use exonum_btc_anchoring::config::Config;
+use exonum_btc_anchoring::{BTC_ANCHORING_SERVICE_ID, BTC_ANCHORING_SERVICE_NAME};
+
+/// BTC anchoring PSBT tool
+///
+/// Allows validators with the offline anchoring keys to sign the anchoring
+/// proposals as partially signed Bitcoin transactions (BIP 174).
+#[derive(StructOpt)]
+enum Opts {
+    /// Exports the current anchoring proposal as PSBT in the base64 encoding.
+    #[structopt(name = "export")]
+    Export {
+        /// Url of the node public API.
+        #[structopt(long = "node-url", default_value = "http://127.0.0.1:8200")]
+        node_url: String,
+        /// Path to the output file, PSBT is printed to the stdout if omitted.
+        #[structopt(long = "output", parse(from_os_str))]
+        output: Option<PathBuf>,
+    },
+    /// Submits the signatures from the given signed PSBT to the node.
+    #[structopt(name = "submit")]
+    Submit {
+        /// Url of the node public API.
+        #[structopt(long = "node-url", default_value = "http://127.0.0.1:8200")]
+        node_url: String,
+        /// Path to the validator node configuration file with the service keys.
+        #[structopt(long = "node-config", parse(from_os_str))]
+        node_config: PathBuf,
+        /// Anchoring public key of the validator, it is taken from the node
+        /// configuration if omitted.
+        #[structopt(long = "public-key")]
+        public_key: Option<PublicKey>,
+        /// Path to the file with the signed PSBT in the base64 encoding.
+        #[structopt(parse(from_os_str))]
+        psbt: PathBuf,
+    },
+}
+
+fn export(node_url: &str, output: Option<PathBuf>) -> Result<(), failure::Error> {
+    let url = format!(
+        "{}/api/services/{}/v1/proposal/psbt",
+        node_url.trim_end_matches('/'),
+        BTC_ANCHORING_SERVICE_NAME
+    );
+    let mut response = reqwest::get(&url)?;
+    ensure!(
+        response.status().is_success(),
+        "Node {} failed with {}: {}",
+        url,
+        response.status(),
+        response.text()?
+    );
+    let psbt: Option<Psbt> = response.json()?;
+    let psbt = psbt.ok_or_else(|| format_err!("There is no anchoring proposal at the moment"))?;
+
+    match output {
+        Some(path) => fs::write(path, psbt.to_string())?,
+        None => println!("{}", psbt),
+    }
+    Ok(())
+}
+
+fn submit(
+    node_url: &str,
+    node_config: PathBuf,
+    public_key: Option<PublicKey>,
+    psbt: PathBuf,
+) -> Result<(), failure::Error> {
+    let psbt: Psbt = fs::read_to_string(psbt)?.parse()?;
+    let node_config: toml::Value = toml::from_str(&fs::read_to_string(node_config)?)?;
+    let anchoring_config: Config = node_config
+        .get("services_configs")
+        .and_then(|configs| configs.get(BTC_ANCHORING_SERVICE_NAME))
+        .ok_or_else(|| format_err!("BTC anchoring config not found"))?
+        .clone()
+        .try_into()?;
+    let node_config: NodeConfig = node_config.try_into()?;
+
+    let validator_id = node_config
+        .genesis
+        .validator_keys
+        .iter()
+        .position(|keys| keys.service_key == node_config.service_public_key)
+        .map(|id| ValidatorId(id as u16))
+        .ok_or_else(|| format_err!("Given node is not a validator"))?;
+    let public_key = match public_key {
+        Some(public_key) => public_key,
+        None => *anchoring_config
+            .global
+            .public_keys
+            .get(validator_id.0 as usize)
+            .ok_or_else(|| format_err!("Anchoring public key of the validator not found"))?,
+    };
+
+    let signatures = psbt.input_signatures(&public_key);
+    ensure!(
+        !signatures.is_empty(),
+        "Given PSBT does not contain signatures for the key {}",
+        public_key
+    );
+
+    let url = format!(
+        "{}/api/explorer/v1/transactions",
+        node_url.trim_end_matches('/')
+    );
+    let client = reqwest::Client::new();
+    for (input, input_signature) in signatures {
+        let message = Message::sign_transaction(
+            TxSignature {
+                validator: validator_id,
+                transaction: psbt.unsigned_tx.clone(),
+                input,
+                input_signature,
+            },
+            BTC_ANCHORING_SERVICE_ID,
+            node_config.service_public_key,
+            &node_config.service_secret_key,
+        );
+
+        let mut response = client
+            .post(&url)
+            .json(&json!({ "tx_body": to_hex_string(&message) }))
+            .send()?;
+        ensure!(
+            response.status().is_success(),
+            "Node {} failed with {}: {}",
+            url,
+            response.status(),
+            response.text()?
+        );
+        println!(
+            "Submitted signature for the input {}: {}",
+            input,
+            message.hash()
+        );
+    }
+    Ok(())
+}
+
+fn main() -> Result<(), failure::Error> {
+    match Opts::from_args() {
+        Opts::Export { node_url, output } => export(&node_url, output),
+        Opts::Submit {
+            node_url,
+            node_config,
+            public_key,
+            psbt,
+        } => submit(&node_url, node_config, public_key, psbt),
+    }
+}
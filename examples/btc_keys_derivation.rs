@@ -0,0 +1,66 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate exonum_btc_anchoring;
+
+extern crate structopt;
+extern crate toml;
+#[macro_use]
+extern crate failure;
+
+use structopt::StructOpt;
+
+use std::fs;
+use std::path::PathBuf;
+
+use exonum_btc_anchoring::config::Config;
+use exonum_btc_anchoring::{keystore, BTC_ANCHORING_SERVICE_NAME};
+
+/// BTC anchoring public key derivation
+///
+/// Prints the anchoring public key derived from the extended private key
+/// in the given node configuration for the given key epoch. The key should be
+/// proposed in the `public_keys` of the anchoring configuration with the same
+/// `key_epoch`.
+#[derive(StructOpt)]
+struct Opts {
+    /// Path to the node configuration file.
+    #[structopt(parse(from_os_str))]
+    node_config: PathBuf,
+    /// Epoch of the anchoring keys.
+    epoch: u32,
+}
+
+fn main() -> Result<(), failure::Error> {
+    let opts = Opts::from_args();
+    let node_config: toml::Value = toml::from_str(&fs::read_to_string(&opts.node_config)?)?;
+    let config: Config = node_config
+        .get("services_configs")
+        .and_then(|configs| configs.get(BTC_ANCHORING_SERVICE_NAME))
+        .ok_or_else(|| format_err!("BTC anchoring config not found"))?
+        .clone()
+        .try_into()?;
+    let extended_private_key = match (
+        config.local.extended_private_key,
+        config.local.encrypted_extended_private_key,
+    ) {
+        (Some(key), _) => key,
+        (None, Some(encrypted_key)) => encrypted_key.decrypt_extended(&keystore::passphrase()?)?,
+        (None, None) => bail!("Given config does not contain extended private key"),
+    };
+
+    let (public_key, _) = extended_private_key.derive_keypair(opts.epoch)?;
+    println!("{}", public_key);
+    Ok(())
+}
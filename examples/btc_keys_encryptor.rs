@@ -0,0 +1,64 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+extern crate exonum_btc_anchoring;
+
+extern crate structopt;
+extern crate toml;
+#[macro_use]
+extern crate failure;
+
+use structopt::StructOpt;
+
+use std::fs;
+use std::path::PathBuf;
+
+use exonum_btc_anchoring::config::Config;
+use exonum_btc_anchoring::{keystore, BTC_ANCHORING_SERVICE_NAME};
+
+/// BTC anchoring private keys encryptor
+///
+/// Encrypts the plain anchoring private keys including the extended one in the given
+/// node configuration with the passphrase, which is taken from the `BTC_ANCHORING_PASSPHRASE`
+/// environment variable or prompted from the terminal.
+#[derive(StructOpt)]
+struct Opts {
+    /// Path to the node configuration file.
+    #[structopt(parse(from_os_str))]
+    node_config: PathBuf,
+}
+
+fn main() -> Result<(), failure::Error> {
+    let opts = Opts::from_args();
+    let mut node_config: toml::Value = toml::from_str(&fs::read_to_string(&opts.node_config)?)?;
+
+    {
+        let service_config = node_config
+            .get_mut("services_configs")
+            .and_then(|configs| configs.get_mut(BTC_ANCHORING_SERVICE_NAME))
+            .ok_or_else(|| format_err!("BTC anchoring config not found"))?;
+        let mut config: Config = service_config.clone().try_into()?;
+        ensure!(
+            !config.local.private_keys.is_empty() || config.local.extended_private_key.is_some(),
+            "Given config does not contain plain private keys"
+        );
+        config
+            .local
+            .encrypt_private_keys(&keystore::new_passphrase()?);
+        *service_config = toml::Value::try_from(config)?;
+    }
+
+    fs::write(&opts.node_config, toml::to_string(&node_config)?)?;
+    Ok(())
+}
@@ -24,7 +24,7 @@ extern crate failure;
 use hex::FromHex;
 use structopt::StructOpt;
 
-use exonum_btc_anchoring::btc::Transaction;
+use exonum_btc_anchoring::btc::{ChainId, Transaction};
 
 /// BTC anchoring payload extractor
 ///
@@ -33,13 +33,25 @@ use exonum_btc_anchoring::btc::Transaction;
 struct Opts {
     /// Bitcoin transaction hex.
     hex: String,
+    /// Expected identifier of the anchored blockchain, payloads of the other
+    /// blockchains are rejected.
+    #[structopt(long = "chain-id")]
+    chain_id: Option<ChainId>,
 }
 
 fn main() -> Result<(), failure::Error> {
-    let transaction = Transaction::from_hex(Opts::from_args().hex)?;
+    let opts = Opts::from_args();
+    let transaction = Transaction::from_hex(opts.hex)?;
     let payload = transaction
         .anchoring_payload()
         .ok_or_else(|| format_err!("Given transaction does not contains anchoring payload"))?;
+    if let Some(chain_id) = opts.chain_id {
+        ensure!(
+            payload.matches_chain(&chain_id),
+            "Given transaction anchors the foreign blockchain {}",
+            payload.chain_id.unwrap()
+        );
+    }
     println!("{}", serde_json::to_string_pretty(&payload)?);
     Ok(())
 }
@@ -0,0 +1,34 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Feeds arbitrary Bitcoin transactions into the anchoring metadata parser, run it with
+//! `cargo +nightly fuzz run anchoring_transaction`.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate bitcoin;
+extern crate exonum_btc_anchoring;
+
+use bitcoin::blockdata::transaction;
+
+use exonum_btc_anchoring::btc::Transaction;
+
+fuzz_target!(|data: &[u8]| {
+    if let Ok(tx) = bitcoin::consensus::deserialize::<transaction::Transaction>(data) {
+        let tx = Transaction::from(tx);
+        let _ = tx.anchoring_metadata();
+        let _ = tx.anchoring_payload();
+    }
+});
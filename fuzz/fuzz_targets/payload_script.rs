@@ -0,0 +1,40 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Feeds arbitrary scripts into the payload parser, run it with
+//! `cargo +nightly fuzz run payload_script`.
+
+#![no_main]
+#[macro_use]
+extern crate libfuzzer_sys;
+extern crate bitcoin;
+extern crate exonum_btc_anchoring;
+
+use bitcoin::blockdata::opcodes::All;
+use bitcoin::blockdata::script::{Builder, Script};
+
+use exonum_btc_anchoring::btc::Payload;
+
+fuzz_target!(|data: &[u8]| {
+    let _ = Payload::from_script(&Script::from(data.to_vec()));
+
+    // Reaches the payload layouts directly with the valid prefix.
+    let mut payload_bytes = b"EXONUM".to_vec();
+    payload_bytes.extend_from_slice(data);
+    let script = Builder::new()
+        .push_opcode(All::OP_RETURN)
+        .push_slice(&payload_bytes)
+        .into_script();
+    let _ = Payload::from_script(&script);
+});
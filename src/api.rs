@@ -17,8 +17,8 @@
 use exonum::api::{self, ServiceApiBuilder, ServiceApiState};
 use exonum::blockchain::{BlockProof, Schema as CoreSchema};
 use exonum::crypto::Hash;
-use exonum::helpers::Height;
-use exonum::storage::{ListProof, MapProof};
+use exonum::helpers::{Height, ValidatorId};
+use exonum::storage::{ListProof, MapProof, ProofListIndex, Snapshot};
 
 use failure::Fail;
 
@@ -27,8 +27,10 @@ use std::cmp::{
     Ordering::{self, Equal, Greater, Less},
 };
 
-use blockchain::BtcAnchoringSchema;
+use audit::{AuditLog, AuditReport};
+use blockchain::{batch_first_height, data_layout::TxInputId, BtcAnchoringSchema};
 use btc;
+use sync::{SharedProofCache, SharedSyncState, SyncState};
 use BTC_ANCHORING_SERVICE_ID;
 
 /// Query parameters for the find transaction request.
@@ -45,6 +47,18 @@ pub struct HeightQuery {
     pub height: u64,
 }
 
+/// Maximum number of anchoring transactions in the single page.
+pub const MAX_TRANSACTIONS_PAGE_SIZE: u64 = 100;
+
+/// Query parameters for the anchoring transactions page request.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct TransactionsQuery {
+    /// Index of the first anchoring transaction in the page.
+    pub from: u64,
+    /// Maximum number of anchoring transactions in the page.
+    pub count: u64,
+}
+
 /// A proof of existence for an anchoring transaction at the given height.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct TransactionProof {
@@ -58,6 +72,88 @@ pub struct TransactionProof {
     pub transactions_count: u64,
 }
 
+/// A proof that the Exonum block at the given height is covered by the anchoring transaction.
+///
+/// The proof consists of the block hashes from the requested height up to the anchored one
+/// and the anchoring transaction which commits to the latter. Both parts are proven against
+/// the same latest authorized block.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnchoredBlockProof {
+    /// Latest authorized block in the blockchain.
+    pub latest_authorized_block: BlockProof,
+    /// Proof for the whole block headers table.
+    pub to_block_headers_table: MapProof<Hash, Hash>,
+    /// Proof for the block headers from the requested height up to the anchored one.
+    pub to_block_headers: ListProof<Hash>,
+    /// Proof for the whole anchoring transactions table.
+    pub to_transactions_table: MapProof<Hash, Hash>,
+    /// Proof for the anchoring transaction which covers the requested block.
+    pub to_transaction: ListProof<btc::Transaction>,
+    /// Anchoring transactions total count.
+    pub transactions_count: u64,
+    /// Proof of inclusion of the anchoring transaction in the Bitcoin blockchain
+    /// if the Bitcoin relay is configured and the synchronization worker has already
+    /// fetched the proof of the confirmed transaction.
+    pub bitcoin_proof: Option<btc::MerkleProof>,
+}
+
+/// A proof that the Exonum block at the given height is committed to by the batch
+/// anchoring transaction.
+///
+/// The proof links the block hash to the batch root from the payload of the anchoring
+/// transaction, which is proven against the latest authorized block.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchProof {
+    /// Latest authorized block in the blockchain.
+    pub latest_authorized_block: BlockProof,
+    /// Proof for the whole anchoring transactions table.
+    pub to_transactions_table: MapProof<Hash, Hash>,
+    /// Proof for the anchoring transaction which commits to the batch.
+    pub to_transaction: ListProof<btc::Transaction>,
+    /// Anchoring transactions total count.
+    pub transactions_count: u64,
+    /// Height of the first block in the batch.
+    pub first_height: Height,
+    /// Number of blocks in the batch.
+    pub blocks_count: u64,
+    /// Proof for the hash of the requested block in the list of the batch block hashes.
+    pub to_block_hash: ListProof<Hash>,
+    /// Proof of inclusion of the anchoring transaction in the Bitcoin blockchain
+    /// if the Bitcoin relay is configured and the synchronization worker has already
+    /// fetched the proof of the confirmed transaction.
+    pub bitcoin_proof: Option<btc::MerkleProof>,
+}
+
+/// Anchoring transaction with its decoded content.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AnchoringTransactionInfo {
+    /// Index of the transaction in the anchoring chain.
+    pub index: u64,
+    /// Transaction identifier.
+    pub txid: Hash,
+    /// Anchoring payload of the transaction.
+    pub payload: Option<btc::Payload>,
+    /// Value of the anchoring output in satoshis.
+    pub value: Option<u64>,
+    /// Anchoring transaction.
+    pub transaction: btc::Transaction,
+}
+
+/// A page of the anchoring transactions chain with the proof of existence.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TransactionsPage {
+    /// Latest authorized block in the blockchain.
+    pub latest_authorized_block: BlockProof,
+    /// Proof for the whole database table.
+    pub to_table: MapProof<Hash, Hash>,
+    /// Proof for the range of transactions in this table.
+    pub to_transactions: ListProof<btc::Transaction>,
+    /// Anchoring transactions total count.
+    pub transactions_count: u64,
+    /// Anchoring transactions in the page.
+    pub transactions: Vec<AnchoringTransactionInfo>,
+}
+
 /// A proof of existence for an anchored or a non-anchored Exonum block at the given height.
 #[derive(Debug, Serialize, Deserialize)]
 pub struct BlockHeaderProof {
@@ -69,6 +165,71 @@ pub struct BlockHeaderProof {
     pub to_block_header: ListProof<Hash>,
 }
 
+/// Signing progress of the single input of the anchoring proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProposalInput {
+    /// Identifier of the transaction spent by this input.
+    pub prev_txid: Hash,
+    /// Index of the spent output.
+    pub prev_output: u32,
+    /// Validators that have signed this input.
+    pub signed_by: Vec<ValidatorId>,
+    /// Validators whose signatures are still missing.
+    pub missing: Vec<ValidatorId>,
+}
+
+/// Anchoring transaction proposal which is being signed by the validators.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PendingProposal {
+    /// Proposed anchoring transaction.
+    pub transaction: btc::Transaction,
+    /// Identifier of the proposed anchoring transaction.
+    pub txid: Hash,
+    /// Number of signatures required for each input.
+    pub quorum: usize,
+    /// Signing progress of the proposal inputs.
+    pub inputs: Vec<ProposalInput>,
+}
+
+/// Kind of the current anchoring state.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AnchoringStateKind {
+    /// The usual anchoring workflow.
+    Regular,
+    /// The transition from the current anchoring address to the following one.
+    Transition,
+}
+
+/// Progress of the signing of the current anchoring proposal.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProposalProgress {
+    /// Identifier of the proposed anchoring transaction.
+    pub txid: Hash,
+    /// Number of the collected signatures for each input of the proposal.
+    pub collected_signatures: Vec<usize>,
+}
+
+/// Summary of the anchoring health at the current node.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AnchoringStatus {
+    /// Kind of the current anchoring state.
+    pub state: AnchoringStateKind,
+    /// Height of the latest anchored block.
+    pub latest_anchored_height: Option<Height>,
+    /// Height of the block which is going to be anchored next.
+    pub next_anchoring_height: Height,
+    /// Remaining balance of the anchoring wallet in satoshis.
+    pub balance: Option<u64>,
+    /// Progress of the signing of the current anchoring proposal if it exists.
+    pub proposal: Option<ProposalProgress>,
+    /// Error occurred during the building of the anchoring proposal.
+    pub proposal_error: Option<String>,
+    /// Number of confirmations of the latest anchoring transaction
+    /// if the Bitcoin relay is configured.
+    pub latest_confirmations: Option<u64>,
+}
+
 /// Public API specification for the Exonum Bitcoin anchoring service.
 pub trait PublicApi {
     /// Error type for the current public API implementation.
@@ -94,25 +255,104 @@ pub trait PublicApi {
         query: FindTransactionQuery,
     ) -> Result<Option<TransactionProof>, Self::Error>;
 
+    /// Returns the composite proof which links the Exonum block at the given height to the
+    /// anchoring transaction that covers it. If the Bitcoin relay is configured, the proof
+    /// also includes the inclusion proof of the anchoring transaction in the Bitcoin
+    /// blockchain once it is fetched during the synchronization following the first
+    /// request. Returns `None` if the block has not been anchored yet.
+    ///
+    /// `GET /{api_prefix}/v1/anchored_block_proof?height={height}`
+    fn anchored_block_proof(
+        &self,
+        query: HeightQuery,
+    ) -> Result<Option<AnchoredBlockProof>, Self::Error>;
+
+    /// Returns the proof which links the Exonum block at the given height to the batch root
+    /// committed to by the anchoring transaction. Similarly to the
+    /// [`anchored_block_proof`](#tymethod.anchored_block_proof), the inclusion proof of
+    /// the anchoring transaction in the Bitcoin blockchain is taken from the cache of
+    /// the synchronization worker. Returns `None` if the block has not been anchored yet
+    /// or the covering anchoring transaction is not a batch one.
+    ///
+    /// `GET /{api_prefix}/v1/batch_proof?height={height}`
+    fn batch_proof(&self, query: HeightQuery) -> Result<Option<BatchProof>, Self::Error>;
+
+    /// Returns the page of the anchoring transactions chain starting from the given index
+    /// with the proof of existence for the whole range. Returns `None` if the chain has
+    /// no transactions with such index. The page contains at most
+    /// [`MAX_TRANSACTIONS_PAGE_SIZE`] transactions.
+    ///
+    /// [`MAX_TRANSACTIONS_PAGE_SIZE`]: constant.MAX_TRANSACTIONS_PAGE_SIZE.html
+    ///
+    /// `GET /{api_prefix}/v1/transactions?from={from}&count={count}`
+    fn transactions(
+        &self,
+        query: TransactionsQuery,
+    ) -> Result<Option<TransactionsPage>, Self::Error>;
+
+    /// Returns the current anchoring proposal with the validators that have and
+    /// haven't signed each of its inputs. Returns `None` if there is no proposal
+    /// at the moment.
+    ///
+    /// `GET /{api_prefix}/v1/proposal`
+    fn pending_proposal(&self, _query: ()) -> Result<Option<PendingProposal>, Self::Error>;
+
+    /// Returns the current anchoring proposal as the partially signed Bitcoin transaction
+    /// (BIP 174) with the spent transactions and the redeem script for each input.
+    /// Returns `None` if there is no proposal at the moment.
+    ///
+    /// `GET /{api_prefix}/v1/proposal/psbt`
+    fn proposal_psbt(&self, _query: ()) -> Result<Option<btc::Psbt>, Self::Error>;
+
     /// A method that provides cryptographic proofs for Exonum blocks including those anchored to
     /// Bitcoin blockchain. The proof is an apparent evidence of availability of a certain Exonum
     /// block in the blockchain.
     ///
     /// `GET /{api_prefix}/v1/block_header_proof?height={height}`
     fn block_header_proof(&self, query: HeightQuery) -> Result<BlockHeaderProof, Self::Error>;
+
+    /// Returns the summary of the anchoring health at the current node.
+    ///
+    /// `GET /{api_prefix}/v1/status`
+    fn status(&self, _query: ()) -> Result<AnchoringStatus, Self::Error>;
+
+    /// Returns the local audit report of the anchoring chain.
+    ///
+    /// `GET /{api_prefix}/v1/audit`
+    fn audit(&self, _query: ()) -> Result<AuditReport, Self::Error>;
+
+    /// Returns the state of the synchronization with the Bitcoin network
+    /// if the Bitcoin relay is configured.
+    ///
+    /// `GET /{api_prefix}/v1/sync`
+    fn sync_state(&self, _query: ()) -> Result<Option<SyncState>, Self::Error>;
+}
+
+/// State of the current node which is available to the API in addition to the blockchain.
+#[derive(Debug, Clone)]
+struct NodeState {
+    audit_log: AuditLog,
+    sync_state: Option<SharedSyncState>,
+    proofs: Option<SharedProofCache>,
+}
+
+/// Public API implementation for the current node.
+struct ApiImpl<'a> {
+    state: &'a ServiceApiState,
+    node: &'a NodeState,
 }
 
-impl PublicApi for ServiceApiState {
+impl<'a> PublicApi for ApiImpl<'a> {
     type Error = api::Error;
 
     fn actual_address(&self, _query: ()) -> Result<btc::Address, Self::Error> {
-        let snapshot = self.snapshot();
+        let snapshot = self.state.snapshot();
         let schema = BtcAnchoringSchema::new(snapshot);
         Ok(schema.actual_configuration().anchoring_address())
     }
 
     fn following_address(&self, _query: ()) -> Result<Option<btc::Address>, Self::Error> {
-        let snapshot = self.snapshot();
+        let snapshot = self.state.snapshot();
         let schema = BtcAnchoringSchema::new(snapshot);
         Ok(schema
             .following_configuration()
@@ -123,7 +363,7 @@ impl PublicApi for ServiceApiState {
         &self,
         query: FindTransactionQuery,
     ) -> Result<Option<TransactionProof>, Self::Error> {
-        let snapshot = self.snapshot();
+        let snapshot = self.state.snapshot();
         let anchoring_schema = BtcAnchoringSchema::new(&snapshot);
         let tx_chain = anchoring_schema.anchoring_transactions_chain();
 
@@ -132,36 +372,7 @@ impl PublicApi for ServiceApiState {
         }
 
         let tx_index = if let Some(height) = query.height {
-            // Handmade binary search.
-            let f = |index| -> Ordering {
-                // index is always in [0, size), that means index is >= 0 and < size.
-                // index >= 0: by definition
-                // index < size: index = size / 2 + size / 4 + size / 8 ...
-                let other = tx_chain
-                    .get(index)
-                    .unwrap()
-                    .anchoring_payload()
-                    .unwrap()
-                    .block_height;
-                other.cmp(&height)
-            };
-
-            let mut base = 0;
-            let mut size = tx_chain.len();
-            while size > 1 {
-                let half = size / 2;
-                let mid = base + half;
-                let cmp = f(mid);
-                base = if cmp == Greater { base } else { mid };
-                size -= half;
-            }
-            // Don't forget to check base value.
-            let cmp = f(base);
-            if cmp == Equal {
-                base
-            } else {
-                cmp::min(base + (cmp == Less) as u64, tx_chain.len() - 1)
-            }
+            find_transaction_index(&tx_chain, height)
         } else {
             tx_chain.len() - 1
         };
@@ -183,8 +394,131 @@ impl PublicApi for ServiceApiState {
         }))
     }
 
+    fn anchored_block_proof(
+        &self,
+        query: HeightQuery,
+    ) -> Result<Option<AnchoredBlockProof>, Self::Error> {
+        anchored_block_proof(self.state, query, self.node.proofs.as_ref())
+    }
+
+    fn batch_proof(&self, query: HeightQuery) -> Result<Option<BatchProof>, Self::Error> {
+        batch_proof(self.state, query, self.node.proofs.as_ref())
+    }
+
+    fn transactions(
+        &self,
+        query: TransactionsQuery,
+    ) -> Result<Option<TransactionsPage>, Self::Error> {
+        if query.count == 0 {
+            return Err(api::Error::BadRequest(
+                "Transactions count must be positive".to_owned(),
+            ));
+        }
+
+        let snapshot = self.state.snapshot();
+        let anchoring_schema = BtcAnchoringSchema::new(&snapshot);
+        let tx_chain = anchoring_schema.anchoring_transactions_chain();
+
+        let transactions_count = tx_chain.len();
+        if query.from >= transactions_count {
+            return Ok(None);
+        }
+        let count = cmp::min(query.count, MAX_TRANSACTIONS_PAGE_SIZE);
+        let to = cmp::min(query.from.saturating_add(count), transactions_count);
+
+        let transactions = (query.from..to)
+            .map(|index| {
+                let transaction = tx_chain.get(index).unwrap();
+                AnchoringTransactionInfo {
+                    index,
+                    txid: transaction.id(),
+                    payload: transaction.anchoring_payload(),
+                    value: transaction.unspent_value(),
+                    transaction,
+                }
+            })
+            .collect();
+
+        let core_schema = CoreSchema::new(&snapshot);
+        let max_height = core_schema.block_hashes_by_height().len() - 1;
+        let latest_authorized_block = core_schema
+            .block_and_precommits(Height(max_height))
+            .unwrap();
+        let to_table: MapProof<Hash, Hash> =
+            core_schema.get_proof_to_service_table(BTC_ANCHORING_SERVICE_ID, 0);
+        let to_transactions = tx_chain.get_range_proof(query.from, to);
+
+        Ok(Some(TransactionsPage {
+            latest_authorized_block,
+            to_table,
+            to_transactions,
+            transactions_count,
+            transactions,
+        }))
+    }
+
+    fn proposal_psbt(&self, _query: ()) -> Result<Option<btc::Psbt>, Self::Error> {
+        let snapshot = self.state.snapshot();
+        let schema = BtcAnchoringSchema::new(&snapshot);
+        let actual_state = schema.actual_state();
+
+        let (proposal, inputs) = match schema.proposed_anchoring_transaction(&actual_state) {
+            Some(proposal) => proposal.map_err(|e| api::Error::InternalError(e.into()))?,
+            None => return Ok(None),
+        };
+        let redeem_script = actual_state.actual_configuration().redeem_script();
+        Ok(Some(btc::Psbt::from_anchoring_proposal(
+            &proposal,
+            &inputs,
+            &redeem_script,
+        )))
+    }
+
+    fn pending_proposal(&self, _query: ()) -> Result<Option<PendingProposal>, Self::Error> {
+        let snapshot = self.state.snapshot();
+        let schema = BtcAnchoringSchema::new(&snapshot);
+        let actual_state = schema.actual_state();
+
+        let transaction = match schema.proposed_anchoring_transaction(&actual_state) {
+            Some(proposal) => proposal.map_err(|e| api::Error::InternalError(e.into()))?.0,
+            None => return Ok(None),
+        };
+        let txid = transaction.id();
+        let redeem_script = actual_state.actual_configuration().redeem_script();
+        let validators_count = redeem_script.content().public_keys.len();
+
+        let inputs = transaction
+            .0
+            .input
+            .iter()
+            .zip(transaction.input_tx_ids())
+            .enumerate()
+            .map(|(index, (input, prev_txid))| {
+                let signatures =
+                    schema.input_signatures(&TxInputId::new(txid, index as u32), &redeem_script);
+                let missing = (0..validators_count as u16)
+                    .map(ValidatorId)
+                    .filter(|id| !signatures.contains(*id))
+                    .collect();
+                ProposalInput {
+                    prev_txid,
+                    prev_output: input.previous_output.vout,
+                    signed_by: signatures.validators().collect(),
+                    missing,
+                }
+            })
+            .collect();
+
+        Ok(Some(PendingProposal {
+            quorum: redeem_script.content().quorum,
+            transaction,
+            txid,
+            inputs,
+        }))
+    }
+
     fn block_header_proof(&self, query: HeightQuery) -> Result<BlockHeaderProof, Self::Error> {
-        let view = self.snapshot();
+        let view = self.state.snapshot();
         let core_schema = CoreSchema::new(&view);
         let anchoring_schema = BtcAnchoringSchema::new(&view);
 
@@ -203,13 +537,286 @@ impl PublicApi for ServiceApiState {
             to_block_header,
         })
     }
+
+    fn status(&self, _query: ()) -> Result<AnchoringStatus, Self::Error> {
+        let sync_state = self.sync_state(())?;
+        Ok(anchoring_status(self.state, sync_state.as_ref()))
+    }
+
+    fn audit(&self, _query: ()) -> Result<AuditReport, Self::Error> {
+        Ok(self.node.audit_log.read().unwrap().clone())
+    }
+
+    fn sync_state(&self, _query: ()) -> Result<Option<SyncState>, Self::Error> {
+        Ok(self
+            .node
+            .sync_state
+            .as_ref()
+            .map(|sync_state| sync_state.read().unwrap().clone()))
+    }
 }
 
-pub(crate) fn wire(builder: &mut ServiceApiBuilder) {
+fn anchoring_status(state: &ServiceApiState, sync_state: Option<&SyncState>) -> AnchoringStatus {
+    let snapshot = state.snapshot();
+    let schema = BtcAnchoringSchema::new(&snapshot);
+    let actual_state = schema.actual_state();
+    let latest_anchored_height = schema.latest_anchored_height();
+
+    let (proposal, proposal_error) = match schema.proposed_anchoring_transaction(&actual_state) {
+        Some(Ok((proposal, _))) => {
+            let txid = proposal.id();
+            let redeem_script = actual_state.actual_configuration().redeem_script();
+            let collected_signatures = (0..proposal.0.input.len() as u32)
+                .map(|input| {
+                    schema
+                        .input_signatures(&TxInputId::new(txid, input), &redeem_script)
+                        .len()
+                })
+                .collect();
+            let progress = ProposalProgress {
+                txid,
+                collected_signatures,
+            };
+            (Some(progress), None)
+        }
+        Some(Err(e)) => (None, Some(e.to_string())),
+        None => (None, None),
+    };
+
+    AnchoringStatus {
+        state: if actual_state.is_transition() {
+            AnchoringStateKind::Transition
+        } else {
+            AnchoringStateKind::Regular
+        },
+        latest_anchored_height,
+        next_anchoring_height: actual_state.following_anchoring_height(latest_anchored_height),
+        balance: schema
+            .anchoring_transactions_chain()
+            .last()
+            .and_then(|tx| tx.unspent_value()),
+        proposal,
+        proposal_error,
+        latest_confirmations: sync_state.and_then(|sync_state| sync_state.latest_confirmations),
+    }
+}
+
+/// Returns the index of the first anchoring transaction which anchors the block with
+/// the height that is greater or equal to the given one, or the index of the latest
+/// anchoring transaction if there is no such transaction.
+///
+/// The anchoring transactions chain must not be empty.
+fn find_transaction_index<T: AsRef<dyn Snapshot>>(
+    tx_chain: &ProofListIndex<T, btc::Transaction>,
+    height: Height,
+) -> u64 {
+    // Handmade binary search.
+    let f = |index| -> Ordering {
+        // index is always in [0, size), that means index is >= 0 and < size.
+        // index >= 0: by definition
+        // index < size: index = size / 2 + size / 4 + size / 8 ...
+        let other = tx_chain
+            .get(index)
+            .unwrap()
+            .anchoring_payload()
+            .unwrap()
+            .block_height;
+        other.cmp(&height)
+    };
+
+    let mut base = 0;
+    let mut size = tx_chain.len();
+    while size > 1 {
+        let half = size / 2;
+        let mid = base + half;
+        let cmp = f(mid);
+        base = if cmp == Greater { base } else { mid };
+        size -= half;
+    }
+    // Don't forget to check base value.
+    let cmp = f(base);
+    if cmp == Equal {
+        base
+    } else {
+        cmp::min(base + (cmp == Less) as u64, tx_chain.len() - 1)
+    }
+}
+
+fn anchored_block_proof(
+    state: &ServiceApiState,
+    query: HeightQuery,
+    proofs: Option<&SharedProofCache>,
+) -> Result<Option<AnchoredBlockProof>, api::Error> {
+    let snapshot = state.snapshot();
+    let anchoring_schema = BtcAnchoringSchema::new(&snapshot);
+    let tx_chain = anchoring_schema.anchoring_transactions_chain();
+
+    if tx_chain.is_empty() {
+        return Ok(None);
+    }
+
+    let tx_index = find_transaction_index(&tx_chain, Height(query.height));
+    let tx = tx_chain.get(tx_index).unwrap();
+    let anchored_height = tx.anchoring_payload().unwrap().block_height;
+    if anchored_height.0 < query.height {
+        return Ok(None);
+    }
+    let bitcoin_proof = proofs.and_then(|proofs| proofs.write().unwrap().get_or_request(&tx.id()));
+
+    let core_schema = CoreSchema::new(&snapshot);
+    let max_height = core_schema.block_hashes_by_height().len() - 1;
+    let latest_authorized_block = core_schema
+        .block_and_precommits(Height(max_height))
+        .unwrap();
+    let to_block_headers_table: MapProof<Hash, Hash> =
+        core_schema.get_proof_to_service_table(BTC_ANCHORING_SERVICE_ID, 3);
+    let to_block_headers = anchoring_schema
+        .anchored_blocks()
+        .get_range_proof(query.height, anchored_height.0 + 1);
+    let to_transactions_table: MapProof<Hash, Hash> =
+        core_schema.get_proof_to_service_table(BTC_ANCHORING_SERVICE_ID, 0);
+    let to_transaction = tx_chain.get_proof(tx_index);
+
+    Ok(Some(AnchoredBlockProof {
+        latest_authorized_block,
+        to_block_headers_table,
+        to_block_headers,
+        to_transactions_table,
+        to_transaction,
+        transactions_count: tx_chain.len(),
+        bitcoin_proof,
+    }))
+}
+
+fn batch_proof(
+    state: &ServiceApiState,
+    query: HeightQuery,
+    proofs: Option<&SharedProofCache>,
+) -> Result<Option<BatchProof>, api::Error> {
+    let snapshot = state.snapshot();
+    let anchoring_schema = BtcAnchoringSchema::new(&snapshot);
+    let tx_chain = anchoring_schema.anchoring_transactions_chain();
+
+    if tx_chain.is_empty() {
+        return Ok(None);
+    }
+
+    let height = Height(query.height);
+    let anchored_height = |index| {
+        tx_chain
+            .get(index)
+            .unwrap()
+            .anchoring_payload()
+            .unwrap()
+            .block_height
+    };
+    // The same block may be anchored several times, the batch belongs to the first one.
+    let mut tx_index = find_transaction_index(&tx_chain, height);
+    while tx_index > 0 && anchored_height(tx_index - 1) >= height {
+        tx_index -= 1;
+    }
+    let tx = tx_chain.get(tx_index).unwrap();
+    let payload = tx.anchoring_payload().unwrap();
+    if payload.block_height < height || payload.batch_root.is_none() {
+        return Ok(None);
+    }
+
+    let prev_anchored_height = tx_index.checked_sub(1).map(anchored_height);
+    let first_height = batch_first_height(prev_anchored_height, payload.block_height);
+    let to_block_hash = anchoring_schema
+        .batch_proof(first_height, payload.block_height, height)
+        .ok_or_else(|| api::Error::NotFound("Batch block not found".to_owned()))?;
+    let bitcoin_proof = proofs.and_then(|proofs| proofs.write().unwrap().get_or_request(&tx.id()));
+
+    let core_schema = CoreSchema::new(&snapshot);
+    let max_height = core_schema.block_hashes_by_height().len() - 1;
+    let latest_authorized_block = core_schema
+        .block_and_precommits(Height(max_height))
+        .unwrap();
+    let to_transactions_table: MapProof<Hash, Hash> =
+        core_schema.get_proof_to_service_table(BTC_ANCHORING_SERVICE_ID, 0);
+    let to_transaction = tx_chain.get_proof(tx_index);
+
+    Ok(Some(BatchProof {
+        latest_authorized_block,
+        to_transactions_table,
+        to_transaction,
+        transactions_count: tx_chain.len(),
+        first_height,
+        blocks_count: payload.block_height.0 - first_height.0 + 1,
+        to_block_hash,
+        bitcoin_proof,
+    }))
+}
+
+pub(crate) fn wire(
+    builder: &mut ServiceApiBuilder,
+    audit_log: AuditLog,
+    sync_state: Option<SharedSyncState>,
+    proofs: Option<SharedProofCache>,
+) {
+    let node = NodeState {
+        audit_log,
+        sync_state,
+        proofs,
+    };
+    // Binds the endpoint to the corresponding method of the API implementation.
+    macro_rules! endpoint {
+        ($method:ident, $query:ty, $output:ty) => {{
+            let node = node.clone();
+            move |state: &ServiceApiState, query: $query| -> Result<$output, api::Error> {
+                ApiImpl { state, node: &node }.$method(query)
+            }
+        }};
+    }
+
     builder
         .public_scope()
-        .endpoint("v1/address/actual", ServiceApiState::actual_address)
-        .endpoint("v1/address/following", ServiceApiState::following_address)
-        .endpoint("v1/transaction", ServiceApiState::find_transaction)
-        .endpoint("v1/block_header_proof", ServiceApiState::block_header_proof);
+        .endpoint(
+            "v1/address/actual",
+            endpoint!(actual_address, (), btc::Address),
+        )
+        .endpoint(
+            "v1/address/following",
+            endpoint!(following_address, (), Option<btc::Address>),
+        )
+        .endpoint(
+            "v1/transaction",
+            endpoint!(
+                find_transaction,
+                FindTransactionQuery,
+                Option<TransactionProof>
+            ),
+        )
+        .endpoint(
+            "v1/transactions",
+            endpoint!(transactions, TransactionsQuery, Option<TransactionsPage>),
+        )
+        .endpoint(
+            "v1/block_header_proof",
+            endpoint!(block_header_proof, HeightQuery, BlockHeaderProof),
+        )
+        .endpoint(
+            "v1/proposal",
+            endpoint!(pending_proposal, (), Option<PendingProposal>),
+        )
+        .endpoint(
+            "v1/proposal/psbt",
+            endpoint!(proposal_psbt, (), Option<btc::Psbt>),
+        )
+        .endpoint(
+            "v1/anchored_block_proof",
+            endpoint!(
+                anchored_block_proof,
+                HeightQuery,
+                Option<AnchoredBlockProof>
+            ),
+        )
+        .endpoint(
+            "v1/batch_proof",
+            endpoint!(batch_proof, HeightQuery, Option<BatchProof>),
+        )
+        .endpoint("v1/status", endpoint!(status, (), AnchoringStatus))
+        .endpoint("v1/audit", endpoint!(audit, (), AuditReport))
+        .endpoint("v1/sync", endpoint!(sync_state, (), Option<SyncState>));
 }
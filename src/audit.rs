@@ -0,0 +1,120 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Local audit of the anchoring chain performed by the non-validator nodes.
+
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+
+use std::sync::{Arc, RwLock};
+
+/// Minimal number of confirmations after which an anchoring transaction
+/// is considered as audited.
+pub const AUDIT_CONFIRMATIONS: u64 = 6;
+
+/// Shared audit report of the current node.
+pub(crate) type AuditLog = Arc<RwLock<AuditReport>>;
+
+/// Discrepancy between the anchoring chain stored in the Exonum blockchain
+/// and the expected one.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Fail)]
+pub enum Discrepancy {
+    /// Anchoring transaction does not contain a valid payload.
+    #[fail(
+        display = "Anchoring transaction {} does not contain a valid payload.",
+        txid
+    )]
+    MissingPayload {
+        /// Index of the transaction in the anchoring chain.
+        index: u64,
+        /// Transaction identifier.
+        txid: Hash,
+    },
+    /// Anchoring transaction does not spend the change output of the previous one.
+    #[fail(
+        display = "Anchoring transaction {} does not spend the previous one {}.",
+        txid, prev_txid
+    )]
+    BrokenChain {
+        /// Index of the transaction in the anchoring chain.
+        index: u64,
+        /// Transaction identifier.
+        txid: Hash,
+        /// Identifier of the previous anchoring transaction.
+        prev_txid: Hash,
+    },
+    /// Anchored block hash or the state commitment differs from the one stored in the blockchain.
+    #[fail(
+        display = "Anchoring transaction {} commits to the unknown block {} at height {}.",
+        txid, block_hash, block_height
+    )]
+    PayloadMismatch {
+        /// Index of the transaction in the anchoring chain.
+        index: u64,
+        /// Transaction identifier.
+        txid: Hash,
+        /// Anchored block height.
+        block_height: Height,
+        /// Anchored block hash.
+        block_hash: Hash,
+    },
+    /// Anchoring transaction is absent in the Bitcoin blockchain, but the following
+    /// anchoring transactions have been already created.
+    #[fail(
+        display = "Anchoring transaction {} is absent in the Bitcoin blockchain.",
+        txid
+    )]
+    MissingInBitcoin {
+        /// Index of the transaction in the anchoring chain.
+        index: u64,
+        /// Transaction identifier.
+        txid: Hash,
+    },
+}
+
+/// Result of the anchoring chain audit performed by the node.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct AuditReport {
+    /// Number of anchoring transactions from the beginning of the chain
+    /// which have been checked against the anchored blocks.
+    pub checked_transactions: u64,
+    /// Number of anchoring transactions from the beginning of the chain which have
+    /// at least [`AUDIT_CONFIRMATIONS`] confirmations in the Bitcoin blockchain.
+    /// It is only tracked if the Bitcoin relay is configured.
+    ///
+    /// [`AUDIT_CONFIRMATIONS`]: constant.AUDIT_CONFIRMATIONS.html
+    #[serde(default)]
+    pub confirmed_transactions: u64,
+    /// Identifier of the latest checked anchoring transaction. It is used to find out
+    /// whether the tail of the anchoring chain has been replaced.
+    #[serde(default)]
+    pub latest_checked: Option<Hash>,
+    /// Discrepancies found during the audit.
+    pub discrepancies: Vec<Discrepancy>,
+}
+
+impl AuditReport {
+    /// Records the given discrepancy if it has not been recorded earlier.
+    pub fn record(&mut self, discrepancy: Discrepancy) {
+        if !self.discrepancies.contains(&discrepancy) {
+            warn!("Anchoring audit: {}", discrepancy);
+            self.discrepancies.push(discrepancy);
+        }
+    }
+
+    /// Checks that no discrepancies have been found.
+    pub fn is_ok(&self) -> bool {
+        self.discrepancies.is_empty()
+    }
+}
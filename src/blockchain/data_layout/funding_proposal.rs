@@ -0,0 +1,27 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use btc::Transaction;
+use proto;
+
+/// Output of the funding transaction proposed by validators to be used
+/// as an input of the following anchoring transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert)]
+#[exonum(pb = "proto::FundingProposal")]
+pub struct FundingProposal {
+    /// Funding transaction.
+    pub transaction: Transaction,
+    /// Index of the output to the anchoring address.
+    pub output: u32,
+}
@@ -48,6 +48,15 @@ impl InputSignatures {
         self.content[index].is_some()
     }
 
+    /// Returns identifiers of the validators whose signatures are in the set.
+    pub fn validators<'a>(&'a self) -> impl Iterator<Item = ValidatorId> + 'a {
+        self.content
+            .iter()
+            .enumerate()
+            .filter(|(_, signature)| signature.is_some())
+            .map(|(index, _)| ValidatorId(index as u16))
+    }
+
     /// Returns the total count of signatures.
     pub fn len(&self) -> usize {
         self.content.iter().filter(|x| x.is_some()).count()
@@ -125,3 +134,15 @@ fn test_input_signatures_storage_value() {
     assert_eq!(signatures, signatures2);
     assert_eq!(signatures2.into_iter().collect::<Vec<_>>(), data);
 }
+
+#[test]
+fn test_input_signatures_validators() {
+    let mut signatures = InputSignatures::new(4);
+    signatures.insert(ValidatorId(3), b"signature3".to_vec());
+    signatures.insert(ValidatorId(1), b"signature1".to_vec());
+
+    assert_eq!(
+        signatures.validators().collect::<Vec<_>>(),
+        vec![ValidatorId(1), ValidatorId(3)]
+    );
+}
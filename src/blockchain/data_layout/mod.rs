@@ -14,8 +14,14 @@
 
 //! Additional data types for the BTC anchoring information schema.
 
+pub use self::funding_proposal::FundingProposal;
 pub use self::input_signatures::InputSignatures;
 pub use self::tx_input_id::TxInputId;
+pub use self::tx_output_id::TxOutputId;
+pub use self::validator_votes::ValidatorVotes;
 
+mod funding_proposal;
 mod input_signatures;
 mod tx_input_id;
+mod tx_output_id;
+mod validator_votes;
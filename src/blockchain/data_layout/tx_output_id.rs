@@ -0,0 +1,88 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use exonum::crypto::{self, CryptoHash, Hash};
+use exonum::storage::{HashedKey, StorageKey};
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+
+use std::io::{Cursor, Read, Write};
+
+/// Unique transaction output identifier composed of a transaction identifier
+/// and an output index.
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct TxOutputId {
+    /// Transaction identifier.
+    pub txid: Hash,
+    /// Transaction output index.
+    pub output: u32,
+}
+
+impl TxOutputId {
+    /// Creates a new identifier.
+    pub fn new(txid: Hash, output: u32) -> Self {
+        Self { txid, output }
+    }
+}
+
+impl StorageKey for TxOutputId {
+    fn size(&self) -> usize {
+        self.txid.size() + self.output.size()
+    }
+
+    fn read(inp: &[u8]) -> Self {
+        let mut reader = Cursor::new(inp);
+
+        let txid = {
+            let mut txid = [0_u8; 32];
+            let _ = reader.read(&mut txid).unwrap();
+            Hash::new(txid)
+        };
+        let output = reader.read_u32::<LittleEndian>().unwrap();
+        Self { txid, output }
+    }
+
+    fn write(&self, out: &mut [u8]) {
+        let mut writer = Cursor::new(out);
+        let _ = writer.write(self.txid.as_ref()).unwrap();
+        writer.write_u32::<LittleEndian>(self.output).unwrap();
+    }
+}
+
+impl CryptoHash for TxOutputId {
+    fn hash(&self) -> Hash {
+        let mut bytes = [0_u8; 36];
+        self.write(&mut bytes);
+        crypto::hash(bytes.as_ref())
+    }
+}
+
+impl HashedKey for TxOutputId {}
+
+#[test]
+fn test_tx_output_id_storage_key() {
+    let txout = TxOutputId {
+        txid: crypto::hash(&[1, 2, 3]),
+        output: 2,
+    };
+
+    let mut buf = vec![0u8; txout.size()];
+    txout.write(&mut buf);
+
+    let txout2 = TxOutputId::read(&buf);
+    assert_eq!(txout, txout2);
+
+    let buf_hash = crypto::hash(&buf);
+    assert_eq!(txout2.hash(), buf_hash);
+}
@@ -0,0 +1,102 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use exonum::crypto::{self, CryptoHash, Hash};
+use exonum::helpers::ValidatorId;
+use exonum::storage::StorageValue;
+
+use std::borrow::Cow;
+
+/// A set of votes of the validators ordered by the validators identifiers.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ValidatorVotes {
+    content: Vec<bool>,
+}
+
+impl ValidatorVotes {
+    /// Creates an empty votes set for the given validators count.
+    pub fn new(validators_count: usize) -> Self {
+        let content = vec![false; validators_count];
+        Self { content }
+    }
+
+    /// Inserts a vote from the validator with the given identifier.
+    pub fn insert(&mut self, id: ValidatorId) {
+        let index = id.0 as usize;
+        if index >= self.content.len() {
+            self.content.resize(index + 1, false);
+        }
+        self.content[index] = true;
+    }
+
+    /// Removes a vote of the validator with the given identifier.
+    pub fn remove(&mut self, id: ValidatorId) {
+        if let Some(vote) = self.content.get_mut(id.0 as usize) {
+            *vote = false;
+        }
+    }
+
+    /// Checks the existence of a vote from the validator with the given identifier.
+    pub fn contains(&self, id: ValidatorId) -> bool {
+        let index = id.0 as usize;
+        self.content.get(index).cloned().unwrap_or(false)
+    }
+
+    /// Returns the total count of votes.
+    pub fn len(&self) -> usize {
+        self.content.iter().filter(|x| **x).count()
+    }
+
+    /// Checks that votes set is empty.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+impl StorageValue for ValidatorVotes {
+    fn into_bytes(self) -> Vec<u8> {
+        self.content.into_iter().map(u8::from).collect()
+    }
+
+    fn from_bytes(value: Cow<[u8]>) -> Self {
+        let content = value.iter().map(|x| *x != 0).collect();
+        Self { content }
+    }
+}
+
+impl CryptoHash for ValidatorVotes {
+    fn hash(&self) -> Hash {
+        crypto::hash(&self.clone().into_bytes())
+    }
+}
+
+#[test]
+fn test_validator_votes_storage_value() {
+    let mut votes = ValidatorVotes::new(4);
+    assert!(votes.is_empty());
+    votes.insert(ValidatorId(3));
+    votes.insert(ValidatorId(1));
+    votes.insert(ValidatorId(1));
+    assert_eq!(votes.len(), 2);
+    assert!(votes.contains(ValidatorId(1)));
+    assert!(!votes.contains(ValidatorId(2)));
+    votes.remove(ValidatorId(3));
+    votes.remove(ValidatorId(5));
+    assert_eq!(votes.len(), 1);
+    assert!(!votes.contains(ValidatorId(3)));
+
+    let bytes = votes.clone().into_bytes();
+    let votes2 = ValidatorVotes::from_bytes(bytes.into());
+    assert_eq!(votes, votes2);
+}
@@ -16,7 +16,7 @@
 
 use btc;
 use exonum::blockchain::ExecutionError;
-use exonum::crypto::Hash;
+use exonum::crypto::{Hash, PublicKey};
 use exonum::helpers::ValidatorId;
 
 /// Possible errors during execution of the `Signature` transaction.
@@ -56,11 +56,105 @@ pub enum SignatureError {
     /// An error in transaction builder occurred.
     #[fail(display = "{}", _0)]
     TxBuilderError(btc::BuilderError),
+    /// Transaction author is not the validator with the given identifier.
+    #[fail(
+        display = "Transaction author {:?} is not the validator {}.",
+        author, validator_id
+    )]
+    UnexpectedAuthor {
+        /// Claimed validator identifier.
+        validator_id: ValidatorId,
+        /// Actual service key of the transaction author.
+        author: PublicKey,
+    },
     /// An unknown error occurred.
     #[fail(display = "Unknown error")]
     UnknownError,
 }
 
+/// Possible errors during execution of the `FeeBump` transaction.
+#[derive(Debug, Fail)]
+pub enum FeeBumpError {
+    /// Fee bumping is disabled in the actual anchoring configuration.
+    #[fail(display = "Fee bumping is disabled in the actual anchoring configuration.")]
+    Disabled,
+    /// Fee bump is requested for the transaction which is not the latest anchoring one.
+    #[fail(
+        display = "Fee bump is requested for the transaction {}, but the latest anchoring transaction is {:?}.",
+        received_id, expected_id
+    )]
+    Unexpected {
+        /// Identifier of the latest anchoring transaction.
+        expected_id: Option<Hash>,
+        /// Identifier of the transaction for which the fee bump is requested.
+        received_id: Hash,
+    },
+    /// The latest anchoring transaction doesn't signal the opt-in Replace-By-Fee.
+    #[fail(display = "Anchoring transaction {} is not replaceable.", _0)]
+    NotReplaceable(Hash),
+    /// Public key of validator with the given identifier is missing.
+    #[fail(display = "Public key of validator {} is missing.", _0)]
+    MissingPublicKey {
+        /// Validator identifier.
+        validator_id: ValidatorId,
+    },
+    /// Transaction author is not the validator with the given identifier.
+    #[fail(
+        display = "Transaction author {:?} is not the validator {}.",
+        author, validator_id
+    )]
+    UnexpectedAuthor {
+        /// Claimed validator identifier.
+        validator_id: ValidatorId,
+        /// Actual service key of the transaction author.
+        author: PublicKey,
+    },
+}
+
+/// Possible errors during execution of the `FundingProposal` transaction.
+#[derive(Debug, Fail)]
+pub enum FundingError {
+    /// Output of the funding transaction doesn't pay to the actual anchoring address.
+    #[fail(
+        display = "Output {} of the funding transaction {} doesn't pay to the actual anchoring address.",
+        output, txid
+    )]
+    UnsuitableOutput {
+        /// Funding transaction identifier.
+        txid: Hash,
+        /// Output index.
+        output: u32,
+    },
+    /// Output of the funding transaction has been already spent by the anchoring chain.
+    #[fail(
+        display = "Output {} of the funding transaction {} has been already spent.",
+        output, txid
+    )]
+    AlreadySpent {
+        /// Funding transaction identifier.
+        txid: Hash,
+        /// Output index.
+        output: u32,
+    },
+    /// Public key of validator with the given identifier is missing.
+    #[fail(display = "Public key of validator {} is missing.", _0)]
+    MissingPublicKey {
+        /// Validator identifier.
+        validator_id: ValidatorId,
+    },
+    /// Transaction author is not the validator with the given identifier.
+    #[fail(
+        display = "Transaction author {:?} is not the validator {}.",
+        author, validator_id
+    )]
+    UnexpectedAuthor {
+        /// Claimed validator identifier.
+        validator_id: ValidatorId,
+        /// Actual service key of the transaction author.
+        author: PublicKey,
+    },
+}
+
 /// Error codes for the BTC anchoring transactions.
 #[derive(Debug)]
 pub enum ErrorCode {
@@ -76,6 +170,18 @@ pub enum ErrorCode {
     VerificationFailed = 5,
     /// [description](SignatureError.t.html#variant.TxBuilderError)
     TxBuilderError = 6,
+    /// [description](SignatureError.t.html#variant.UnexpectedAuthor)
+    UnexpectedAuthor = 7,
+    /// [description](FeeBumpError.t.html#variant.Disabled)
+    FeeBumpDisabled = 8,
+    /// [description](FeeBumpError.t.html#variant.Unexpected)
+    UnexpectedFeeBump = 9,
+    /// [description](FeeBumpError.t.html#variant.NotReplaceable)
+    NotReplaceable = 10,
+    /// [description](FundingError.t.html#variant.UnsuitableOutput)
+    UnsuitableFundingOutput = 11,
+    /// [description](FundingError.t.html#variant.AlreadySpent)
+    FundingAlreadySpent = 12,
     /// [description](SignatureError.t.html#variant.UnknownError)
     UnknownError = 255,
 }
@@ -89,6 +195,7 @@ impl SignatureError {
             SignatureError::NoSuchInput { .. } => ErrorCode::NoSuchInput,
             SignatureError::VerificationFailed => ErrorCode::VerificationFailed,
             SignatureError::TxBuilderError(..) => ErrorCode::TxBuilderError,
+            SignatureError::UnexpectedAuthor { .. } => ErrorCode::UnexpectedAuthor,
             _ => ErrorCode::UnknownError,
         }
     }
@@ -100,3 +207,40 @@ impl From<SignatureError> for ExecutionError {
         Self::with_description(value.code() as u8, description)
     }
 }
+
+impl FeeBumpError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            FeeBumpError::Disabled => ErrorCode::FeeBumpDisabled,
+            FeeBumpError::Unexpected { .. } => ErrorCode::UnexpectedFeeBump,
+            FeeBumpError::NotReplaceable(..) => ErrorCode::NotReplaceable,
+            FeeBumpError::MissingPublicKey { .. } => ErrorCode::MissingPublicKey,
+            FeeBumpError::UnexpectedAuthor { .. } => ErrorCode::UnexpectedAuthor,
+        }
+    }
+}
+
+impl From<FeeBumpError> for ExecutionError {
+    fn from(value: FeeBumpError) -> Self {
+        let description = format!("{}", value);
+        Self::with_description(value.code() as u8, description)
+    }
+}
+
+impl FundingError {
+    fn code(&self) -> ErrorCode {
+        match self {
+            FundingError::UnsuitableOutput { .. } => ErrorCode::UnsuitableFundingOutput,
+            FundingError::AlreadySpent { .. } => ErrorCode::FundingAlreadySpent,
+            FundingError::MissingPublicKey { .. } => ErrorCode::MissingPublicKey,
+            FundingError::UnexpectedAuthor { .. } => ErrorCode::UnexpectedAuthor,
+        }
+    }
+}
+
+impl From<FundingError> for ExecutionError {
+    fn from(value: FundingError) -> Self {
+        let description = format!("{}", value);
+        Self::with_description(value.code() as u8, description)
+    }
+}
@@ -19,6 +19,8 @@ pub use self::transactions::Transactions;
 
 use exonum::helpers::Height;
 
+use std::cmp;
+
 use bitcoin::blockdata::script::Script;
 use btc_transaction_utils::multisig::RedeemScript;
 use btc_transaction_utils::p2wsh;
@@ -31,6 +33,18 @@ pub mod errors;
 pub mod schema;
 pub mod transactions;
 
+/// Returns the height of the first block of the batch anchored at the given height.
+/// The batch starts right after the previous anchored block, or includes only the
+/// anchored block if it has been anchored again (e.g. during the transition).
+pub fn batch_first_height(
+    previous_anchored_height: Option<Height>,
+    anchoring_height: Height,
+) -> Height {
+    previous_anchored_height.map_or_else(Height::zero, |height| {
+        cmp::min(height.next(), anchoring_height)
+    })
+}
+
 /// Current state of the BTC anchoring service.
 #[derive(Debug, Clone)]
 pub enum BtcAnchoringState {
@@ -15,19 +15,24 @@
 //! Information schema for the btc anchoring service.
 
 use exonum::blockchain::{Schema, StoredConfiguration};
-use exonum::crypto::Hash;
+use exonum::crypto::{CryptoHash, Hash};
 use exonum::helpers::Height;
-use exonum::storage::{Fork, ProofListIndex, ProofMapIndex, Snapshot};
+use exonum::storage::{
+    Database, Fork, ListProof, MemoryDB, ProofListIndex, ProofMapIndex, Snapshot,
+};
 
 use btc_transaction_utils::multisig::RedeemScript;
 use serde_json;
 
-use btc::{BtcAnchoringTransactionBuilder, BuilderError, Transaction};
-use config::GlobalConfig;
+use btc::{
+    self, BtcAnchoringTransactionBuilder, BuilderError, ChainId, Payload, PayloadVersion,
+    Transaction,
+};
+use config::{byzantine_quorum, FeePolicy, GlobalConfig};
 use BTC_ANCHORING_SERVICE_NAME;
 
 use super::data_layout::*;
-use super::BtcAnchoringState;
+use super::{batch_first_height, BtcAnchoringState};
 
 /// Defines `&str` constants with given name and value.
 macro_rules! define_names {
@@ -44,7 +49,12 @@ define_names!(
     TRANSACTIONS_CHAIN => "transactions_chain";
     TRANSACTION_SIGNATURES => "transaction_signatures";
     SPENT_FUNDING_TRANSACTIONS => "spent_funding_transactions";
+    SPENT_FUNDING_OUTPUTS => "spent_funding_outputs";
     ANCHORED_BLOCKS => "anchored_blocks";
+    FEE_BUMP_VOTES => "fee_bump_votes";
+    FUNDING_PROPOSAL_VOTES => "funding_proposal_votes";
+    APPROVED_FUNDING_PROPOSALS => "approved_funding_proposals";
+    BATCH_BLOCKS => "batch_blocks";
 );
 
 /// Information schema for `exonum-btc-anchoring`.
@@ -64,11 +74,24 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
         ProofListIndex::new(TRANSACTIONS_CHAIN, &self.snapshot)
     }
 
-    /// Returns the table that contains already spent funding transactions.
+    /// Returns the table that contains funding transactions, which have been spent
+    /// before the spent outputs were tracked separately.
     pub fn spent_funding_transactions(&self) -> ProofMapIndex<&T, Hash, Transaction> {
         ProofMapIndex::new(SPENT_FUNDING_TRANSACTIONS, &self.snapshot)
     }
 
+    /// Returns the table that contains already spent outputs of the funding transactions.
+    pub fn spent_funding_outputs(&self) -> ProofMapIndex<&T, TxOutputId, Transaction> {
+        ProofMapIndex::new(SPENT_FUNDING_OUTPUTS, &self.snapshot)
+    }
+
+    /// Checks that the given output of the funding transaction has been already spent
+    /// by the anchoring chain.
+    pub fn is_spent_funding_output(&self, output_id: &TxOutputId) -> bool {
+        self.spent_funding_outputs().contains(output_id)
+            || self.spent_funding_transactions().contains(&output_id.txid)
+    }
+
     /// Returns the table that contains signatures for the given transaction input.
     pub fn transaction_signatures(&self) -> ProofMapIndex<&T, TxInputId, InputSignatures> {
         ProofMapIndex::new(TRANSACTION_SIGNATURES, &self.snapshot)
@@ -79,6 +102,24 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
         ProofListIndex::new(ANCHORED_BLOCKS, &self.snapshot)
     }
 
+    /// Returns the table that contains votes of validators for the replacement
+    /// of the given anchoring transaction.
+    pub fn fee_bump_votes(&self) -> ProofMapIndex<&T, Hash, ValidatorVotes> {
+        ProofMapIndex::new(FEE_BUMP_VOTES, &self.snapshot)
+    }
+
+    /// Returns the table that contains votes of validators for the funding proposals
+    /// with the given hashes.
+    pub fn funding_proposal_votes(&self) -> ProofMapIndex<&T, Hash, ValidatorVotes> {
+        ProofMapIndex::new(FUNDING_PROPOSAL_VOTES, &self.snapshot)
+    }
+
+    /// Returns the list of funding proposals approved by validators in the order
+    /// of their approval.
+    pub fn approved_funding_proposals(&self) -> ProofListIndex<&T, FundingProposal> {
+        ProofListIndex::new(APPROVED_FUNDING_PROPOSALS, &self.snapshot)
+    }
+
     /// Returns hashes of the stored tables.
     pub fn state_hash(&self) -> Vec<Hash> {
         vec![
@@ -86,6 +127,10 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
             self.spent_funding_transactions().merkle_root(),
             self.transaction_signatures().merkle_root(),
             self.anchored_blocks().merkle_root(),
+            self.fee_bump_votes().merkle_root(),
+            self.funding_proposal_votes().merkle_root(),
+            self.approved_funding_proposals().merkle_root(),
+            self.spent_funding_outputs().merkle_root(),
         ]
     }
 
@@ -135,9 +180,19 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
         &self,
         actual_state: &BtcAnchoringState,
     ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
+        // Replaces the stuck anchoring transaction before anchoring the following block.
+        // If the replacement cannot be built, the following anchoring transaction spends
+        // the stuck one as usual.
+        match self.proposed_fee_bump(actual_state) {
+            Some(Ok(replacement)) => return Some(Ok(replacement)),
+            Some(Err(e)) => trace!("Unable to replace the stuck anchoring transaction: {}", e),
+            None => {}
+        }
+
         let config = actual_state.actual_configuration();
         let unspent_anchoring_transaction = self.anchoring_transactions_chain().last();
-        let unspent_funding_transaction = self.unspent_funding_transaction();
+        let unspent_funding_transactions = self.unspent_funding_transactions();
+        let unspent_funding_proposals = self.unspent_funding_proposals();
 
         let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
         // First anchoring transaction doesn't have previous.
@@ -162,7 +217,7 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
             }
 
             if let Err(e) = builder.prev_tx(tx) {
-                if unspent_funding_transaction.is_none() {
+                if unspent_funding_transactions.is_empty() && unspent_funding_proposals.is_empty() {
                     return Some(Err(e));
                 }
                 error!("Anchoring is broken: '{}'. Will try to recover", e);
@@ -170,11 +225,17 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
             }
         }
 
-        if let Some(tx) = unspent_funding_transaction {
+        for tx in unspent_funding_transactions {
             if let Err(e) = builder.additional_funds(tx) {
                 return Some(Err(e));
             }
         }
+        for proposal in unspent_funding_proposals {
+            let out = proposal.output as usize;
+            if let Err(e) = builder.additional_funds_output(proposal.transaction, out) {
+                return Some(Err(e));
+            }
+        }
 
         // Adds corresponding payload.
         let latest_anchored_height = self.latest_anchored_height();
@@ -184,7 +245,18 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
             Schema::new(&self.snapshot).block_hash_by_height(anchoring_height)?;
 
         builder.payload(anchoring_height, anchoring_block_hash);
-        builder.fee(config.transaction_fee);
+        if config.batch_anchoring {
+            let first_height = batch_first_height(latest_anchored_height, anchoring_height);
+            builder.batch_root(self.batch_root(first_height, anchoring_height)?);
+        } else if config.include_chain_id {
+            // The chain identifier takes the place of the state commitment.
+            builder.chain_id(self.chain_id()?);
+        } else if config.payload_version != PayloadVersion::V1 {
+            builder.state_commitment(self.state_commitment(anchoring_height)?);
+        }
+        builder.payload_version(config.payload_version);
+        builder.fee(self.fee_rate(config, anchoring_height));
+        builder.replace_by_fee(config.replace_by_fee);
 
         // Creates anchoring proposal.
         Some(builder.create())
@@ -198,17 +270,217 @@ impl<T: AsRef<dyn Snapshot>> BtcAnchoringSchema<T> {
         self.proposed_anchoring_transaction(&actual_state)
     }
 
-    /// Returns the unspent funding transaction if it is exist.
-    pub fn unspent_funding_transaction(&self) -> Option<Transaction> {
-        let tx_candidate = self.actual_configuration().funding_transaction?;
-        let txid = tx_candidate.id();
-        if self.spent_funding_transactions().contains(&txid) {
-            None
+    /// Returns the latest anchoring transaction if validators have agreed that it is stuck
+    /// in the Bitcoin mempool and it should be replaced.
+    pub fn pending_fee_bump(&self) -> Option<Transaction> {
+        let config = self.actual_configuration();
+        config.active_fee_bump()?;
+
+        let tx = self.anchoring_transactions_chain().last()?;
+        let votes = self.fee_bump_votes().get(&tx.id())?;
+        if votes.len() >= byzantine_quorum(config.public_keys.len()) {
+            Some(tx)
         } else {
-            Some(tx_candidate)
+            None
         }
     }
 
+    /// Returns the replacement of the stuck anchoring transaction with the increased fee
+    /// if validators have agreed to replace it. Replacement is possible only in the
+    /// regular anchoring state.
+    pub fn proposed_fee_bump(
+        &self,
+        actual_state: &BtcAnchoringState,
+    ) -> Option<Result<(Transaction, Vec<Transaction>), BuilderError>> {
+        if !actual_state.is_regular() {
+            return None;
+        }
+
+        let config = actual_state.actual_configuration();
+        let fee_bump = config.active_fee_bump()?;
+        let tx = self.pending_fee_bump()?;
+        let input_transactions = self.latest_transaction_inputs()?;
+
+        Some(btc::bump_fee(
+            &config.redeem_script(),
+            &tx,
+            input_transactions,
+            fee_bump.fee_increment,
+        ))
+    }
+
+    /// Returns the fee per virtual byte for the anchoring transaction at the given height
+    /// according to the fee policy of the given configuration.
+    pub fn fee_rate(&self, config: &GlobalConfig, anchoring_height: Height) -> u64 {
+        let latest_fee_rate = match config.fee_policy {
+            FeePolicy::Bounded { .. } => self.latest_fee_rate(),
+            _ => None,
+        };
+        config.fee_policy.resolve(anchoring_height, latest_fee_rate)
+    }
+
+    /// Returns the fee per virtual byte paid by the latest anchoring transaction
+    /// if all of its inputs are known.
+    pub fn latest_fee_rate(&self) -> Option<u64> {
+        let tx = self.anchoring_transactions_chain().last()?;
+        let input_transactions = self.latest_transaction_inputs()?;
+
+        let input_value =
+            tx.0.input
+                .iter()
+                .zip(&input_transactions)
+                .filter_map(|(input, input_tx)| {
+                    input_tx
+                        .0
+                        .output
+                        .get(input.previous_output.vout as usize)
+                        .map(|out| out.value)
+                })
+                .sum::<u64>();
+        let output_value = tx.0.output.iter().map(|out| out.value).sum::<u64>();
+        Some(input_value.saturating_sub(output_value) / tx.vsize())
+    }
+
+    /// Returns the transactions spent by the latest anchoring transaction if all
+    /// of them are known.
+    fn latest_transaction_inputs(&self) -> Option<Vec<Transaction>> {
+        let anchoring_txs = self.anchoring_transactions_chain();
+        let tx = anchoring_txs.last()?;
+        let prev_tx = anchoring_txs
+            .len()
+            .checked_sub(2)
+            .and_then(|index| anchoring_txs.get(index));
+        tx.spent_outputs()
+            .into_iter()
+            .map(|(txid, output)| match prev_tx {
+                Some(ref prev_tx) if prev_tx.id() == txid => Some(prev_tx.clone()),
+                _ => self
+                    .spent_funding_outputs()
+                    .get(&TxOutputId::new(txid, output))
+                    .or_else(|| self.spent_funding_transactions().get(&txid)),
+            })
+            .collect()
+    }
+
+    /// Returns the unspent funding transactions in the order in which they
+    /// are specified in the actual configuration.
+    pub fn unspent_funding_transactions(&self) -> Vec<Transaction> {
+        let mut unspent_funding_transactions: Vec<Transaction> = Vec::new();
+        for tx in self.actual_configuration().funding_transactions {
+            let txid = tx.id();
+            let is_known = unspent_funding_transactions
+                .iter()
+                .any(|unspent_tx| unspent_tx.id() == txid);
+            // The funding transaction may be carried over from the previous configuration
+            // with the other anchoring address, thus it is spent if any of its outputs is.
+            let is_spent = (0..tx.0.output.len())
+                .any(|output| self.is_spent_funding_output(&TxOutputId::new(txid, output as u32)));
+            if !is_known && !is_spent {
+                unspent_funding_transactions.push(tx);
+            }
+        }
+        unspent_funding_transactions
+    }
+
+    /// Returns the approved funding proposals which are not spent yet and pay to the
+    /// actual anchoring address. Funding transactions specified in the actual
+    /// configuration take precedence over the proposals.
+    pub fn unspent_funding_proposals(&self) -> Vec<FundingProposal> {
+        let config = self.actual_configuration();
+        let script_pubkey = config.redeem_script().as_ref().to_v0_p2wsh();
+
+        let mut unspent_funding_proposals: Vec<FundingProposal> = Vec::new();
+        for proposal in self.approved_funding_proposals().iter() {
+            let output_id = TxOutputId::new(proposal.transaction.id(), proposal.output);
+            let is_suitable = proposal
+                .transaction
+                .0
+                .output
+                .get(proposal.output as usize)
+                .map_or(false, |out| out.script_pubkey == script_pubkey);
+            let is_known = config
+                .funding_transactions
+                .iter()
+                .filter_map(|tx| {
+                    let (output, _) = tx.find_out(&script_pubkey)?;
+                    Some(TxOutputId::new(tx.id(), output as u32))
+                })
+                .chain(
+                    unspent_funding_proposals
+                        .iter()
+                        .map(|p| TxOutputId::new(p.transaction.id(), p.output)),
+                )
+                .any(|known_id| known_id == output_id);
+            if is_suitable && !is_known && !self.is_spent_funding_output(&output_id) {
+                unspent_funding_proposals.push(proposal);
+            }
+        }
+        unspent_funding_proposals
+    }
+
+    /// Returns the commitment to the state hash of the block at the given height and
+    /// the hash of the configuration, which is actual at this height.
+    pub fn state_commitment(&self, height: Height) -> Option<Hash> {
+        let core_schema = Schema::new(&self.snapshot);
+        let block_hash = core_schema.block_hash_by_height(height)?;
+        let block = core_schema.blocks().get(&block_hash)?;
+        let config_hash = core_schema.configuration_by_height(height).hash();
+        Some(Payload::state_commitment_for(
+            block.state_hash(),
+            &config_hash,
+        ))
+    }
+
+    /// Returns the identifier of the blockchain derived from its genesis block hash.
+    pub fn chain_id(&self) -> Option<ChainId> {
+        let genesis_hash = Schema::new(&self.snapshot).block_hash_by_height(Height::zero())?;
+        Some(ChainId::from_genesis_hash(&genesis_hash))
+    }
+
+    /// Returns the Merkle root of the hashes of the blocks in the given range of heights
+    /// inclusive, which is committed to by the batch anchoring payload.
+    pub fn batch_root(&self, first_height: Height, last_height: Height) -> Option<Hash> {
+        let db = MemoryDB::new();
+        let mut fork = db.fork();
+        Some(
+            self.batch_index(&mut fork, first_height, last_height)?
+                .merkle_root(),
+        )
+    }
+
+    /// Returns the proof of the hash of the block at the given height in the list of
+    /// block hashes of the batch with the given range of heights.
+    pub fn batch_proof(
+        &self,
+        first_height: Height,
+        last_height: Height,
+        height: Height,
+    ) -> Option<ListProof<Hash>> {
+        if height < first_height || height > last_height {
+            return None;
+        }
+
+        let db = MemoryDB::new();
+        let mut fork = db.fork();
+        let index = self.batch_index(&mut fork, first_height, last_height)?;
+        Some(index.get_proof(height.0 - first_height.0))
+    }
+
+    /// Fills the temporary list with the hashes of the blocks of the batch.
+    fn batch_index<'a>(
+        &self,
+        fork: &'a mut Fork,
+        first_height: Height,
+        last_height: Height,
+    ) -> Option<ProofListIndex<&'a mut Fork, Hash>> {
+        let core_schema = Schema::new(&self.snapshot);
+        let mut index = ProofListIndex::new(BATCH_BLOCKS, fork);
+        for height in first_height.0..=last_height.0 {
+            index.push(core_schema.block_hash_by_height(Height(height))?);
+        }
+        Some(index)
+    }
+
     /// Returns the height of the latest anchored block.
     pub fn latest_anchored_height(&self) -> Option<Height> {
         let tx = self.anchoring_transactions_chain().last()?;
@@ -240,12 +512,22 @@ impl<'a> BtcAnchoringSchema<&'a mut Fork> {
     /// Mutable variant of the [`spent_funding_transactions`][1] index.
     ///
     /// [1]: struct.BtcAnchoringSchema.html#method.spent_funding_transactions
+    #[deprecated(note = "Spent funding outputs are tracked by the `spent_funding_outputs` index.")]
     pub fn spent_funding_transactions_mut(
         &mut self,
     ) -> ProofMapIndex<&mut Fork, Hash, Transaction> {
         ProofMapIndex::new(SPENT_FUNDING_TRANSACTIONS, &mut self.snapshot)
     }
 
+    /// Mutable variant of the [`spent_funding_outputs`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.spent_funding_outputs
+    pub fn spent_funding_outputs_mut(
+        &mut self,
+    ) -> ProofMapIndex<&mut Fork, TxOutputId, Transaction> {
+        ProofMapIndex::new(SPENT_FUNDING_OUTPUTS, &mut self.snapshot)
+    }
+
     /// Mutable variant of the [`anchored_blocks`][1] index.
     ///
     /// [1]: struct.BtcAnchoringSchema.html#method.anchored_blocks
@@ -261,4 +543,25 @@ impl<'a> BtcAnchoringSchema<&'a mut Fork> {
     pub fn anchored_blocks_mut(&mut self) -> ProofListIndex<&mut Fork, Hash> {
         ProofListIndex::new(ANCHORED_BLOCKS, &mut self.snapshot)
     }
+
+    /// Mutable variant of the [`funding_proposal_votes`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.funding_proposal_votes
+    pub fn funding_proposal_votes_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, ValidatorVotes> {
+        ProofMapIndex::new(FUNDING_PROPOSAL_VOTES, &mut self.snapshot)
+    }
+
+    /// Mutable variant of the [`approved_funding_proposals`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.approved_funding_proposals
+    pub fn approved_funding_proposals_mut(&mut self) -> ProofListIndex<&mut Fork, FundingProposal> {
+        ProofListIndex::new(APPROVED_FUNDING_PROPOSALS, &mut self.snapshot)
+    }
+
+    /// Mutable variant of the [`fee_bump_votes`][1] index.
+    ///
+    /// [1]: struct.BtcAnchoringSchema.html#method.fee_bump_votes
+    pub fn fee_bump_votes_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, ValidatorVotes> {
+        ProofMapIndex::new(FEE_BUMP_VOTES, &mut self.snapshot)
+    }
 }
@@ -15,17 +15,19 @@
 //! BTC anchoring transactions.
 
 use exonum::{
-    blockchain::{ExecutionResult, Transaction, TransactionContext},
+    blockchain::{ExecutionResult, Schema, Transaction, TransactionContext},
+    crypto::{CryptoHash, Hash},
     helpers::ValidatorId,
 };
 
 use btc_transaction_utils::{p2wsh::InputSigner, InputSignature, TxInRef};
 use secp256k1::Secp256k1;
 
-use super::data_layout::TxInputId;
-use super::errors::SignatureError;
+use super::data_layout::{FundingProposal, TxInputId, TxOutputId, ValidatorVotes};
+use super::errors::{FeeBumpError, FundingError, SignatureError};
 use super::BtcAnchoringSchema;
 use btc;
+use config::byzantine_quorum;
 use proto;
 
 /// Exonum message with the signature for the new anchoring transaction.
@@ -42,11 +44,53 @@ pub struct TxSignature {
     pub input_signature: btc::InputSignature,
 }
 
+/// Exonum message with the vote of the validator for the replacement of the
+/// latest anchoring transaction which is stuck in the Bitcoin mempool.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert)]
+#[exonum(pb = "proto::TxFeeBump")]
+pub struct TxFeeBump {
+    /// Public key index in the anchoring public keys list.
+    pub validator: ValidatorId,
+    /// Identifier of the stuck anchoring transaction.
+    pub txid: Hash,
+    /// Withdraws the vote of the validator, since the transaction has been confirmed
+    /// in the Bitcoin blockchain and it should not be replaced anymore.
+    pub cancel: bool,
+}
+
+/// Exonum message with the vote of the validator for the funding transaction output
+/// which should be used by the following anchoring transaction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, ProtobufConvert)]
+#[exonum(pb = "proto::TxFundingProposal")]
+pub struct TxFundingProposal {
+    /// Public key index in the anchoring public keys list.
+    pub validator: ValidatorId,
+    /// Funding transaction.
+    pub transaction: btc::Transaction,
+    /// Index of the output to the anchoring address.
+    pub output: u32,
+}
+
 /// Exonum BTC anchoring transactions.
 #[derive(Serialize, Deserialize, Clone, Debug, TransactionSet)]
 pub enum Transactions {
     /// Exonum message with the signature for the new anchoring transaction.
     Signature(TxSignature),
+    /// Exonum message with the vote for the stuck anchoring transaction replacement.
+    FeeBump(TxFeeBump),
+    /// Exonum message with the vote for the funding transaction output.
+    FundingProposal(TxFundingProposal),
+}
+
+/// Checks that the transaction author is the validator with the given identifier.
+fn is_validator_author(context: &mut TransactionContext, validator: ValidatorId) -> bool {
+    let author = context.author();
+    let service_key = Schema::new(context.fork())
+        .actual_configuration()
+        .validator_keys
+        .get(validator.0 as usize)
+        .map(|keys| keys.service_key);
+    service_key == Some(author)
 }
 
 impl TxSignature {
@@ -61,7 +105,14 @@ impl TxSignature {
 
 impl Transaction for TxSignature {
     fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
-        // TODO Checks that transaction author is validator.
+        if !is_validator_author(&mut context, self.validator) {
+            return Err(SignatureError::UnexpectedAuthor {
+                validator_id: self.validator,
+                author: context.author(),
+            }
+            .into());
+        }
+
         let tx = &self.transaction;
         let mut schema = BtcAnchoringSchema::new(context.fork());
         // Checks that the number of signatures is sufficient to spend.
@@ -158,14 +209,170 @@ impl Transaction for TxSignature {
             info!("balance: {}", tx.0.output[0].value);
             trace!("Anchoring txhex: {}", tx.to_string());
 
+            // Replaces the stuck transaction in the tail of anchoring transactions
+            // only if the finalized transaction spends the same inputs.
+            let stuck_tx = schema.pending_fee_bump().filter(|stuck_tx| {
+                schema.actual_state().is_regular() && stuck_tx.input_tx_ids() == tx.input_tx_ids()
+            });
+            if let Some(stuck_tx) = stuck_tx {
+                info!("replaces: {}", stuck_tx.id().to_hex());
+                let mut anchoring_txs = schema.anchoring_transactions_chain_mut();
+                let index = anchoring_txs.len() - 1;
+                anchoring_txs.set(index, tx);
+                return Ok(());
+            }
+
+            // Marks the consumed outputs of the funding transactions as spent.
+            let funding_transactions = schema
+                .unspent_funding_proposals()
+                .into_iter()
+                .map(|proposal| proposal.transaction)
+                .chain(schema.unspent_funding_transactions())
+                .collect::<Vec<_>>();
+            for (txid, output) in tx.spent_outputs() {
+                if let Some(funding_tx) = funding_transactions.iter().find(|tx| tx.id() == txid) {
+                    schema
+                        .spent_funding_outputs_mut()
+                        .put(&TxOutputId::new(txid, output), funding_tx.clone());
+                }
+            }
             // Adds finalized transaction to the tail of anchoring transactions.
             schema.anchoring_transactions_chain_mut().push(tx);
-            if let Some(unspent_funding_tx) = schema.unspent_funding_transaction() {
-                schema
-                    .spent_funding_transactions_mut()
-                    .put(&unspent_funding_tx.id(), unspent_funding_tx);
+        }
+        Ok(())
+    }
+}
+
+impl TxFundingProposal {
+    /// Returns the proposed funding transaction output.
+    pub fn proposal(&self) -> FundingProposal {
+        FundingProposal {
+            transaction: self.transaction.clone(),
+            output: self.output,
+        }
+    }
+}
+
+impl Transaction for TxFundingProposal {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        if !is_validator_author(&mut context, self.validator) {
+            return Err(FundingError::UnexpectedAuthor {
+                validator_id: self.validator,
+                author: context.author(),
             }
+            .into());
+        }
+
+        let mut schema = BtcAnchoringSchema::new(context.fork());
+        let config = schema.actual_configuration();
+        let validators_count = config.public_keys.len();
+        if self.validator.0 as usize >= validators_count {
+            return Err(FundingError::MissingPublicKey {
+                validator_id: self.validator,
+            }
+            .into());
+        }
+
+        // Checks that the output pays to the actual anchoring address.
+        let txid = self.transaction.id();
+        let script_pubkey = config.redeem_script().as_ref().to_v0_p2wsh();
+        let is_suitable = self
+            .transaction
+            .0
+            .output
+            .get(self.output as usize)
+            .map_or(false, |out| out.script_pubkey == script_pubkey);
+        if !is_suitable {
+            return Err(FundingError::UnsuitableOutput {
+                txid,
+                output: self.output,
+            }
+            .into());
+        }
+        if schema.is_spent_funding_output(&TxOutputId::new(txid, self.output)) {
+            return Err(FundingError::AlreadySpent {
+                txid,
+                output: self.output,
+            }
+            .into());
+        }
+
+        let proposal = self.proposal();
+        let proposal_hash = proposal.hash();
+        let quorum = byzantine_quorum(validators_count);
+        let mut votes = schema
+            .funding_proposal_votes()
+            .get(&proposal_hash)
+            .unwrap_or_else(|| ValidatorVotes::new(validators_count));
+        let is_approved = votes.len() >= quorum;
+        votes.insert(self.validator);
+
+        // Approved proposal will be used by the following anchoring transaction.
+        if !is_approved && votes.len() >= quorum {
+            info!(
+                "Funding transaction output {}:{} has been approved.",
+                txid.to_hex(),
+                self.output
+            );
+            schema.approved_funding_proposals_mut().push(proposal);
+        }
+        schema
+            .funding_proposal_votes_mut()
+            .put(&proposal_hash, votes);
+        Ok(())
+    }
+}
+
+impl Transaction for TxFeeBump {
+    fn execute(&self, mut context: TransactionContext) -> ExecutionResult {
+        if !is_validator_author(&mut context, self.validator) {
+            return Err(FeeBumpError::UnexpectedAuthor {
+                validator_id: self.validator,
+                author: context.author(),
+            }
+            .into());
+        }
+
+        let mut schema = BtcAnchoringSchema::new(context.fork());
+        let config = schema.actual_configuration();
+        if config.active_fee_bump().is_none() {
+            return Err(FeeBumpError::Disabled.into());
+        }
+
+        let validators_count = config.public_keys.len();
+        if self.validator.0 as usize >= validators_count {
+            return Err(FeeBumpError::MissingPublicKey {
+                validator_id: self.validator,
+            }
+            .into());
+        }
+
+        // Checks that the vote is for the latest anchoring transaction.
+        let latest_tx = schema.anchoring_transactions_chain().last();
+        let tx = match latest_tx {
+            Some(ref tx) if tx.id() == self.txid => tx,
+            _ => {
+                return Err(FeeBumpError::Unexpected {
+                    expected_id: latest_tx.as_ref().map(|tx| tx.id()),
+                    received_id: self.txid,
+                }
+                .into());
+            }
+        };
+        if !tx.signals_rbf() {
+            return Err(FeeBumpError::NotReplaceable(self.txid).into());
+        }
+
+        let mut votes = schema
+            .fee_bump_votes()
+            .get(&self.txid)
+            .unwrap_or_else(|| ValidatorVotes::new(validators_count));
+        if self.cancel {
+            votes.remove(self.validator);
+        } else {
+            votes.insert(self.validator);
         }
+        schema.fee_bump_votes_mut().put(&self.txid, votes);
         Ok(())
     }
 }
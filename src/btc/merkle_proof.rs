@@ -0,0 +1,587 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use byteorder::{ByteOrder, LittleEndian, WriteBytesExt};
+use exonum::crypto::Hash;
+use hex::ToHex;
+
+use bitcoin::blockdata::block::BlockHeader;
+use bitcoin::consensus;
+use bitcoin::network::constants::Network;
+use bitcoin::util::hash::{BitcoinHash, Sha256dHash};
+use bitcoin::util::uint::Uint256;
+
+/// Size of the serialized block header.
+const BLOCK_HEADER_SIZE: usize = 80;
+/// Size of the serialized hash.
+const HASH_SIZE: usize = 32;
+/// Maximum number of transactions in the block, which is the maximum block weight
+/// divided by the weight of the smallest possible transaction.
+const MAX_BLOCK_TRANSACTIONS: u32 = 4_000_000 / 60;
+
+/// Proof of inclusion of the transaction in the Bitcoin block (SPV proof).
+///
+/// The proof consists of the block header and the merkle branch that connects
+/// the transaction identifier to the merkle root of the block.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MerkleProof {
+    /// Header of the block which contains the transaction.
+    pub block_header: BlockHeader,
+    /// Index of the transaction in the block.
+    pub index: u32,
+    /// Hashes of the merkle tree nodes from the transaction up to the root.
+    pub branch: Vec<Sha256dHash>,
+}
+
+/// Merkle proof verification errors.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum MerkleProofError {
+    /// Merkle proof is malformed.
+    #[fail(display = "Merkle proof is malformed: {}.", _0)]
+    Malformed(String),
+    /// Block header doesn't satisfy its proof of work.
+    #[fail(display = "Block header doesn't satisfy its proof of work.")]
+    InvalidProofOfWork,
+    /// Target of the block header is easier than the required one.
+    #[fail(display = "Block header target is easier than the required one.")]
+    InsufficientProofOfWork,
+    /// Block header doesn't belong to the expected block.
+    #[fail(
+        display = "Block hash {} differs from the expected {}.",
+        actual, expected
+    )]
+    BlockHashMismatch {
+        /// Hash of the block header from the proof.
+        actual: Hash,
+        /// Expected block hash.
+        expected: Hash,
+    },
+    /// Merkle branch doesn't lead to the merkle root of the block.
+    #[fail(display = "Merkle branch doesn't lead to the merkle root of the block.")]
+    MerkleRootMismatch,
+    /// Transaction is not included in the merkle block.
+    #[fail(display = "Transaction {} is not included in the merkle block.", _0)]
+    TransactionNotFound(Hash),
+}
+
+impl MerkleProof {
+    /// Creates a merkle proof for the transaction with the given index among
+    /// the transactions of the block.
+    ///
+    /// # Panics
+    ///
+    /// Panics if the index is out of the transactions range.
+    pub fn new(block_header: BlockHeader, txids: &[Hash], index: u32) -> Self {
+        let mut level = txids.iter().map(to_sha256d).collect::<Vec<_>>();
+        let mut position = index as usize;
+        let mut branch = Vec::new();
+        while level.len() > 1 {
+            // The last node is paired with itself on the odd levels.
+            branch.push(*level.get(position ^ 1).unwrap_or(&level[position]));
+            level = level
+                .chunks(2)
+                .map(|pair| merkle_node(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+                .collect();
+            position /= 2;
+        }
+        Self {
+            block_header,
+            index,
+            branch,
+        }
+    }
+
+    /// Extracts the merkle proof for the given transaction from the serialized merkle
+    /// block as returned by the `gettxoutproof` RPC call.
+    pub fn from_merkle_block(bytes: &[u8], txid: &Hash) -> Result<Self, MerkleProofError> {
+        let mut reader = MerkleBlockReader::new(bytes);
+        let block_header = reader.read_block_header()?;
+        let transactions_count = reader.read_u32()?;
+        let hashes_count = reader.read_var_int()?;
+        let hashes = (0..hashes_count)
+            .map(|_| reader.read_hash())
+            .collect::<Result<Vec<_>, _>>()?;
+        let flags_count = reader.read_var_int()?;
+        let flags = reader.read_bytes(flags_count as usize)?.to_vec();
+        if !reader.is_empty() {
+            return Err(malformed("unexpected trailing bytes"));
+        }
+
+        let mut tree = PartialMerkleTree {
+            transactions_count,
+            hashes: &hashes,
+            flags: &flags,
+            hashes_used: 0,
+            bits_used: 0,
+            txid: to_sha256d(txid),
+        };
+        let (root, matched) = tree.extract()?;
+        if root != block_header.merkle_root {
+            return Err(MerkleProofError::MerkleRootMismatch);
+        }
+        let (index, branch) =
+            matched.ok_or_else(|| MerkleProofError::TransactionNotFound(*txid))?;
+        Ok(Self {
+            block_header,
+            index,
+            branch,
+        })
+    }
+
+    /// Returns the hash of the block which contains the transaction.
+    pub fn block_hash(&self) -> Hash {
+        from_sha256d(&self.block_header.bitcoin_hash())
+    }
+
+    /// Verifies that the transaction with the given identifier is included in the block
+    /// whose header satisfies the proof of work with the target not easier than `max_target`.
+    ///
+    /// The target encoded in the header itself is chosen by the header author, so it cannot
+    /// be trusted on its own. The `max_target` should be taken from the actual difficulty
+    /// of the Bitcoin network, for example, from the trusted header of the same difficulty
+    /// period. The [`pow_limit`] of the network only rejects the headers which are invalid
+    /// for any difficulty.
+    ///
+    /// [`pow_limit`]: fn.pow_limit.html
+    pub fn verify(&self, txid: &Hash, max_target: &Uint256) -> Result<(), MerkleProofError> {
+        let target = self.block_header.target();
+        if target > *max_target {
+            return Err(MerkleProofError::InsufficientProofOfWork);
+        }
+        self.block_header
+            .spv_validate(&target)
+            .map_err(|_| MerkleProofError::InvalidProofOfWork)?;
+        self.verify_branch(txid)
+    }
+
+    /// Verifies that the transaction with the given identifier is included in the block
+    /// with the given hash, which should be obtained from a trusted source, such as
+    /// the own Bitcoin node.
+    pub fn verify_in_block(&self, txid: &Hash, block_hash: &Hash) -> Result<(), MerkleProofError> {
+        let actual = self.block_hash();
+        if actual != *block_hash {
+            return Err(MerkleProofError::BlockHashMismatch {
+                actual,
+                expected: *block_hash,
+            });
+        }
+        self.verify_branch(txid)
+    }
+
+    /// Verifies that the merkle branch connects the transaction with the given identifier
+    /// to the merkle root of the block.
+    fn verify_branch(&self, txid: &Hash) -> Result<(), MerkleProofError> {
+        if self.branch.len() < 32 && u64::from(self.index) >> self.branch.len() != 0 {
+            return Err(malformed("transaction index is out of the branch range"));
+        }
+
+        let mut hash = to_sha256d(txid);
+        let mut position = self.index;
+        for sibling in &self.branch {
+            hash = if position & 1 == 0 {
+                merkle_node(&hash, sibling)
+            } else {
+                merkle_node(sibling, &hash)
+            };
+            position >>= 1;
+        }
+
+        if hash == self.block_header.merkle_root {
+            Ok(())
+        } else {
+            Err(MerkleProofError::MerkleRootMismatch)
+        }
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = consensus::serialize(&self.block_header);
+        bytes.write_u32::<LittleEndian>(self.index).unwrap();
+        bytes.push(self.branch.len() as u8);
+        for hash in &self.branch {
+            bytes.extend_from_slice(&hash[..]);
+        }
+        bytes
+    }
+
+    fn from_bytes(bytes: &[u8]) -> Result<Self, MerkleProofError> {
+        let mut reader = MerkleBlockReader::new(bytes);
+        let block_header = reader.read_block_header()?;
+        let index = reader.read_u32()?;
+        let branch_len = reader.read_bytes(1)?[0];
+        let branch = (0..branch_len)
+            .map(|_| reader.read_hash())
+            .collect::<Result<Vec<_>, _>>()?;
+        if !reader.is_empty() {
+            return Err(malformed("unexpected trailing bytes"));
+        }
+        Ok(Self {
+            block_header,
+            index,
+            branch,
+        })
+    }
+}
+
+impl ::hex::FromHex for MerkleProof {
+    type Error = ::failure::Error;
+
+    fn from_hex<T: AsRef<[u8]>>(hex: T) -> Result<Self, Self::Error> {
+        let bytes = ::hex::decode(hex)?;
+        Self::from_bytes(&bytes).map_err(From::from)
+    }
+}
+
+impl ToHex for MerkleProof {
+    fn write_hex<W: ::std::fmt::Write>(&self, w: &mut W) -> ::std::fmt::Result {
+        self.to_bytes().write_hex(w)
+    }
+
+    fn write_hex_upper<W: ::std::fmt::Write>(&self, w: &mut W) -> ::std::fmt::Result {
+        self.to_bytes().write_hex_upper(w)
+    }
+}
+
+impl_string_conversions_for_hex! { MerkleProof }
+impl_serde_str! { MerkleProof }
+
+/// Returns the easiest proof of work target allowed in the given Bitcoin network.
+pub fn pow_limit(network: Network) -> Uint256 {
+    let bits = match network {
+        Network::Bitcoin | Network::Testnet => 0x1d00_ffff,
+        Network::Regtest => 0x207f_ffff,
+    };
+    BlockHeader {
+        version: 0,
+        prev_blockhash: Sha256dHash::default(),
+        merkle_root: Sha256dHash::default(),
+        time: 0,
+        bits,
+        nonce: 0,
+    }
+    .target()
+}
+
+/// Partial merkle tree of the merkle block (BIP37).
+struct PartialMerkleTree<'a> {
+    transactions_count: u32,
+    hashes: &'a [Sha256dHash],
+    flags: &'a [u8],
+    hashes_used: usize,
+    bits_used: usize,
+    txid: Sha256dHash,
+}
+
+impl<'a> PartialMerkleTree<'a> {
+    /// Computes the merkle root and the branch for the requested transaction if it is matched.
+    fn extract(
+        &mut self,
+    ) -> Result<(Sha256dHash, Option<(u32, Vec<Sha256dHash>)>), MerkleProofError> {
+        if self.transactions_count == 0 {
+            return Err(malformed("block has no transactions"));
+        }
+        if self.transactions_count > MAX_BLOCK_TRANSACTIONS {
+            return Err(malformed("too many transactions"));
+        }
+        if self.hashes.len() > self.transactions_count as usize {
+            return Err(malformed("too many hashes"));
+        }
+        if self.flags.len() * 8 < self.hashes.len() {
+            return Err(malformed("not enough flag bits"));
+        }
+
+        let mut height = 0;
+        while self.width(height) > 1 {
+            height += 1;
+        }
+        let (root, matched) = self.traverse(height, 0)?;
+        if self.hashes_used != self.hashes.len() {
+            return Err(malformed("not all hashes were used"));
+        }
+        if (self.bits_used + 7) / 8 != self.flags.len() {
+            return Err(malformed("not all flag bits were used"));
+        }
+        Ok((root, matched))
+    }
+
+    fn width(&self, height: u32) -> u32 {
+        ((u64::from(self.transactions_count) + (1 << height) - 1) >> height) as u32
+    }
+
+    fn traverse(
+        &mut self,
+        height: u32,
+        position: u32,
+    ) -> Result<(Sha256dHash, Option<(u32, Vec<Sha256dHash>)>), MerkleProofError> {
+        let flag_byte = *self
+            .flags
+            .get(self.bits_used / 8)
+            .ok_or_else(|| malformed("not enough flag bits"))?;
+        let parent_of_match = (flag_byte >> (self.bits_used % 8)) & 1 == 1;
+        self.bits_used += 1;
+
+        if height == 0 || !parent_of_match {
+            let hash = *self
+                .hashes
+                .get(self.hashes_used)
+                .ok_or_else(|| malformed("not enough hashes"))?;
+            self.hashes_used += 1;
+            let matched = if height == 0 && parent_of_match && hash == self.txid {
+                Some((position, Vec::new()))
+            } else {
+                None
+            };
+            return Ok((hash, matched));
+        }
+
+        let (left, left_matched) = self.traverse(height - 1, position * 2)?;
+        let (right, right_matched) = if position * 2 + 1 < self.width(height - 1) {
+            let (right, right_matched) = self.traverse(height - 1, position * 2 + 1)?;
+            if right == left {
+                return Err(malformed("identical left and right nodes"));
+            }
+            (right, right_matched)
+        } else {
+            (left, None)
+        };
+
+        let matched = match (left_matched, right_matched) {
+            (Some((index, mut branch)), _) => {
+                branch.push(right);
+                Some((index, branch))
+            }
+            (None, Some((index, mut branch))) => {
+                branch.push(left);
+                Some((index, branch))
+            }
+            (None, None) => None,
+        };
+        Ok((merkle_node(&left, &right), matched))
+    }
+}
+
+/// Reader of the consensus encoded merkle block.
+struct MerkleBlockReader<'a> {
+    bytes: &'a [u8],
+}
+
+impl<'a> MerkleBlockReader<'a> {
+    fn new(bytes: &'a [u8]) -> Self {
+        Self { bytes }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.bytes.is_empty()
+    }
+
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], MerkleProofError> {
+        if self.bytes.len() < len {
+            return Err(malformed("unexpected end of data"));
+        }
+        let (head, tail) = self.bytes.split_at(len);
+        self.bytes = tail;
+        Ok(head)
+    }
+
+    fn read_block_header(&mut self) -> Result<BlockHeader, MerkleProofError> {
+        let bytes = self.read_bytes(BLOCK_HEADER_SIZE)?;
+        consensus::deserialize(bytes).map_err(|e| malformed(&e.to_string()))
+    }
+
+    fn read_hash(&mut self) -> Result<Sha256dHash, MerkleProofError> {
+        let bytes = self.read_bytes(HASH_SIZE)?;
+        consensus::deserialize(bytes).map_err(|e| malformed(&e.to_string()))
+    }
+
+    fn read_u32(&mut self) -> Result<u32, MerkleProofError> {
+        self.read_bytes(4).map(LittleEndian::read_u32)
+    }
+
+    fn read_var_int(&mut self) -> Result<u64, MerkleProofError> {
+        let prefix = self.read_bytes(1)?[0];
+        match prefix {
+            0xFD => self
+                .read_bytes(2)
+                .map(|b| u64::from(LittleEndian::read_u16(b))),
+            0xFE => self
+                .read_bytes(4)
+                .map(|b| u64::from(LittleEndian::read_u32(b))),
+            0xFF => self.read_bytes(8).map(LittleEndian::read_u64),
+            _ => Ok(u64::from(prefix)),
+        }
+    }
+}
+
+fn malformed(reason: &str) -> MerkleProofError {
+    MerkleProofError::Malformed(reason.to_owned())
+}
+
+fn merkle_node(left: &Sha256dHash, right: &Sha256dHash) -> Sha256dHash {
+    let mut data = Vec::with_capacity(HASH_SIZE * 2);
+    data.extend_from_slice(&left[..]);
+    data.extend_from_slice(&right[..]);
+    Sha256dHash::from_data(&data)
+}
+
+/// Converts the transaction identifier into the internal byte order of the Bitcoin hashes.
+fn to_sha256d(txid: &Hash) -> Sha256dHash {
+    let mut bytes = [0_u8; HASH_SIZE];
+    bytes.copy_from_slice(txid.as_ref());
+    bytes.reverse();
+    consensus::deserialize(&bytes).unwrap()
+}
+
+fn from_sha256d(hash: &Sha256dHash) -> Hash {
+    let mut bytes = [0_u8; HASH_SIZE];
+    bytes.copy_from_slice(&hash[..]);
+    bytes.reverse();
+    Hash::new(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::{self, Hash};
+
+    use bitcoin::blockdata::block::BlockHeader;
+    use bitcoin::network::constants::Network;
+    use bitcoin::util::hash::Sha256dHash;
+    use hex::FromHex;
+
+    use super::{malformed, merkle_node, pow_limit, to_sha256d, MerkleProof, MerkleProofError};
+
+    fn txids(count: u8) -> Vec<Hash> {
+        (0..count).map(|i| crypto::hash(&[i])).collect()
+    }
+
+    fn merkle_root(txids: &[Hash]) -> Sha256dHash {
+        let mut level = txids.iter().map(to_sha256d).collect::<Vec<_>>();
+        while level.len() > 1 {
+            level = level
+                .chunks(2)
+                .map(|pair| merkle_node(&pair[0], pair.get(1).unwrap_or(&pair[0])))
+                .collect();
+        }
+        level[0]
+    }
+
+    fn block_header(txids: &[Hash]) -> BlockHeader {
+        let mut header = BlockHeader {
+            version: 1,
+            prev_blockhash: Sha256dHash::default(),
+            merkle_root: merkle_root(txids),
+            time: 0,
+            // Minimal regtest difficulty.
+            bits: 0x207f_ffff,
+            nonce: 0,
+        };
+        while header.spv_validate(&header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    #[test]
+    fn test_merkle_proof_verify() {
+        let max_target = pow_limit(Network::Regtest);
+        for count in 1..8 {
+            let txids = txids(count);
+            let header = block_header(&txids);
+            for (index, txid) in txids.iter().enumerate() {
+                let proof = MerkleProof::new(header.clone(), &txids, index as u32);
+                assert_eq!(proof.verify(txid, &max_target), Ok(()));
+                assert_eq!(proof.verify_in_block(txid, &proof.block_hash()), Ok(()));
+                // Other transactions are not proven.
+                let other = crypto::hash(&[0xFF]);
+                assert_eq!(
+                    proof.verify(&other, &max_target),
+                    Err(MerkleProofError::MerkleRootMismatch)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_merkle_proof_verify_block() {
+        let txids = txids(3);
+        let proof = MerkleProof::new(block_header(&txids), &txids, 1);
+        // Regtest difficulty is not enough for the main network.
+        assert_eq!(
+            proof.verify(&txids[1], &pow_limit(Network::Bitcoin)),
+            Err(MerkleProofError::InsufficientProofOfWork)
+        );
+
+        let other_block = MerkleProof::new(block_header(&txids[..2]), &txids[..2], 1);
+        assert_eq!(
+            proof.verify_in_block(&txids[1], &other_block.block_hash()),
+            Err(MerkleProofError::BlockHashMismatch {
+                actual: proof.block_hash(),
+                expected: other_block.block_hash(),
+            })
+        );
+    }
+
+    #[test]
+    fn test_merkle_proof_hex_roundtrip() {
+        let txids = txids(5);
+        let proof = MerkleProof::new(block_header(&txids), &txids, 3);
+        let hex = proof.to_string();
+        assert_eq!(MerkleProof::from_hex(&hex).unwrap(), proof);
+    }
+
+    #[test]
+    fn test_merkle_proof_from_merkle_block() {
+        // Merkle block with three transactions, where the third one is matched.
+        let txids = txids(3);
+        let header = block_header(&txids);
+        let hashes = vec![
+            merkle_node(&to_sha256d(&txids[0]), &to_sha256d(&txids[1])),
+            to_sha256d(&txids[2]),
+        ];
+        // Traversal bits: root, left subtree, right subtree, matched leaf.
+        let flags = vec![0b1101];
+
+        let mut bytes = ::bitcoin::consensus::serialize(&header);
+        bytes.extend_from_slice(&[3, 0, 0, 0]);
+        bytes.push(hashes.len() as u8);
+        for hash in &hashes {
+            bytes.extend_from_slice(&hash[..]);
+        }
+        bytes.push(flags.len() as u8);
+        bytes.extend_from_slice(&flags);
+
+        let proof = MerkleProof::from_merkle_block(&bytes, &txids[2]).unwrap();
+        assert_eq!(proof, MerkleProof::new(header.clone(), &txids, 2));
+        assert_eq!(
+            proof.verify(&txids[2], &pow_limit(Network::Regtest)),
+            Ok(())
+        );
+        assert_eq!(
+            MerkleProof::from_merkle_block(&bytes, &txids[0]),
+            Err(MerkleProofError::TransactionNotFound(txids[0]))
+        );
+    }
+
+    #[test]
+    fn test_merkle_proof_from_merkle_block_too_many_transactions() {
+        let txids = txids(1);
+        let mut bytes = ::bitcoin::consensus::serialize(&block_header(&txids));
+        bytes.extend_from_slice(&[0xFF, 0xFF, 0xFF, 0xFF, 1]);
+        bytes.extend_from_slice(&to_sha256d(&txids[0])[..]);
+        bytes.extend_from_slice(&[1, 1]);
+
+        assert_eq!(
+            MerkleProof::from_merkle_block(&bytes, &txids[0]),
+            Err(malformed("too many transactions"))
+        );
+    }
+}
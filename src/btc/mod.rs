@@ -14,13 +14,19 @@
 
 //! Collection of wrappers for the rust-bitcoin crate.
 
-pub use self::payload::Payload;
-pub use self::transaction::{BtcAnchoringTransactionBuilder, BuilderError, Transaction};
+pub use self::merkle_proof::{pow_limit, MerkleProof, MerkleProofError};
+pub use self::payload::{
+    ChainId, Payload, PayloadError, PayloadVersion, UnknownRecord, MAX_PAYLOAD_LEN,
+};
+pub use self::psbt::{Psbt, PsbtError, PsbtInput, UnknownEntries};
+pub use self::transaction::{bump_fee, BtcAnchoringTransactionBuilder, BuilderError, Transaction};
 
 use bitcoin::network::constants::Network;
 use bitcoin::util::address;
+use bitcoin::util::bip32;
 use bitcoin::util::privkey;
 use btc_transaction_utils;
+use failure;
 use hex::{self, FromHex, ToHex};
 
 use rand::{self, Rng};
@@ -30,13 +36,23 @@ use std::ops::Deref;
 #[macro_use]
 mod macros;
 
+/// Maximal epoch of the anchoring keys, which are derived as the hardened children
+/// of the extended private key.
+pub const MAX_KEY_EPOCH: u32 = (1 << 31) - 1;
+
+pub(crate) mod merkle_proof;
 pub(crate) mod payload;
+pub(crate) mod psbt;
 pub(crate) mod transaction;
 
 /// Bitcoin ECDSA private key wrapper.
 #[derive(Clone, From, Into, PartialEq, Eq)]
 pub struct Privkey(pub privkey::Privkey);
 
+/// Bitcoin extended private key wrapper (BIP 32).
+#[derive(Clone, From, Into, PartialEq, Eq)]
+pub struct ExtendedPrivkey(pub bip32::ExtendedPrivKey);
+
 /// Secp256k1 public key wrapper, used for verification of signatures.
 #[derive(Debug, Clone, Copy, From, Into, PartialEq, Eq)]
 pub struct PublicKey(pub secp256k1::PublicKey);
@@ -69,6 +85,57 @@ impl ::std::fmt::Debug for Privkey {
     }
 }
 
+impl ExtendedPrivkey {
+    /// Generates a new random master key for the given network.
+    pub fn generate(network: Network) -> Self {
+        let context = secp256k1::Secp256k1::new();
+        let seed: [u8; 32] = rand::thread_rng().gen();
+        let inner = bip32::ExtendedPrivKey::new_master(&context, network, &seed)
+            .expect("Unable to create master key from the random seed");
+        ExtendedPrivkey(inner)
+    }
+
+    /// Derives the anchoring keypair for the given epoch of the anchoring configuration.
+    /// The keypair is the hardened child of this key with the epoch as the index, thus
+    /// the epoch should not exceed `MAX_KEY_EPOCH`.
+    pub fn derive_keypair(&self, epoch: u32) -> Result<(PublicKey, Privkey), failure::Error> {
+        ensure!(
+            epoch <= MAX_KEY_EPOCH,
+            "Key epoch {} is out of the hardened indexes range",
+            epoch
+        );
+
+        let context = secp256k1::Secp256k1::new();
+        let child = self
+            .0
+            .ckd_priv(&context, bip32::ChildNumber::from_hardened_idx(epoch))
+            .map_err(|e| format_err!("Unable to derive child key: {}", e))?;
+        let priv_key = privkey::Privkey::from_secret_key(child.secret_key, true, child.network);
+        let pub_key = secp256k1::PublicKey::from_secret_key(&context, &child.secret_key);
+        Ok((pub_key.into(), priv_key.into()))
+    }
+}
+
+impl ToString for ExtendedPrivkey {
+    fn to_string(&self) -> String {
+        self.0.to_string()
+    }
+}
+
+impl ::std::str::FromStr for ExtendedPrivkey {
+    type Err = <bip32::ExtendedPrivKey as ::std::str::FromStr>::Err;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        bip32::ExtendedPrivKey::from_str(s).map(From::from)
+    }
+}
+
+impl ::std::fmt::Debug for ExtendedPrivkey {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_struct("ExtendedPrivkey").finish()
+    }
+}
+
 impl FromHex for PublicKey {
     type Error = ::failure::Error;
 
@@ -152,6 +219,7 @@ impl_string_conversions_for_hex! { PublicKey }
 impl_string_conversions_for_hex! { InputSignature }
 
 impl_serde_str! { Privkey }
+impl_serde_str! { ExtendedPrivkey }
 impl_serde_str! { PublicKey }
 impl_serde_str! { Address }
 impl_serde_str! { InputSignature }
@@ -173,3 +241,45 @@ pub fn gen_keypair(network: Network) -> (PublicKey, Privkey) {
     let mut rng = rand::thread_rng();
     gen_keypair_with_rng(network, &mut rng)
 }
+
+/// Serde helpers for the byte vectors in the hex format.
+pub(crate) mod hex_bytes {
+    use hex;
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(bytes: &[u8], ser: S) -> Result<S::Ok, S::Error> {
+        ser.serialize_str(&hex::encode(bytes))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+        use serde::de::Error;
+
+        let hex_str = String::deserialize(deserializer)?;
+        hex::decode(hex_str).map_err(D::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::network::constants::Network;
+
+    use super::{ExtendedPrivkey, MAX_KEY_EPOCH};
+
+    #[test]
+    fn test_extended_privkey_derivation() {
+        let key = ExtendedPrivkey::generate(Network::Testnet);
+        let key2: ExtendedPrivkey = key.to_string().parse().unwrap();
+        assert_eq!(key2, key);
+
+        let (public_key, privkey) = key.derive_keypair(0).unwrap();
+        let (public_key2, privkey2) = key2.derive_keypair(0).unwrap();
+        assert_eq!(public_key, public_key2);
+        assert_eq!(privkey, privkey2);
+
+        let (public_key3, _) = key.derive_keypair(1).unwrap();
+        assert_ne!(public_key, public_key3);
+
+        assert!(key.derive_keypair(MAX_KEY_EPOCH).is_ok());
+        assert!(key.derive_keypair(MAX_KEY_EPOCH + 1).is_err());
+    }
+}
@@ -12,18 +12,36 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use exonum::crypto::Hash;
+use exonum::crypto::{self, Hash};
 use exonum::helpers::Height;
 
 use bitcoin::blockdata::opcodes::All;
 use bitcoin::blockdata::script::{Builder, Instruction, Script};
 use byteorder::{ByteOrder, LittleEndian};
+use hex::{self, FromHex, ToHex};
 
 const PAYLOAD_PREFIX: &[u8] = b"EXONUM";
 const PAYLOAD_HEADER_LEN: usize = 8;
 const PAYLOAD_V1: u8 = 1;
 const PAYLOAD_V1_KIND_REGULAR: u8 = 0;
 const PAYLOAD_V1_KIND_RECOVER: u8 = 1;
+const PAYLOAD_V2: u8 = 2;
+const PAYLOAD_V2_KIND_REGULAR: u8 = 0;
+const PAYLOAD_V2_KIND_BATCH: u8 = 1;
+const PAYLOAD_V2_KIND_IDENTIFIED: u8 = 2;
+const PAYLOAD_V3: u8 = 3;
+// The payload v.3 has no kind byte.
+const PAYLOAD_V3_HEADER_LEN: usize = 7;
+const PAYLOAD_V3_RECORD_HEIGHT: u8 = 1;
+const PAYLOAD_V3_RECORD_BLOCK_HASH: u8 = 2;
+const PAYLOAD_V3_RECORD_PREV_TX_CHAIN: u8 = 3;
+const PAYLOAD_V3_RECORD_STATE_COMMITMENT: u8 = 4;
+const PAYLOAD_V3_RECORD_BATCH_ROOT: u8 = 5;
+const PAYLOAD_V3_RECORD_CHAIN_ID: u8 = 6;
+const CHAIN_ID_LEN: usize = 8;
+
+/// Maximum size of the data in the standard `OP_RETURN` output.
+pub const MAX_PAYLOAD_LEN: usize = 80;
 
 /// Anchoring transaction payload.
 ///
@@ -39,6 +57,66 @@ const PAYLOAD_V1_KIND_RECOVER: u8 = 1;
 /// | 48..80 (Optionally)   | Txid of previous tx chain (only for recover kind) |
 ///
 /// In this way the length of `regular` payload is 48, and for `recover` is 80.
+///
+/// Data layout in `OP_RETURN` script for `Payload` v.2:
+///
+/// | Position in bytes     | Description                                       |
+/// |-----------------------|---------------------------------------------------|
+/// | 0..6                  | ASCII-encoded prefix `EXONUM`                     |
+/// | 6                     | Version byte, 2                                   |
+/// | 7                     | Payload kind: (0 is regular, 1 is batch,          |
+/// |                       | 2 is identified)                                  |
+/// | 8..16                 | Block height                                      |
+/// | 16..48                | Block hash                                        |
+/// | 48..80                | State commitment or batch root (for batch kind)   |
+///
+/// The identified payload kind has the following layout:
+///
+/// | Position in bytes     | Description                                       |
+/// |-----------------------|---------------------------------------------------|
+/// | 8..16                 | Block height                                      |
+/// | 16..48                | Block hash                                        |
+/// | 48..56                | Chain identifier                                  |
+///
+/// The state commitment is the hash of the concatenated state hash of the anchored block
+/// and the hash of the configuration, which is actual at the anchored height. Both hashes
+/// don't fit into the 80 bytes of the standard `OP_RETURN` output along with the block
+/// hash, therefore they are committed with the single hash. For the same reason there is
+/// no `recover` kind in v.2, such payloads are always written in v.1.
+///
+/// The batch root is the Merkle root of the list of the block hashes from the block following
+/// the previous anchored one up to the anchored block inclusive. It links each block of the
+/// batch to the Bitcoin blockchain directly.
+///
+/// The chain identifier allows to distinguish the payloads of the different Exonum
+/// blockchains. There is no room for it in the payloads which already occupy the whole
+/// `OP_RETURN` output, thus only the regular payloads can be identified.
+///
+/// Data layout in `OP_RETURN` script for `Payload` v.3:
+///
+/// | Position in bytes     | Description                                       |
+/// |-----------------------|---------------------------------------------------|
+/// | 0..6                  | ASCII-encoded prefix `EXONUM`                     |
+/// | 6                     | Version byte, 3                                   |
+/// | 7..                   | Records                                           |
+///
+/// Each record consists of the type byte, the length byte and the value. The known
+/// record types are the following:
+///
+/// | Type | Value                                                                 |
+/// |------|-----------------------------------------------------------------------|
+/// | 1    | Block height, little-endian without the trailing zero bytes (required) |
+/// | 2    | Block hash (required)                                                 |
+/// | 3    | Txid of previous tx chain                                             |
+/// | 4    | State commitment                                                      |
+/// | 5    | Batch root                                                            |
+/// | 6    | Chain identifier                                                      |
+///
+/// Records of the other types are preserved as unknown ones, so the new fields can be
+/// added to v.3 without breaking the older parsers. The whole payload still should fit
+/// into the 80 bytes of the standard `OP_RETURN` output, otherwise the payload isn't built.
+/// For example, the state commitment doesn't fit along with the height starting from 2^24.
+/// The recover payload with such height is written in v.1.
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct Payload {
     /// Anchored block height.
@@ -47,6 +125,119 @@ pub struct Payload {
     pub block_hash: Hash,
     /// `Txid` of previous transactions chain if it has been lost.
     pub prev_tx_chain: Option<Hash>,
+    /// Commitment to the state hash of the anchored block and the hash of the actual
+    /// configuration. It is present only in the payload v.2.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state_commitment: Option<Hash>,
+    /// Merkle root of the hashes of the blocks since the previous anchored block.
+    /// It is present only in the batch payload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub batch_root: Option<Hash>,
+    /// Identifier of the anchored blockchain. It is present only in the identified payload.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub chain_id: Option<ChainId>,
+    /// Records of the payload v.3 which are unknown to this version of the service.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub unknown_records: Vec<UnknownRecord>,
+}
+
+/// Record of the payload v.3 with the unknown type.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UnknownRecord {
+    /// Type of the record.
+    pub record_type: u8,
+    /// Value of the record.
+    #[serde(with = "::btc::hex_bytes")]
+    pub value: Vec<u8>,
+}
+
+/// Payload builder errors.
+#[derive(Debug, Copy, Clone, PartialEq, Fail)]
+pub enum PayloadError {
+    /// Payload doesn't fit into the standard `OP_RETURN` output.
+    #[fail(
+        display = "Payload size {} exceeds the limit of the standard OP_RETURN output, \
+                   which is {} bytes",
+        size, limit
+    )]
+    TooLarge {
+        /// Size of the payload in bytes.
+        size: usize,
+        /// Maximum size of the payload in bytes.
+        limit: usize,
+    },
+    /// Unknown record has the type of the known one.
+    #[fail(
+        display = "Record type {} is reserved for the known payload field",
+        record_type
+    )]
+    ReservedRecordType {
+        /// Type of the record.
+        record_type: u8,
+    },
+}
+
+/// Identifier of the anchored Exonum blockchain, which consists of the first bytes
+/// of its genesis block hash.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ChainId(pub [u8; CHAIN_ID_LEN]);
+
+impl ChainId {
+    /// Returns the identifier of the blockchain with the given genesis block hash.
+    pub fn from_genesis_hash(genesis_hash: &Hash) -> Self {
+        let mut id = [0; CHAIN_ID_LEN];
+        id.copy_from_slice(&genesis_hash.as_ref()[..CHAIN_ID_LEN]);
+        ChainId(id)
+    }
+}
+
+impl FromHex for ChainId {
+    type Error = ::failure::Error;
+
+    fn from_hex<T: AsRef<[u8]>>(hex: T) -> Result<Self, Self::Error> {
+        let bytes = hex::decode(hex)?;
+        ensure!(
+            bytes.len() == CHAIN_ID_LEN,
+            "Chain identifier should be {} bytes long",
+            CHAIN_ID_LEN
+        );
+        let mut id = [0; CHAIN_ID_LEN];
+        id.copy_from_slice(&bytes);
+        Ok(ChainId(id))
+    }
+}
+
+impl ToHex for ChainId {
+    fn write_hex<W: ::std::fmt::Write>(&self, w: &mut W) -> ::std::fmt::Result {
+        self.0.as_ref().write_hex(w)
+    }
+
+    fn write_hex_upper<W: ::std::fmt::Write>(&self, w: &mut W) -> ::std::fmt::Result {
+        self.0.as_ref().write_hex_upper(w)
+    }
+}
+
+impl_string_conversions_for_hex! { ChainId }
+impl_serde_str! { ChainId }
+
+/// Version of the anchoring transaction payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PayloadVersion {
+    /// Payload commits to the anchored block hash.
+    V1,
+    /// Payload also commits to the state hash of the anchored block and
+    /// the hash of the actual configuration.
+    V2,
+    /// Extensible payload which consists of the records. It contains the same
+    /// fields as v.2 and allows to add the new ones.
+    V3,
+}
+
+impl Default for PayloadVersion {
+    fn default() -> Self {
+        PayloadVersion::V1
+    }
 }
 
 #[derive(Debug)]
@@ -55,20 +246,32 @@ enum PayloadV1 {
     Recover(Height, Hash, Hash),
 }
 
+#[derive(Debug)]
+enum PayloadV2 {
+    Regular(Height, Hash, Hash),
+    Batch(Height, Hash, Hash),
+    Identified(Height, Hash, ChainId),
+}
+
 #[derive(Debug, Default)]
-pub struct PayloadV1Builder {
+pub struct PayloadBuilder {
     block_hash: Option<Hash>,
     block_height: Option<Height>,
     prev_tx_chain: Option<Hash>,
+    state_commitment: Option<Hash>,
+    batch_root: Option<Hash>,
+    chain_id: Option<ChainId>,
+    unknown_records: Vec<UnknownRecord>,
+    version: PayloadVersion,
 }
 
-pub type PayloadBuilder = PayloadV1Builder;
+#[derive(Debug)]
+struct PayloadV3(Payload);
 
 #[cfg_attr(feature = "cargo-clippy", allow(clippy::len_without_is_empty))]
 impl PayloadV1 {
     fn read(bytes: &[u8]) -> Option<Self> {
-        let kind = bytes[0];
-        let data = &bytes[1..];
+        let (&kind, data) = bytes.split_first()?;
         match kind {
             PAYLOAD_V1_KIND_REGULAR => {
                 if data.len() != 40 {
@@ -128,27 +331,273 @@ impl PayloadV1 {
     }
 
     fn into_script(self) -> Script {
-        let len = self.len() + PAYLOAD_HEADER_LEN;
-        let mut buf = vec![0; len];
-        // Serialize header
-        buf[0..6].copy_from_slice(PAYLOAD_PREFIX);
-        buf[6] = PAYLOAD_V1;
+        let mut buf = vec![0; self.len() + PAYLOAD_HEADER_LEN];
         self.write(&mut buf[7..]);
-        // Build script
-        Builder::new()
-            .push_opcode(All::OP_RETURN)
-            .push_slice(buf.as_ref())
-            .into_script()
+        payload_script(PAYLOAD_V1, buf)
     }
 }
 
-impl PayloadV1Builder {
-    pub fn new() -> Self {
-        Self {
-            block_hash: None,
-            block_height: None,
+#[cfg_attr(feature = "cargo-clippy", allow(clippy::len_without_is_empty))]
+impl PayloadV2 {
+    fn read(bytes: &[u8]) -> Option<Self> {
+        let (&kind, data) = bytes.split_first()?;
+        let expected_len = match kind {
+            PAYLOAD_V2_KIND_REGULAR | PAYLOAD_V2_KIND_BATCH => 72,
+            PAYLOAD_V2_KIND_IDENTIFIED => 40 + CHAIN_ID_LEN,
+            _ => return None,
+        };
+        if data.len() != expected_len {
+            return None;
+        }
+
+        let block_height = Height(LittleEndian::read_u64(&data[0..8]));
+        let block_hash = Hash::from_slice(&data[8..40]).unwrap();
+        match kind {
+            PAYLOAD_V2_KIND_REGULAR => {
+                let state_commitment = Hash::from_slice(&data[40..72]).unwrap();
+                Some(PayloadV2::Regular(
+                    block_height,
+                    block_hash,
+                    state_commitment,
+                ))
+            }
+            PAYLOAD_V2_KIND_BATCH => {
+                let batch_root = Hash::from_slice(&data[40..72]).unwrap();
+                Some(PayloadV2::Batch(block_height, block_hash, batch_root))
+            }
+            _ => {
+                let mut chain_id = [0; CHAIN_ID_LEN];
+                chain_id.copy_from_slice(&data[40..]);
+                Some(PayloadV2::Identified(
+                    block_height,
+                    block_hash,
+                    ChainId(chain_id),
+                ))
+            }
+        }
+    }
+
+    fn write(&self, buf: &mut [u8]) {
+        buf[0] = match *self {
+            PayloadV2::Regular(..) => PAYLOAD_V2_KIND_REGULAR,
+            PayloadV2::Batch(..) => PAYLOAD_V2_KIND_BATCH,
+            PayloadV2::Identified(..) => PAYLOAD_V2_KIND_IDENTIFIED,
+        };
+
+        let buf = &mut buf[1..];
+        debug_assert_eq!(buf.len(), self.len());
+        match *self {
+            PayloadV2::Regular(height, hash, commitment)
+            | PayloadV2::Batch(height, hash, commitment) => {
+                LittleEndian::write_u64(&mut buf[0..8], height.0);
+                buf[8..40].copy_from_slice(hash.as_ref());
+                buf[40..72].copy_from_slice(commitment.as_ref());
+            }
+            PayloadV2::Identified(height, hash, chain_id) => {
+                LittleEndian::write_u64(&mut buf[0..8], height.0);
+                buf[8..40].copy_from_slice(hash.as_ref());
+                buf[40..].copy_from_slice(&chain_id.0);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        match *self {
+            PayloadV2::Regular(..) | PayloadV2::Batch(..) => 72,
+            PayloadV2::Identified(..) => 40 + CHAIN_ID_LEN,
+        }
+    }
+
+    fn into_script(self) -> Script {
+        let mut buf = vec![0; self.len() + PAYLOAD_HEADER_LEN];
+        self.write(&mut buf[7..]);
+        payload_script(PAYLOAD_V2, buf)
+    }
+}
+
+/// Fills the payload header and builds the `OP_RETURN` script with the given payload.
+fn payload_script(version: u8, mut buf: Vec<u8>) -> Script {
+    // Serialize header
+    buf[0..6].copy_from_slice(PAYLOAD_PREFIX);
+    buf[6] = version;
+    // Build script
+    Builder::new()
+        .push_opcode(All::OP_RETURN)
+        .push_slice(buf.as_ref())
+        .into_script()
+}
+
+#[cfg_attr(feature = "cargo-clippy", allow(clippy::len_without_is_empty))]
+impl PayloadV3 {
+    fn read(bytes: &[u8]) -> Option<Self> {
+        let mut block_height = None;
+        let mut block_hash = None;
+        let mut payload = Payload {
+            block_height: Height::zero(),
+            block_hash: Hash::zero(),
             prev_tx_chain: None,
+            state_commitment: None,
+            batch_root: None,
+            chain_id: None,
+            unknown_records: Vec::new(),
+        };
+
+        let mut bytes = bytes;
+        while !bytes.is_empty() {
+            if bytes.len() < 2 || bytes.len() < 2 + bytes[1] as usize {
+                return None;
+            }
+            let (record_type, value) = (bytes[0], &bytes[2..2 + bytes[1] as usize]);
+            bytes = &bytes[2 + value.len()..];
+
+            // Each of the known records can be present only once.
+            let is_duplicate = match record_type {
+                PAYLOAD_V3_RECORD_HEIGHT => {
+                    if value.is_empty() || value.len() > 8 {
+                        return None;
+                    }
+                    let mut height = [0; 8];
+                    height[..value.len()].copy_from_slice(value);
+                    block_height
+                        .replace(Height(LittleEndian::read_u64(&height)))
+                        .is_some()
+                }
+                PAYLOAD_V3_RECORD_BLOCK_HASH => {
+                    block_hash.replace(Hash::from_slice(value)?).is_some()
+                }
+                PAYLOAD_V3_RECORD_PREV_TX_CHAIN => payload
+                    .prev_tx_chain
+                    .replace(Hash::from_slice(value)?)
+                    .is_some(),
+                PAYLOAD_V3_RECORD_STATE_COMMITMENT => payload
+                    .state_commitment
+                    .replace(Hash::from_slice(value)?)
+                    .is_some(),
+                PAYLOAD_V3_RECORD_BATCH_ROOT => payload
+                    .batch_root
+                    .replace(Hash::from_slice(value)?)
+                    .is_some(),
+                PAYLOAD_V3_RECORD_CHAIN_ID => {
+                    if value.len() != CHAIN_ID_LEN {
+                        return None;
+                    }
+                    let mut chain_id = [0; CHAIN_ID_LEN];
+                    chain_id.copy_from_slice(value);
+                    payload.chain_id.replace(ChainId(chain_id)).is_some()
+                }
+                _ => {
+                    payload.unknown_records.push(UnknownRecord {
+                        record_type,
+                        value: value.to_vec(),
+                    });
+                    false
+                }
+            };
+            if is_duplicate {
+                return None;
+            }
         }
+
+        payload.block_height = block_height?;
+        payload.block_hash = block_hash?;
+        Some(PayloadV3(payload))
+    }
+
+    fn records(&self) -> Vec<(u8, Vec<u8>)> {
+        let payload = &self.0;
+        // Height is written without the trailing zero bytes.
+        let mut height = [0; 8];
+        LittleEndian::write_u64(&mut height, payload.block_height.0);
+        let height_len = height
+            .iter()
+            .rposition(|&byte| byte != 0)
+            .map_or(1, |pos| pos + 1);
+
+        let mut records = vec![
+            (PAYLOAD_V3_RECORD_HEIGHT, height[..height_len].to_vec()),
+            (
+                PAYLOAD_V3_RECORD_BLOCK_HASH,
+                payload.block_hash.as_ref().to_vec(),
+            ),
+        ];
+        let hashes = [
+            (PAYLOAD_V3_RECORD_PREV_TX_CHAIN, payload.prev_tx_chain),
+            (PAYLOAD_V3_RECORD_STATE_COMMITMENT, payload.state_commitment),
+            (PAYLOAD_V3_RECORD_BATCH_ROOT, payload.batch_root),
+        ];
+        for &(record_type, hash) in &hashes {
+            if let Some(hash) = hash {
+                records.push((record_type, hash.as_ref().to_vec()));
+            }
+        }
+        if let Some(chain_id) = payload.chain_id {
+            records.push((PAYLOAD_V3_RECORD_CHAIN_ID, chain_id.0.to_vec()));
+        }
+        for record in &payload.unknown_records {
+            records.push((record.record_type, record.value.clone()));
+        }
+        records
+    }
+
+    fn len(&self) -> usize {
+        PAYLOAD_V3_HEADER_LEN
+            + self
+                .records()
+                .iter()
+                .map(|&(_, ref value)| 2 + value.len())
+                .sum::<usize>()
+    }
+
+    fn into_script(self) -> Result<Script, PayloadError> {
+        for record in &self.0.unknown_records {
+            if record.record_type >= PAYLOAD_V3_RECORD_HEIGHT
+                && record.record_type <= PAYLOAD_V3_RECORD_CHAIN_ID
+            {
+                return Err(PayloadError::ReservedRecordType {
+                    record_type: record.record_type,
+                });
+            }
+        }
+
+        let size = self.len();
+        if size > MAX_PAYLOAD_LEN {
+            // The recover payload doesn't fit only with the whole height, and it is written
+            // in v.1 instead, since the link to the previous transactions chain is required.
+            let payload = self.0;
+            match payload {
+                Payload {
+                    block_height,
+                    block_hash,
+                    prev_tx_chain: Some(txid),
+                    state_commitment: None,
+                    batch_root: None,
+                    chain_id: None,
+                    ref unknown_records,
+                } if unknown_records.is_empty() => {
+                    let v1 = PayloadV1::Recover(block_height, block_hash, txid);
+                    return Ok(v1.into_script());
+                }
+                _ => {}
+            }
+            return Err(PayloadError::TooLarge {
+                size,
+                limit: MAX_PAYLOAD_LEN,
+            });
+        }
+
+        let mut buf = vec![0; PAYLOAD_V3_HEADER_LEN];
+        for (record_type, value) in self.records() {
+            buf.push(record_type);
+            buf.push(value.len() as u8);
+            buf.extend_from_slice(&value);
+        }
+        Ok(payload_script(PAYLOAD_V3, buf))
+    }
+}
+
+impl PayloadBuilder {
+    pub fn new() -> Self {
+        Self::default()
     }
 
     pub fn block_height(mut self, height: Height) -> Self {
@@ -166,19 +615,92 @@ impl PayloadV1Builder {
         self
     }
 
-    pub fn into_script(self) -> Script {
+    /// Sets the state commitment, which switches the regular payload to v.2.
+    pub fn state_commitment(mut self, state_commitment: Option<Hash>) -> Self {
+        self.state_commitment = state_commitment;
+        self
+    }
+
+    /// Sets the root of the anchored blocks batch, which switches the regular payload
+    /// to the batch one. The batch root takes precedence over the state commitment.
+    pub fn batch_root(mut self, batch_root: Option<Hash>) -> Self {
+        self.batch_root = batch_root;
+        self
+    }
+
+    /// Sets the identifier of the anchored blockchain. It is written only into the regular
+    /// payload without the other hashes, since the other ones have no room for it. The payload
+    /// v.3 with the other hashes isn't built at all.
+    pub fn chain_id(mut self, chain_id: Option<ChainId>) -> Self {
+        self.chain_id = chain_id;
+        self
+    }
+
+    /// Adds the record with the unknown type, which is written only into the payload v.3.
+    #[cfg(test)]
+    pub fn unknown_record(mut self, record_type: u8, value: Vec<u8>) -> Self {
+        self.unknown_records
+            .push(UnknownRecord { record_type, value });
+        self
+    }
+
+    /// Sets the payload version. The fixed layouts of v.1 and v.2 are chosen by the set
+    /// fields, while v.3 contains all of them.
+    pub fn version(mut self, version: PayloadVersion) -> Self {
+        self.version = version;
+        self
+    }
+
+    /// Builds the `OP_RETURN` script with the payload. Returns an error if the records
+    /// of the payload v.3 don't fit into the standard `OP_RETURN` output.
+    pub fn into_script(self) -> Result<Script, PayloadError> {
         let block_height = self.block_height.expect("Block height is not set");
         let block_hash = self.block_hash.expect("Block hash is not set");
 
-        let payload = match self.prev_tx_chain {
-            Some(txid) => PayloadV1::Recover(block_height, block_hash, txid),
-            None => PayloadV1::Regular(block_height, block_hash),
-        };
-        payload.into_script()
+        if self.version == PayloadVersion::V3 {
+            return PayloadV3(Payload {
+                block_height,
+                block_hash,
+                prev_tx_chain: self.prev_tx_chain,
+                state_commitment: self.state_commitment,
+                batch_root: self.batch_root,
+                chain_id: self.chain_id,
+                unknown_records: self.unknown_records,
+            })
+            .into_script();
+        }
+
+        // Only one of the additional hashes fits into the `OP_RETURN` output.
+        Ok(if let Some(txid) = self.prev_tx_chain {
+            PayloadV1::Recover(block_height, block_hash, txid).into_script()
+        } else if let Some(batch_root) = self.batch_root {
+            PayloadV2::Batch(block_height, block_hash, batch_root).into_script()
+        } else if let Some(state_commitment) = self.state_commitment {
+            PayloadV2::Regular(block_height, block_hash, state_commitment).into_script()
+        } else if let Some(chain_id) = self.chain_id {
+            PayloadV2::Identified(block_height, block_hash, chain_id).into_script()
+        } else {
+            PayloadV1::Regular(block_height, block_hash).into_script()
+        })
     }
 }
 
 impl Payload {
+    /// Computes the state commitment for the given state hash of the anchored block
+    /// and the hash of the configuration, which is actual at the anchored height.
+    pub fn state_commitment_for(state_hash: &Hash, config_hash: &Hash) -> Hash {
+        let mut bytes = Vec::with_capacity(64);
+        bytes.extend_from_slice(state_hash.as_ref());
+        bytes.extend_from_slice(config_hash.as_ref());
+        crypto::hash(&bytes)
+    }
+
+    /// Checks that the payload doesn't belong to another blockchain. Payloads without
+    /// the chain identifier can't be attributed and match any blockchain.
+    pub fn matches_chain(&self, chain_id: &ChainId) -> bool {
+        self.chain_id.as_ref().map_or(true, |id| id == chain_id)
+    }
+
     /// Tries to extract payload from given `Script`
     pub fn from_script(script: &Script) -> Option<Self> {
         let mut instructions = script.iter(true);
@@ -203,6 +725,8 @@ impl Payload {
                     let version = bytes[6];
                     match version {
                         PAYLOAD_V1 => PayloadV1::read(&bytes[7..]).map(Self::from),
+                        PAYLOAD_V2 => PayloadV2::read(&bytes[7..]).map(Self::from),
+                        PAYLOAD_V3 => PayloadV3::read(&bytes[7..]).map(|v3| v3.0),
                         _ => None,
                     }
                 } else {
@@ -219,11 +743,53 @@ impl From<PayloadV1> for Payload {
                 block_height: height,
                 block_hash: hash,
                 prev_tx_chain: None,
+                state_commitment: None,
+                batch_root: None,
+                chain_id: None,
+                unknown_records: Vec::new(),
             },
             PayloadV1::Recover(height, hash, txid) => Self {
                 block_height: height,
                 block_hash: hash,
                 prev_tx_chain: Some(txid),
+                state_commitment: None,
+                batch_root: None,
+                chain_id: None,
+                unknown_records: Vec::new(),
+            },
+        }
+    }
+}
+
+impl From<PayloadV2> for Payload {
+    fn from(v2: PayloadV2) -> Self {
+        match v2 {
+            PayloadV2::Regular(height, hash, state_commitment) => Self {
+                block_height: height,
+                block_hash: hash,
+                prev_tx_chain: None,
+                state_commitment: Some(state_commitment),
+                batch_root: None,
+                chain_id: None,
+                unknown_records: Vec::new(),
+            },
+            PayloadV2::Batch(height, hash, batch_root) => Self {
+                block_height: height,
+                block_hash: hash,
+                prev_tx_chain: None,
+                state_commitment: None,
+                batch_root: Some(batch_root),
+                chain_id: None,
+                unknown_records: Vec::new(),
+            },
+            PayloadV2::Identified(height, hash, chain_id) => Self {
+                block_height: height,
+                block_hash: hash,
+                prev_tx_chain: None,
+                state_commitment: None,
+                batch_root: None,
+                chain_id: Some(chain_id),
+                unknown_records: Vec::new(),
             },
         }
     }
@@ -234,10 +800,16 @@ mod tests {
     use exonum::crypto::{hash, Hash};
     use exonum::helpers::Height;
 
-    use bitcoin::blockdata::script::Script;
+    use bitcoin::blockdata::opcodes::All;
+    use bitcoin::blockdata::script::{Builder, Script};
     use hex;
 
-    use super::{Payload, PayloadBuilder};
+    use proptest::prelude::*;
+
+    use super::{
+        ChainId, Payload, PayloadBuilder, PayloadError, PayloadVersion, UnknownRecord,
+        MAX_PAYLOAD_LEN, PAYLOAD_PREFIX,
+    };
 
     trait HexValue {
         fn from_hex(hex: impl AsRef<[u8]>) -> Self;
@@ -262,7 +834,8 @@ mod tests {
         let payload_script = PayloadBuilder::new()
             .block_hash(block_hash)
             .block_height(Height(1234))
-            .into_script();
+            .into_script()
+            .unwrap();
 
         assert_eq!(
             payload_script.to_hex(),
@@ -293,7 +866,8 @@ mod tests {
             .block_hash(block_hash)
             .block_height(Height(1234))
             .prev_tx_chain(Some(prev_txid))
-            .into_script();
+            .into_script()
+            .unwrap();
 
         assert_eq!(
             payload_script.to_hex(),
@@ -320,6 +894,276 @@ mod tests {
         assert_eq!(payload.prev_tx_chain, Some(prev_txid));
     }
 
+    #[test]
+    fn test_payload_v2_roundtrip() {
+        let block_hash = hash(&[1]);
+        let state_commitment = Payload::state_commitment_for(&hash(&[2]), &hash(&[3]));
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .state_commitment(Some(state_commitment))
+            .into_script()
+            .unwrap();
+        // The payload fits into the standard `OP_RETURN` output.
+        assert_eq!(payload_script.len(), 83);
+        assert_eq!(&payload_script.to_hex()[..22], "6a4c5045584f4e554d0200");
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.prev_tx_chain, None);
+        assert_eq!(payload.state_commitment, Some(state_commitment));
+
+        // The recover payload is written in v.1.
+        let prev_txid = hash(&[4]);
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .prev_tx_chain(Some(prev_txid))
+            .state_commitment(Some(state_commitment))
+            .into_script()
+            .unwrap();
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.prev_tx_chain, Some(prev_txid));
+        assert_eq!(payload.state_commitment, None);
+    }
+
+    #[test]
+    fn test_payload_batch_roundtrip() {
+        let block_hash = hash(&[1]);
+        let batch_root = hash(&[2]);
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .state_commitment(Some(hash(&[3])))
+            .batch_root(Some(batch_root))
+            .into_script()
+            .unwrap();
+        assert_eq!(payload_script.len(), 83);
+        assert_eq!(&payload_script.to_hex()[..22], "6a4c5045584f4e554d0201");
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.batch_root, Some(batch_root));
+        assert_eq!(payload.state_commitment, None);
+        assert_eq!(payload.prev_tx_chain, None);
+    }
+
+    #[test]
+    fn test_payload_chain_id_roundtrip() {
+        let block_hash = hash(&[1]);
+        let chain_id = ChainId::from_genesis_hash(&hash(&[0]));
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .chain_id(Some(chain_id))
+            .into_script()
+            .unwrap();
+        assert_eq!(payload_script.len(), 58);
+        assert_eq!(&payload_script.to_hex()[..20], "6a3845584f4e554d0202");
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.chain_id, Some(chain_id));
+        assert!(payload.matches_chain(&chain_id));
+        assert!(!payload.matches_chain(&ChainId::from_genesis_hash(&hash(&[2]))));
+        assert_eq!(chain_id.to_string().parse::<ChainId>().unwrap(), chain_id);
+
+        // The recover payload has no room for the chain identifier.
+        let payload_script = PayloadBuilder::new()
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .prev_tx_chain(Some(hash(&[2])))
+            .chain_id(Some(chain_id))
+            .into_script()
+            .unwrap();
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.chain_id, None);
+        assert!(payload.matches_chain(&chain_id));
+    }
+
+    #[test]
+    fn test_payload_v3_roundtrip() {
+        let block_hash = hash(&[1]);
+        let chain_id = ChainId::from_genesis_hash(&hash(&[0]));
+        let payload_script = PayloadBuilder::new()
+            .version(PayloadVersion::V3)
+            .block_hash(block_hash)
+            .block_height(Height(1234))
+            .chain_id(Some(chain_id))
+            .unknown_record(100, vec![1, 2, 3])
+            .into_script()
+            .unwrap();
+        // Header, height, block hash, chain identifier and the unknown record.
+        assert_eq!(payload_script.len(), 2 + 7 + 4 + 34 + 10 + 5);
+        assert_eq!(&payload_script.to_hex()[..18], "6a3c45584f4e554d03");
+
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_hash, block_hash);
+        assert_eq!(payload.block_height, Height(1234));
+        assert_eq!(payload.chain_id, Some(chain_id));
+        assert_eq!(payload.prev_tx_chain, None);
+        assert_eq!(
+            payload.unknown_records,
+            vec![UnknownRecord {
+                record_type: 100,
+                value: vec![1, 2, 3],
+            }]
+        );
+
+        // The recover payload with the whole height fits exactly into the limit.
+        let payload_script = PayloadBuilder::new()
+            .version(PayloadVersion::V3)
+            .block_hash(block_hash)
+            .block_height(Height(0xFF_FFFF))
+            .prev_tx_chain(Some(hash(&[2])))
+            .into_script()
+            .unwrap();
+        assert_eq!(payload_script.len(), 83);
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_height, Height(0xFF_FFFF));
+        assert_eq!(payload.prev_tx_chain, Some(hash(&[2])));
+    }
+
+    #[test]
+    fn test_payload_v3_optional_records() {
+        let block_hash = hash(&[1]);
+        let state_commitment = hash(&[2]);
+        let chain_id = ChainId::from_genesis_hash(&hash(&[0]));
+        let builder = |height| {
+            PayloadBuilder::new()
+                .version(PayloadVersion::V3)
+                .block_hash(block_hash)
+                .block_height(height)
+                .state_commitment(Some(state_commitment))
+                .chain_id(Some(chain_id))
+        };
+
+        // The chain identifier doesn't fit along with the state commitment.
+        assert_eq!(
+            builder(Height(1)).into_script().unwrap_err(),
+            PayloadError::TooLarge {
+                size: 88,
+                limit: MAX_PAYLOAD_LEN,
+            }
+        );
+
+        // The state commitment fits up to the height 2^24.
+        let builder = |height| builder(height).chain_id(None);
+        let payload_script = builder(Height(0xFF_FFFF)).into_script().unwrap();
+        assert_eq!(payload_script.len(), 83);
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_height, Height(0xFF_FFFF));
+        assert_eq!(payload.state_commitment, Some(state_commitment));
+        assert_eq!(
+            builder(Height(0x100_0000)).into_script().unwrap_err(),
+            PayloadError::TooLarge {
+                size: 81,
+                limit: MAX_PAYLOAD_LEN,
+            }
+        );
+
+        // The chain identifier fits along with the whole height.
+        let payload_script = PayloadBuilder::new()
+            .version(PayloadVersion::V3)
+            .block_hash(block_hash)
+            .block_height(Height(u64::max_value()))
+            .chain_id(Some(chain_id))
+            .into_script()
+            .unwrap();
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_height, Height(u64::max_value()));
+        assert_eq!(payload.chain_id, Some(chain_id));
+
+        // The recover payload has no room for the chain identifier.
+        let prev_txid = hash(&[3]);
+        let builder = || {
+            PayloadBuilder::new()
+                .version(PayloadVersion::V3)
+                .block_hash(block_hash)
+                .block_height(Height(1234))
+                .prev_tx_chain(Some(prev_txid))
+        };
+        assert_eq!(
+            builder()
+                .chain_id(Some(chain_id))
+                .into_script()
+                .unwrap_err(),
+            PayloadError::TooLarge {
+                size: 89,
+                limit: MAX_PAYLOAD_LEN,
+            }
+        );
+        let payload_script = builder().into_script().unwrap();
+        assert_eq!(&payload_script.to_hex()[..20], "6a4c4f45584f4e554d03");
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.prev_tx_chain, Some(prev_txid));
+
+        // The recover payload with the whole height is written in v.1.
+        let payload_script = PayloadBuilder::new()
+            .version(PayloadVersion::V3)
+            .block_hash(block_hash)
+            .block_height(Height(0x100_0000))
+            .prev_tx_chain(Some(prev_txid))
+            .into_script()
+            .unwrap();
+        assert_eq!(&payload_script.to_hex()[..22], "6a4c5045584f4e554d0101");
+        let payload = Payload::from_script(&payload_script).unwrap();
+        assert_eq!(payload.block_height, Height(0x100_0000));
+        assert_eq!(payload.prev_tx_chain, Some(prev_txid));
+    }
+
+    #[test]
+    fn test_payload_v3_errors() {
+        // Unknown records don't fit along with the state commitment.
+        let builder = PayloadBuilder::new()
+            .version(PayloadVersion::V3)
+            .block_hash(hash(&[1]))
+            .block_height(Height(1))
+            .state_commitment(Some(hash(&[2])))
+            .unknown_record(100, vec![0; 40]);
+        assert_eq!(
+            builder.into_script().unwrap_err(),
+            PayloadError::TooLarge {
+                size: 120,
+                limit: MAX_PAYLOAD_LEN,
+            }
+        );
+
+        let builder = PayloadBuilder::new()
+            .version(PayloadVersion::V3)
+            .block_hash(hash(&[1]))
+            .block_height(Height(1))
+            .unknown_record(2, vec![0; 32]);
+        assert_eq!(
+            builder.into_script().unwrap_err(),
+            PayloadError::ReservedRecordType { record_type: 2 }
+        );
+        assert_eq!(
+            PayloadError::TooLarge {
+                size: 86,
+                limit: MAX_PAYLOAD_LEN,
+            }
+            .to_string(),
+            "Payload size 86 exceeds the limit of the standard OP_RETURN output, which is 80 bytes"
+        );
+    }
+
+    #[test]
+    fn test_payload_v3_incorrect_deserialize() {
+        // Truncated record.
+        let payload_script = Script::from_hex("6a0b45584f4e554d0301030a0b");
+        assert_eq!(Payload::from_script(&payload_script), None);
+        // Block hash is absent.
+        let payload_script = Script::from_hex("6a0a45584f4e554d0301010a");
+        assert_eq!(Payload::from_script(&payload_script), None);
+        // Duplicate height.
+        let payload_script = Script::from_hex("6a0d45584f4e554d0301010a01010b");
+        assert_eq!(Payload::from_script(&payload_script), None);
+    }
+
     #[test]
     fn test_payload_incorrect_deserialize() {
         // Payload from old anchoring transaction
@@ -336,4 +1180,197 @@ mod tests {
         let script_pubkey = Script::from_hex("a91472b7506704dc074fa46359251052e781d96f939a87");
         assert_eq!(Payload::from_script(&script_pubkey), None);
     }
+
+    fn any_hash() -> impl Strategy<Value = Hash> {
+        prop::array::uniform32(any::<u8>()).prop_map(Hash::new)
+    }
+
+    fn any_height() -> impl Strategy<Value = Height> {
+        any::<u64>().prop_map(Height)
+    }
+
+    fn any_chain_id() -> impl Strategy<Value = ChainId> {
+        any::<[u8; 8]>().prop_map(ChainId)
+    }
+
+    fn any_unknown_record() -> impl Strategy<Value = UnknownRecord> {
+        let record_type = any::<u8>().prop_filter("Known record type", |&record_type| {
+            record_type == 0 || record_type > 6
+        });
+        (record_type, prop::collection::vec(any::<u8>(), 0..16))
+            .prop_map(|(record_type, value)| UnknownRecord { record_type, value })
+    }
+
+    fn roundtrip(builder: PayloadBuilder) -> Payload {
+        let script = builder.into_script().unwrap();
+        assert!(script.len() <= MAX_PAYLOAD_LEN + 3);
+        Payload::from_script(&script).unwrap()
+    }
+
+    proptest! {
+        #[test]
+        fn test_payload_v1_regular_roundtrip(height in any_height(), block_hash in any_hash()) {
+            let payload = roundtrip(
+                PayloadBuilder::new()
+                    .block_height(height)
+                    .block_hash(block_hash),
+            );
+            prop_assert_eq!(payload.block_height, height);
+            prop_assert_eq!(payload.block_hash, block_hash);
+            prop_assert_eq!(payload.prev_tx_chain, None);
+        }
+
+        #[test]
+        fn test_payload_v1_recover_roundtrip(height in any_height(),
+                                             block_hash in any_hash(),
+                                             prev_txid in any_hash(),
+                                             state_commitment in prop::option::of(any_hash()),
+                                             chain_id in prop::option::of(any_chain_id())) {
+            // The recover kind takes precedence over the other kinds.
+            let payload = roundtrip(
+                PayloadBuilder::new()
+                    .block_height(height)
+                    .block_hash(block_hash)
+                    .prev_tx_chain(Some(prev_txid))
+                    .state_commitment(state_commitment)
+                    .chain_id(chain_id),
+            );
+            prop_assert_eq!(payload.block_height, height);
+            prop_assert_eq!(payload.block_hash, block_hash);
+            prop_assert_eq!(payload.prev_tx_chain, Some(prev_txid));
+            prop_assert_eq!(payload.state_commitment, None);
+            prop_assert_eq!(payload.chain_id, None);
+        }
+
+        #[test]
+        fn test_payload_v2_any_roundtrip(height in any_height(),
+                                         block_hash in any_hash(),
+                                         state_commitment in prop::option::of(any_hash()),
+                                         batch_root in prop::option::of(any_hash()),
+                                         chain_id in any_chain_id()) {
+            let payload = roundtrip(
+                PayloadBuilder::new()
+                    .block_height(height)
+                    .block_hash(block_hash)
+                    .state_commitment(state_commitment)
+                    .batch_root(batch_root)
+                    .chain_id(Some(chain_id)),
+            );
+            prop_assert_eq!(payload.block_height, height);
+            prop_assert_eq!(payload.block_hash, block_hash);
+            prop_assert_eq!(payload.prev_tx_chain, None);
+            prop_assert_eq!(payload.batch_root, batch_root);
+            prop_assert_eq!(
+                payload.state_commitment,
+                if batch_root.is_some() { None } else { state_commitment }
+            );
+            prop_assert_eq!(
+                payload.chain_id,
+                if batch_root.is_some() || state_commitment.is_some() {
+                    None
+                } else {
+                    Some(chain_id)
+                }
+            );
+        }
+
+        #[test]
+        fn test_payload_v3_roundtrip_or_error(height in any_height(),
+                                              block_hash in any_hash(),
+                                              prev_txid in prop::option::of(any_hash()),
+                                              state_commitment in prop::option::of(any_hash()),
+                                              batch_root in prop::option::of(any_hash()),
+                                              chain_id in prop::option::of(any_chain_id()),
+                                              ref unknown_records in prop::collection::vec(
+                                                  any_unknown_record(), 0..3
+                                              )) {
+            let builder = unknown_records.iter().cloned().fold(
+                PayloadBuilder::new()
+                    .version(PayloadVersion::V3)
+                    .block_height(height)
+                    .block_hash(block_hash)
+                    .prev_tx_chain(prev_txid)
+                    .state_commitment(state_commitment)
+                    .batch_root(batch_root)
+                    .chain_id(chain_id),
+                |builder, record| builder.unknown_record(record.record_type, record.value),
+            );
+
+            match builder.into_script() {
+                Ok(script) => {
+                    prop_assert!(script.len() <= MAX_PAYLOAD_LEN + 3);
+                    let payload = Payload::from_script(&script).unwrap();
+                    prop_assert_eq!(payload.block_height, height);
+                    prop_assert_eq!(payload.block_hash, block_hash);
+                    prop_assert_eq!(payload.prev_tx_chain, prev_txid);
+                    // The optional records are never omitted.
+                    prop_assert_eq!(payload.state_commitment, state_commitment);
+                    prop_assert_eq!(payload.batch_root, batch_root);
+                    prop_assert_eq!(payload.chain_id, chain_id);
+                    if payload.unknown_records.is_empty() {
+                        prop_assert!(unknown_records.is_empty());
+                    } else {
+                        prop_assert_eq!(&payload.unknown_records, unknown_records);
+                    }
+                }
+                Err(PayloadError::TooLarge { size, .. }) => {
+                    prop_assert!(size > MAX_PAYLOAD_LEN);
+                    prop_assert!(
+                        !unknown_records.is_empty()
+                            || state_commitment.is_some()
+                            || batch_root.is_some()
+                            || chain_id.is_some()
+                    );
+                }
+                Err(e) => prop_assert!(false, "Unexpected error: {}", e),
+            }
+        }
+
+        #[test]
+        fn test_payload_from_arbitrary_script(ref bytes in prop::collection::vec(
+                                                  any::<u8>(), 0..100
+                                              )) {
+            // Parsing must never panic.
+            Payload::from_script(&Script::from(bytes.clone()));
+        }
+
+        #[test]
+        fn test_payload_from_arbitrary_data(version in 0u8..5,
+                                            kind in 0u8..4,
+                                            len in prop_oneof![
+                                                Just(41_usize), Just(49), Just(73), 0_usize..80
+                                            ],
+                                            ref data in prop::collection::vec(any::<u8>(), 80)) {
+            // The data after the valid prefix, including the truncated headers and the
+            // lengths of the fixed layouts, which consist of the kind byte and the data.
+            let mut payload_bytes = PAYLOAD_PREFIX.to_vec();
+            payload_bytes.push(version);
+            if len > 0 {
+                payload_bytes.push(kind);
+                payload_bytes.extend_from_slice(&data[..len - 1]);
+            }
+            let script = Builder::new()
+                .push_opcode(All::OP_RETURN)
+                .push_slice(&payload_bytes)
+                .into_script();
+
+            if let Some(payload) = Payload::from_script(&script) {
+                prop_assert!(version >= 1 && version <= 3);
+                // The fixed layouts are canonical, so the parsed payload is encoded back
+                // into the same script.
+                if version < 3 {
+                    let rebuilt = PayloadBuilder::new()
+                        .block_height(payload.block_height)
+                        .block_hash(payload.block_hash)
+                        .prev_tx_chain(payload.prev_tx_chain)
+                        .state_commitment(payload.state_commitment)
+                        .batch_root(payload.batch_root)
+                        .chain_id(payload.chain_id)
+                        .into_script()
+                        .unwrap();
+                    prop_assert_eq!(rebuilt, script);
+                }
+            }
+        }
+    }
 }
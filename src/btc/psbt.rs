@@ -0,0 +1,467 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use base64;
+use byteorder::{ByteOrder, LittleEndian};
+
+use bitcoin::blockdata::script::Script;
+use bitcoin::blockdata::transaction::{self, TxOut};
+use bitcoin::consensus;
+use btc_transaction_utils::{self, multisig::RedeemScript};
+use secp256k1;
+
+use std::collections::BTreeMap;
+
+use super::{InputSignature, PublicKey, Transaction};
+
+/// Magic bytes of the serialized PSBT.
+const PSBT_MAGIC: [u8; 5] = [0x70, 0x73, 0x62, 0x74, 0xff];
+/// Separator of the PSBT key-value maps.
+const PSBT_SEPARATOR: u8 = 0x00;
+
+const PSBT_GLOBAL_UNSIGNED_TX: u8 = 0x00;
+
+const PSBT_IN_NON_WITNESS_UTXO: u8 = 0x00;
+const PSBT_IN_WITNESS_UTXO: u8 = 0x01;
+const PSBT_IN_PARTIAL_SIG: u8 = 0x02;
+const PSBT_IN_SIGHASH_TYPE: u8 = 0x03;
+const PSBT_IN_WITNESS_SCRIPT: u8 = 0x05;
+
+/// Sighash type used by the anchoring transactions.
+const SIGHASH_ALL: u32 = 0x01;
+
+/// Raw key-value pairs of the PSBT map that are not interpreted by this implementation.
+pub type UnknownEntries = BTreeMap<Vec<u8>, Vec<u8>>;
+
+/// Partially signed Bitcoin transaction in the [BIP 174] format.
+///
+/// Only the fields that are needed to sign the anchoring transaction inputs are
+/// interpreted, the other fields are preserved as is.
+///
+/// [BIP 174]: https://github.com/bitcoin/bips/blob/master/bip-0174.mediawiki
+#[derive(Debug, Clone, PartialEq)]
+pub struct Psbt {
+    /// Transaction with the empty input scripts and witnesses.
+    pub unsigned_tx: Transaction,
+    /// Signing data of the transaction inputs.
+    pub inputs: Vec<PsbtInput>,
+    /// Unknown entries of the transaction outputs.
+    pub outputs: Vec<UnknownEntries>,
+    /// Unknown entries of the global map.
+    pub unknown: UnknownEntries,
+}
+
+/// Signing data of the single PSBT input.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct PsbtInput {
+    /// Transaction which output is spent by this input.
+    pub non_witness_utxo: Option<Transaction>,
+    /// Output which is spent by this input.
+    pub witness_utxo: Option<TxOut>,
+    /// Signatures of this input with the corresponding public keys.
+    pub partial_sigs: Vec<(PublicKey, InputSignature)>,
+    /// Sighash type which should be used to sign this input.
+    pub sighash_type: Option<u32>,
+    /// Witness script of the spent output.
+    pub witness_script: Option<Script>,
+    /// Unknown entries of this input.
+    pub unknown: UnknownEntries,
+}
+
+/// PSBT decoding errors.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum PsbtError {
+    /// PSBT is malformed.
+    #[fail(display = "PSBT is malformed: {}.", _0)]
+    Malformed(String),
+}
+
+impl Psbt {
+    /// Creates PSBT for the anchoring proposal with the given transactions which outputs are
+    /// spent by the proposal inputs and the redeem script of the anchoring address.
+    pub fn from_anchoring_proposal(
+        proposal: &Transaction,
+        inputs: &[Transaction],
+        redeem_script: &RedeemScript,
+    ) -> Self {
+        let mut unsigned_tx = proposal.clone();
+        for input in &mut unsigned_tx.0.input {
+            input.script_sig = Script::new();
+            input.witness.clear();
+        }
+
+        let inputs = proposal
+            .0
+            .input
+            .iter()
+            .zip(inputs)
+            .map(|(input, prev_tx)| PsbtInput {
+                non_witness_utxo: Some(prev_tx.clone()),
+                witness_utxo: prev_tx
+                    .0
+                    .output
+                    .get(input.previous_output.vout as usize)
+                    .cloned(),
+                sighash_type: Some(SIGHASH_ALL),
+                witness_script: Some(redeem_script.as_ref().clone()),
+                ..PsbtInput::default()
+            })
+            .collect();
+
+        Psbt {
+            outputs: vec![UnknownEntries::new(); unsigned_tx.0.output.len()],
+            unsigned_tx,
+            inputs,
+            unknown: UnknownEntries::new(),
+        }
+    }
+
+    /// Returns signatures made with the given public key along with the indexes
+    /// of the signed inputs.
+    pub fn input_signatures(&self, public_key: &PublicKey) -> Vec<(u32, InputSignature)> {
+        self.inputs
+            .iter()
+            .enumerate()
+            .filter_map(|(index, input)| {
+                input
+                    .partial_sigs
+                    .iter()
+                    .find(|(key, _)| key == public_key)
+                    .map(|(_, signature)| (index as u32, signature.clone()))
+            })
+            .collect()
+    }
+
+    /// Serializes PSBT into bytes.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut buf = PSBT_MAGIC.to_vec();
+
+        write_entry(
+            &mut buf,
+            &[PSBT_GLOBAL_UNSIGNED_TX],
+            &consensus::serialize(&self.unsigned_tx.0),
+        );
+        write_map_end(&mut buf, &self.unknown);
+
+        for input in &self.inputs {
+            if let Some(ref prev_tx) = input.non_witness_utxo {
+                write_entry(
+                    &mut buf,
+                    &[PSBT_IN_NON_WITNESS_UTXO],
+                    &consensus::serialize(&prev_tx.0),
+                );
+            }
+            if let Some(ref output) = input.witness_utxo {
+                write_entry(
+                    &mut buf,
+                    &[PSBT_IN_WITNESS_UTXO],
+                    &consensus::serialize(output),
+                );
+            }
+            for (public_key, signature) in &input.partial_sigs {
+                let mut key = vec![PSBT_IN_PARTIAL_SIG];
+                key.extend_from_slice(&public_key.0.serialize());
+                write_entry(&mut buf, &key, signature.0.as_ref());
+            }
+            if let Some(sighash_type) = input.sighash_type {
+                let mut value = [0; 4];
+                LittleEndian::write_u32(&mut value, sighash_type);
+                write_entry(&mut buf, &[PSBT_IN_SIGHASH_TYPE], &value);
+            }
+            if let Some(ref script) = input.witness_script {
+                write_entry(&mut buf, &[PSBT_IN_WITNESS_SCRIPT], script.as_bytes());
+            }
+            write_map_end(&mut buf, &input.unknown);
+        }
+
+        for output in &self.outputs {
+            write_map_end(&mut buf, output);
+        }
+        buf
+    }
+
+    /// Deserializes PSBT from bytes.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, PsbtError> {
+        let mut reader = Reader { data: bytes };
+        if reader.read_bytes(PSBT_MAGIC.len())? != &PSBT_MAGIC[..] {
+            return Err(malformed("invalid magic bytes"));
+        }
+
+        let mut unknown = reader.read_map()?;
+        let unsigned_tx = unknown
+            .remove(&vec![PSBT_GLOBAL_UNSIGNED_TX])
+            .ok_or_else(|| malformed("unsigned transaction is absent"))?;
+        let unsigned_tx: transaction::Transaction = consensus::deserialize(&unsigned_tx)
+            .map_err(|e| malformed(format!("invalid unsigned transaction: {}", e)))?;
+        if unsigned_tx
+            .input
+            .iter()
+            .any(|input| !input.script_sig.is_empty() || !input.witness.is_empty())
+        {
+            return Err(malformed("unsigned transaction has non-empty inputs"));
+        }
+
+        let inputs = (0..unsigned_tx.input.len())
+            .map(|_| reader.read_map().and_then(parse_input))
+            .collect::<Result<Vec<_>, _>>()?;
+        let outputs = (0..unsigned_tx.output.len())
+            .map(|_| reader.read_map())
+            .collect::<Result<Vec<_>, _>>()?;
+        if !reader.data.is_empty() {
+            return Err(malformed("trailing bytes"));
+        }
+
+        Ok(Psbt {
+            unsigned_tx: unsigned_tx.into(),
+            inputs,
+            outputs,
+            unknown,
+        })
+    }
+}
+
+impl ::std::fmt::Display for Psbt {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{}", base64::encode(&self.to_bytes()))
+    }
+}
+
+impl ::std::str::FromStr for Psbt {
+    type Err = PsbtError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bytes = base64::decode(s.trim()).map_err(|e| malformed(e.to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+impl_serde_str! { Psbt }
+
+fn malformed(reason: impl Into<String>) -> PsbtError {
+    PsbtError::Malformed(reason.into())
+}
+
+fn parse_input(mut entries: UnknownEntries) -> Result<PsbtInput, PsbtError> {
+    let mut input = PsbtInput::default();
+
+    if let Some(value) = entries.remove(&vec![PSBT_IN_NON_WITNESS_UTXO]) {
+        let prev_tx: transaction::Transaction = consensus::deserialize(&value)
+            .map_err(|e| malformed(format!("invalid input transaction: {}", e)))?;
+        input.non_witness_utxo = Some(prev_tx.into());
+    }
+    if let Some(value) = entries.remove(&vec![PSBT_IN_WITNESS_UTXO]) {
+        let output: TxOut = consensus::deserialize(&value)
+            .map_err(|e| malformed(format!("invalid witness output: {}", e)))?;
+        input.witness_utxo = Some(output);
+    }
+    if let Some(value) = entries.remove(&vec![PSBT_IN_SIGHASH_TYPE]) {
+        if value.len() != 4 {
+            return Err(malformed("invalid sighash type"));
+        }
+        input.sighash_type = Some(LittleEndian::read_u32(&value));
+    }
+    if let Some(value) = entries.remove(&vec![PSBT_IN_WITNESS_SCRIPT]) {
+        input.witness_script = Some(Script::from(value));
+    }
+
+    let signature_keys = entries
+        .keys()
+        .filter(|key| key[0] == PSBT_IN_PARTIAL_SIG)
+        .cloned()
+        .collect::<Vec<_>>();
+    let context = secp256k1::Secp256k1::without_caps();
+    for key in signature_keys {
+        let value = entries.remove(&key).unwrap();
+        let public_key = secp256k1::PublicKey::from_slice(&context, &key[1..])
+            .map_err(|e| malformed(format!("invalid public key: {}", e)))?;
+        let signature = btc_transaction_utils::InputSignature::from_bytes(&context, value)
+            .map_err(|e| malformed(format!("invalid signature: {}", e)))?;
+        input
+            .partial_sigs
+            .push((PublicKey(public_key), InputSignature(signature)));
+    }
+
+    input.unknown = entries;
+    Ok(input)
+}
+
+fn write_compact_size(buf: &mut Vec<u8>, value: usize) {
+    let value = value as u64;
+    if value < 0xfd {
+        buf.push(value as u8);
+    } else if value <= 0xffff {
+        let mut bytes = [0; 2];
+        LittleEndian::write_u16(&mut bytes, value as u16);
+        buf.push(0xfd);
+        buf.extend_from_slice(&bytes);
+    } else if value <= 0xffff_ffff {
+        let mut bytes = [0; 4];
+        LittleEndian::write_u32(&mut bytes, value as u32);
+        buf.push(0xfe);
+        buf.extend_from_slice(&bytes);
+    } else {
+        let mut bytes = [0; 8];
+        LittleEndian::write_u64(&mut bytes, value);
+        buf.push(0xff);
+        buf.extend_from_slice(&bytes);
+    }
+}
+
+fn write_entry(buf: &mut Vec<u8>, key: &[u8], value: &[u8]) {
+    write_compact_size(buf, key.len());
+    buf.extend_from_slice(key);
+    write_compact_size(buf, value.len());
+    buf.extend_from_slice(value);
+}
+
+fn write_map_end(buf: &mut Vec<u8>, unknown: &UnknownEntries) {
+    for (key, value) in unknown {
+        write_entry(buf, key, value);
+    }
+    buf.push(PSBT_SEPARATOR);
+}
+
+/// Reader of the serialized PSBT.
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], PsbtError> {
+        if self.data.len() < len {
+            return Err(malformed("unexpected end of data"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read_compact_size(&mut self) -> Result<usize, PsbtError> {
+        let value = match self.read_bytes(1)?[0] {
+            0xfd => u64::from(LittleEndian::read_u16(self.read_bytes(2)?)),
+            0xfe => u64::from(LittleEndian::read_u32(self.read_bytes(4)?)),
+            0xff => LittleEndian::read_u64(self.read_bytes(8)?),
+            value => u64::from(value),
+        };
+        // Rejects the lengths which do not fit into `usize` as well.
+        if value > self.data.len() as u64 {
+            return Err(malformed("unexpected end of data"));
+        }
+        Ok(value as usize)
+    }
+
+    /// Reads key-value pairs until the separator.
+    fn read_map(&mut self) -> Result<UnknownEntries, PsbtError> {
+        let mut entries = UnknownEntries::new();
+        loop {
+            let key_len = self.read_compact_size()?;
+            if key_len == 0 {
+                return Ok(entries);
+            }
+            let key = self.read_bytes(key_len)?.to_vec();
+            let value_len = self.read_compact_size()?;
+            let value = self.read_bytes(value_len)?.to_vec();
+            if entries.insert(key, value).is_some() {
+                return Err(malformed("duplicate key"));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::Hash;
+    use exonum::helpers::Height;
+
+    use bitcoin::network::constants::Network;
+    use btc_transaction_utils::multisig::RedeemScriptBuilder;
+    use btc_transaction_utils::{p2wsh, TxInRef};
+
+    use super::{Psbt, PsbtError, SIGHASH_ALL};
+    use btc::{gen_keypair, Address, BtcAnchoringTransactionBuilder};
+    use test_helpers::testkit::create_fake_funding_transaction;
+
+    #[test]
+    fn test_psbt_roundtrip_with_signatures() {
+        let keypairs = (0..3)
+            .map(|_| gen_keypair(Network::Testnet))
+            .collect::<Vec<_>>();
+        let redeem_script = RedeemScriptBuilder::with_public_keys(
+            keypairs.iter().map(|(public_key, _)| public_key.0),
+        )
+        .quorum(2)
+        .to_script()
+        .unwrap();
+        let address = Address(p2wsh::address(&redeem_script, Network::Testnet));
+        let funding_tx = create_fake_funding_transaction(&address, 100_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(1);
+        builder.payload(Height::zero(), Hash::zero());
+        let (proposal, inputs) = builder.create().unwrap();
+
+        let mut psbt = Psbt::from_anchoring_proposal(&proposal, &inputs, &redeem_script);
+        assert_eq!(psbt.inputs.len(), 1);
+        assert_eq!(psbt.inputs[0].non_witness_utxo, Some(funding_tx.clone()));
+        assert_eq!(psbt.inputs[0].sighash_type, Some(SIGHASH_ALL));
+        assert_eq!(
+            psbt.inputs[0].witness_script.as_ref(),
+            Some(redeem_script.as_ref())
+        );
+        assert_eq!(psbt.to_string().parse::<Psbt>().unwrap(), psbt);
+
+        // The offline signer adds its signature and the unknown entries are preserved.
+        let (ref public_key, ref privkey) = keypairs[1];
+        let signature = p2wsh::InputSigner::new(redeem_script.clone())
+            .sign_input(
+                TxInRef::new(proposal.as_ref(), 0),
+                funding_tx.as_ref(),
+                privkey.0.secret_key(),
+            )
+            .unwrap();
+        psbt.inputs[0]
+            .partial_sigs
+            .push((*public_key, signature.into()));
+        psbt.inputs[0]
+            .unknown
+            .insert(vec![0xfc, 0x01], vec![0xde, 0xad]);
+        psbt.outputs[0].insert(vec![0xfc, 0x02], vec![0xbe, 0xef]);
+
+        let psbt2 = psbt.to_string().parse::<Psbt>().unwrap();
+        assert_eq!(psbt2, psbt);
+        let signatures = psbt2.input_signatures(public_key);
+        assert_eq!(signatures.len(), 1);
+        assert_eq!(signatures[0].0, 0);
+        assert!(psbt2.input_signatures(&keypairs[0].0).is_empty());
+
+        let serde_psbt: Psbt = ::serde_json::from_value(json!(psbt2)).unwrap();
+        assert_eq!(serde_psbt, psbt);
+    }
+
+    #[test]
+    fn test_psbt_malformed() {
+        assert_matches!(Psbt::from_bytes(b"psbt"), Err(PsbtError::Malformed(_)));
+        // No unsigned transaction in the global map.
+        assert_matches!(
+            Psbt::from_bytes(&[0x70, 0x73, 0x62, 0x74, 0xff, 0x00]),
+            Err(PsbtError::Malformed(_))
+        );
+        // Length of the key exceeds the data.
+        assert_matches!(
+            Psbt::from_bytes(&[0x70, 0x73, 0x62, 0x74, 0xff, 0xfd, 0xff, 0xff]),
+            Err(PsbtError::Malformed(_))
+        );
+    }
+}
@@ -14,7 +14,21 @@ use bitcoin::blockdata::script::Script;
 use bitcoin::blockdata::transaction::{self, OutPoint, TxIn, TxOut};
 use btc_transaction_utils::multisig::RedeemScript;
 
-use super::{payload::PayloadBuilder, Payload};
+use super::{payload::PayloadBuilder, ChainId, Payload, PayloadError, PayloadVersion};
+
+/// Weight of the non-witness byte in the weight units (BIP141).
+const WITNESS_SCALE_FACTOR: u64 = 4;
+/// Size of the segwit marker and flag fields.
+const SEGWIT_MARKER_SIZE: u64 = 2;
+/// Maximum size of the DER-encoded signature including the sighash type byte.
+const MAX_SIGNATURE_SIZE: u64 = 73;
+/// Minimal value of the P2WSH output which is relayed by the Bitcoin nodes with
+/// the default dust relay fee.
+const DUST_LIMIT: u64 = 330;
+/// Sequence number of the inputs which are not replaceable.
+const FINAL_SEQUENCE: u32 = 0xFFFF_FFFF;
+/// Sequence number of the inputs which signal the opt-in Replace-By-Fee (BIP125).
+const RBF_SEQUENCE: u32 = 0xFFFF_FFFD;
 
 /// Bitcoin transaction wrapper.
 #[derive(Debug, Clone, From, Into, PartialEq)]
@@ -39,10 +53,38 @@ impl Transaction {
 
     /// Returns the previous anchoring transaction identifier.
     pub fn prev_tx_id(&self) -> Hash {
-        let mut bytes = [0_u8; 32];
-        bytes.copy_from_slice(&self.0.input[0].previous_output.txid[..]);
-        bytes.reverse();
-        Hash::new(bytes)
+        self.input_tx_ids()[0]
+    }
+
+    /// Returns identifiers of the transactions spent by this transaction.
+    pub fn input_tx_ids(&self) -> Vec<Hash> {
+        self.spent_outputs()
+            .into_iter()
+            .map(|(txid, _)| txid)
+            .collect()
+    }
+
+    /// Returns the outputs spent by this transaction as pairs of the transaction
+    /// identifier and the output index.
+    pub fn spent_outputs(&self) -> Vec<(Hash, u32)> {
+        self.0
+            .input
+            .iter()
+            .map(|input| {
+                let mut bytes = [0_u8; 32];
+                bytes.copy_from_slice(&input.previous_output.txid[..]);
+                bytes.reverse();
+                (Hash::new(bytes), input.previous_output.vout)
+            })
+            .collect()
+    }
+
+    /// Checks that the transaction signals the opt-in Replace-By-Fee (BIP125).
+    pub fn signals_rbf(&self) -> bool {
+        self.0
+            .input
+            .iter()
+            .any(|input| input.sequence < FINAL_SEQUENCE - 1)
     }
 
     /// Find output number for the given script pubkey.
@@ -68,6 +110,19 @@ impl Transaction {
         Some((script_pubkey, payload))
     }
 
+    /// Returns the virtual size of the transaction (BIP141).
+    pub fn vsize(&self) -> u64 {
+        let full_size = ::bitcoin::consensus::serialize(&self.0).len() as u64;
+        let base_size = {
+            let mut stripped_tx = self.0.clone();
+            for input in &mut stripped_tx.input {
+                input.witness.clear();
+            }
+            ::bitcoin::consensus::serialize(&stripped_tx).len() as u64
+        };
+        weight_to_vsize(base_size * (WITNESS_SCALE_FACTOR - 1) + full_size)
+    }
+
     /// Returns the total available amount for the transaction
     /// if it is the anchoring transaction.
     pub fn unspent_value(&self) -> Option<u64> {
@@ -78,13 +133,19 @@ impl Transaction {
 /// Builder for the anchoring transactions.
 #[derive(Debug)]
 pub struct BtcAnchoringTransactionBuilder {
+    redeem_script: RedeemScript,
     script_pubkey: Script,
     transit_to: Option<Script>,
     prev_tx: Option<Transaction>,
     recovery_tx: Option<Hash>,
     additional_funds: Vec<(usize, Transaction)>,
     fee: Option<u64>,
+    replace_by_fee: bool,
     payload: Option<(Height, Hash)>,
+    state_commitment: Option<Hash>,
+    batch_root: Option<Hash>,
+    chain_id: Option<ChainId>,
+    payload_version: PayloadVersion,
 }
 
 /// Anchoring transaction builder errors.
@@ -102,6 +163,17 @@ pub enum BuilderError {
         /// Available balance.
         balance: u64,
     },
+    /// Change output value is below the dust limit.
+    #[fail(
+        display = "Change output value {} is below the dust limit {}.",
+        value, limit
+    )]
+    DustOutput {
+        /// Value of the change output.
+        value: u64,
+        /// Minimal value of the output.
+        limit: u64,
+    },
     /// At least one input should be provided.
     #[fail(display = "At least one input should be provided.")]
     NoInputs,
@@ -111,19 +183,28 @@ pub enum BuilderError {
     /// Funding transaction doesn't contains outputs to the anchoring address.
     #[fail(display = "Funding transaction doesn't contains outputs to the anchoring address.")]
     UnsuitableFundingTx,
+    /// Unable to build the anchoring transaction payload.
+    #[fail(display = "Unable to build the anchoring transaction payload: {}", _0)]
+    Payload(PayloadError),
 }
 
 impl BtcAnchoringTransactionBuilder {
     /// Creates a new btc anchoring transaction builder for the given redeem script.
     pub fn new(redeem_script: &RedeemScript) -> BtcAnchoringTransactionBuilder {
         BtcAnchoringTransactionBuilder {
+            redeem_script: redeem_script.clone(),
             script_pubkey: redeem_script.as_ref().to_v0_p2wsh(),
             transit_to: None,
             prev_tx: None,
             recovery_tx: None,
             additional_funds: Vec::default(),
             fee: None,
+            replace_by_fee: false,
             payload: None,
+            state_commitment: None,
+            batch_root: None,
+            chain_id: None,
+            payload_version: PayloadVersion::default(),
         }
     }
 
@@ -160,20 +241,66 @@ impl BtcAnchoringTransactionBuilder {
         Ok(())
     }
 
-    /// Sets the fee per byte value.
+    /// Add the given output of the funding transaction as additional input
+    /// for the following anchoring transaction.
+    pub fn additional_funds_output(
+        &mut self,
+        tx: Transaction,
+        out: usize,
+    ) -> Result<(), BuilderError> {
+        match tx.0.output.get(out) {
+            Some(output) if output.script_pubkey == self.script_pubkey => {}
+            _ => return Err(BuilderError::UnsuitableFundingTx),
+        }
+        self.additional_funds.push((out, tx));
+        Ok(())
+    }
+
+    /// Sets the fee per virtual byte value.
     pub fn fee(&mut self, fee: u64) {
         self.fee = Some(fee);
     }
 
+    /// Enables the opt-in Replace-By-Fee signalling for the transaction inputs.
+    pub fn replace_by_fee(&mut self, enabled: bool) {
+        self.replace_by_fee = enabled;
+    }
+
     /// Sets the anchoring transaction payload.
     pub fn payload(&mut self, block_height: Height, block_hash: Hash) {
         self.payload = Some((block_height, block_hash));
     }
 
+    /// Sets the state commitment of the anchored block, which switches the payload to v.2.
+    pub fn state_commitment(&mut self, state_commitment: Hash) {
+        self.state_commitment = Some(state_commitment);
+    }
+
+    /// Sets the Merkle root of the anchored blocks batch, which switches the payload
+    /// to the batch one.
+    pub fn batch_root(&mut self, batch_root: Hash) {
+        self.batch_root = Some(batch_root);
+    }
+
+    /// Sets the identifier of the anchored blockchain written into the regular payload.
+    pub fn chain_id(&mut self, chain_id: ChainId) {
+        self.chain_id = Some(chain_id);
+    }
+
+    /// Sets the version of the anchoring transaction payload.
+    pub fn payload_version(&mut self, version: PayloadVersion) {
+        self.payload_version = version;
+    }
+
     /// Finalizes the anchoring transaction and returns
     /// it and also the list of input transactions.
     pub fn create(mut self) -> Result<(Transaction, Vec<Transaction>), BuilderError> {
         // Creates transaction inputs.
+        let sequence = if self.replace_by_fee {
+            RBF_SEQUENCE
+        } else {
+            FINAL_SEQUENCE
+        };
         let (input, input_transactions, balance) = {
             let mut input = Vec::new();
             let mut input_transactions = Vec::new();
@@ -191,7 +318,7 @@ impl BtcAnchoringTransactionBuilder {
                         vout: out_index as u32,
                     },
                     script_sig: Script::default(),
-                    sequence: 0xFFFF_FFFF,
+                    sequence,
                     witness: Vec::default(),
                 };
                 balance += tx.0.output[out_index].value;
@@ -202,11 +329,22 @@ impl BtcAnchoringTransactionBuilder {
         };
         // Computes payload script.
         let (block_height, block_hash) = self.payload.take().expect("Payload isn't set.");
+        // The recover payload has no room for the other hashes and the chain identifier.
+        let (state_commitment, batch_root, chain_id) = if self.recovery_tx.is_some() {
+            (None, None, None)
+        } else {
+            (self.state_commitment, self.batch_root, self.chain_id)
+        };
         let payload_script = PayloadBuilder::new()
+            .version(self.payload_version)
             .block_hash(block_hash)
             .block_height(block_height)
             .prev_tx_chain(self.recovery_tx)
-            .into_script();
+            .state_commitment(state_commitment)
+            .batch_root(batch_root)
+            .chain_id(chain_id)
+            .into_script()
+            .map_err(BuilderError::Payload)?;
         let output = match self.transit_to {
             Some(script) => script,
             _ => self.script_pubkey,
@@ -229,12 +367,9 @@ impl BtcAnchoringTransactionBuilder {
             ],
         });
 
-        // Computes a total fee value.
-        let size_in_bytes = {
-            let bytes = ::bitcoin::consensus::serialize(&transaction.0);
-            bytes.len() as u64
-        };
-        let total_fee = self.fee.expect("Fee per byte isn't set.") * size_in_bytes;
+        // Computes a total fee value for the signed transaction.
+        let vsize = estimate_vsize(&transaction, &self.redeem_script);
+        let total_fee = self.fee.expect("Fee per virtual byte isn't set.") * vsize;
         if total_fee > balance {
             return Err(BuilderError::InsufficientFunds { total_fee, balance });
         }
@@ -244,6 +379,94 @@ impl BtcAnchoringTransactionBuilder {
     }
 }
 
+/// Creates an unsigned replacement of the given anchoring transaction which spends
+/// the same inputs and pays the given additional fee per virtual byte.
+///
+/// All inputs of the transaction should be spendable by the given redeem script.
+pub fn bump_fee(
+    redeem_script: &RedeemScript,
+    tx: &Transaction,
+    input_transactions: Vec<Transaction>,
+    fee_increment: u64,
+) -> Result<(Transaction, Vec<Transaction>), BuilderError> {
+    if tx.0.input.is_empty() {
+        return Err(BuilderError::NoInputs);
+    }
+
+    let script_pubkey = redeem_script.as_ref().to_v0_p2wsh();
+    for (input, input_tx) in tx.0.input.iter().zip(&input_transactions) {
+        let out = input_tx.0.output.get(input.previous_output.vout as usize);
+        if out.map(|out| &out.script_pubkey) != Some(&script_pubkey) {
+            return Err(BuilderError::UnsuitableOutput);
+        }
+    }
+
+    let mut replacement = tx.clone();
+    for input in &mut replacement.0.input {
+        input.witness.clear();
+    }
+
+    let additional_fee = fee_increment * estimate_vsize(&replacement, redeem_script);
+    let balance = replacement.0.output[0].value;
+    if additional_fee > balance {
+        return Err(BuilderError::InsufficientFunds {
+            total_fee: additional_fee,
+            balance,
+        });
+    }
+    let value = balance - additional_fee;
+    if value < DUST_LIMIT {
+        return Err(BuilderError::DustOutput {
+            value,
+            limit: DUST_LIMIT,
+        });
+    }
+    replacement.0.output[0].value = value;
+    Ok((replacement, input_transactions))
+}
+
+/// Estimates the virtual size of the given unsigned transaction after its inputs
+/// have been signed with the given redeem script.
+fn estimate_vsize(tx: &Transaction, redeem_script: &RedeemScript) -> u64 {
+    // Unsigned transaction is serialized without the witness data.
+    let base_size = ::bitcoin::consensus::serialize(&tx.0).len() as u64;
+    let witness_size =
+        SEGWIT_MARKER_SIZE + tx.0.input.len() as u64 * estimate_witness_size(redeem_script);
+    weight_to_vsize(base_size * WITNESS_SCALE_FACTOR + witness_size)
+}
+
+/// Converts the transaction weight to the virtual size which is the weight divided
+/// by the scale factor and rounded up (BIP141).
+fn weight_to_vsize(weight: u64) -> u64 {
+    (weight + WITNESS_SCALE_FACTOR - 1) / WITNESS_SCALE_FACTOR
+}
+
+/// Returns the size of the bitcoin variable length integer.
+fn var_int_size(value: u64) -> u64 {
+    match value {
+        0..=0xFC => 1,
+        0xFD..=0xFFFF => 3,
+        0x1_0000..=0xFFFF_FFFF => 5,
+        _ => 9,
+    }
+}
+
+/// Estimates the maximum size of the witness which spends the p2wsh input with
+/// the given redeem script.
+///
+/// Witness consists of the empty item required by the `OP_CHECKMULTISIG`,
+/// quorum signatures and the redeem script itself.
+fn estimate_witness_size(redeem_script: &RedeemScript) -> u64 {
+    let quorum = redeem_script.content().quorum as u64;
+    let script_len = redeem_script.as_ref().len() as u64;
+
+    var_int_size(quorum + 2)
+        + 1
+        + quorum * (1 + MAX_SIGNATURE_SIZE)
+        + var_int_size(script_len)
+        + script_len
+}
+
 #[cfg(test)]
 mod tests {
     use std::borrow::Cow;
@@ -256,6 +479,8 @@ mod tests {
     use bitcoin::util::hash::Sha256dHash;
     use btc::PublicKey;
     use btc_transaction_utils::multisig::RedeemScriptBuilder;
+    use btc_transaction_utils::test_data::secp_gen_keypair;
+    use btc_transaction_utils::{p2wsh, TxInRef};
     use hex::FromHex;
 
     use exonum::crypto::CryptoHash;
@@ -263,7 +488,8 @@ mod tests {
     use exonum::helpers::Height;
     use exonum::storage::StorageValue;
 
-    use super::{BtcAnchoringTransactionBuilder, BuilderError, Transaction};
+    use super::{bump_fee, BtcAnchoringTransactionBuilder, BuilderError, Transaction};
+    use test_helpers::testkit::create_fake_funding_transaction;
 
     #[test]
     fn test_transaction_conversions() {
@@ -606,4 +832,88 @@ mod tests {
             BuilderError::UnsuitableFundingTx
         );
     }
+
+    #[test]
+    fn test_anchoring_transaction_builder_fee_per_vbyte() {
+        let keypairs = (0..4).map(|_| secp_gen_keypair()).collect::<Vec<_>>();
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keypairs.iter().map(|x| x.0))
+            .quorum(3)
+            .to_script()
+            .unwrap();
+        let address = p2wsh::address(&redeem_script, Network::Testnet);
+        let funding_tx = create_fake_funding_transaction(&address, 100_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(1);
+        builder.payload(Height::zero(), funding_tx.hash());
+        let (mut tx, inputs) = builder.create().unwrap();
+        let total_fee = 100_000 - tx.0.output[0].value;
+
+        // Signs the transaction to measure its actual virtual size.
+        let mut signer = p2wsh::InputSigner::new(redeem_script.clone());
+        let signatures = keypairs
+            .iter()
+            .take(3)
+            .map(|keypair| {
+                signer
+                    .sign_input(TxInRef::new(tx.as_ref(), 0), inputs[0].as_ref(), &keypair.1)
+                    .unwrap()
+            })
+            .collect::<Vec<_>>();
+        signer.spend_input(&mut tx.0.input[0], signatures);
+        let vsize = tx.vsize();
+
+        // The estimation assumes the signatures of the maximum length.
+        assert!(total_fee >= vsize);
+        assert!(total_fee - vsize <= 3);
+    }
+
+    #[test]
+    fn test_anchoring_transaction_bump_fee() {
+        let keypairs = (0..4).map(|_| secp_gen_keypair()).collect::<Vec<_>>();
+        let redeem_script = RedeemScriptBuilder::with_public_keys(keypairs.iter().map(|x| x.0))
+            .quorum(3)
+            .to_script()
+            .unwrap();
+        let address = p2wsh::address(&redeem_script, Network::Testnet);
+        let funding_tx = create_fake_funding_transaction(&address, 100_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(1);
+        builder.replace_by_fee(true);
+        builder.payload(Height::zero(), funding_tx.hash());
+        let (tx, inputs) = builder.create().unwrap();
+        assert!(tx.signals_rbf());
+        let total_fee = 100_000 - tx.0.output[0].value;
+
+        let (replacement, replacement_inputs) = bump_fee(&redeem_script, &tx, inputs, 2).unwrap();
+        assert_ne!(replacement.id(), tx.id());
+        assert_eq!(replacement.input_tx_ids(), tx.input_tx_ids());
+        assert_eq!(replacement_inputs, vec![funding_tx.clone()]);
+        assert_eq!(replacement.anchoring_payload(), tx.anchoring_payload());
+        // Both transactions have the same virtual size.
+        assert_eq!(
+            tx.0.output[0].value - replacement.0.output[0].value,
+            2 * total_fee
+        );
+
+        // Replacement with the change output below the dust limit is rejected.
+        let fee_increment = tx.0.output[0].value / total_fee;
+        assert_matches!(
+            bump_fee(&redeem_script, &tx, vec![funding_tx.clone()], fee_increment).unwrap_err(),
+            BuilderError::DustOutput { limit: 330, .. }
+        );
+
+        // Inputs of the transaction are not spendable by the another redeem script.
+        let other_redeem_script =
+            RedeemScriptBuilder::with_public_keys(keypairs.iter().skip(1).map(|x| x.0))
+                .to_script()
+                .unwrap();
+        assert_matches!(
+            bump_fee(&other_redeem_script, &tx, vec![funding_tx], 2).unwrap_err(),
+            BuilderError::UnsuitableOutput
+        );
+    }
 }
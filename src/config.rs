@@ -20,10 +20,15 @@ use bitcoin::network::constants::Network;
 use btc_transaction_utils::multisig::{RedeemScript, RedeemScriptBuilder, RedeemScriptError};
 use btc_transaction_utils::p2wsh;
 
+use std::cmp;
 use std::collections::HashMap;
 
-use btc::{Address, Privkey, PublicKey, Transaction};
-use rpc::BitcoinRpcConfig;
+use btc::{
+    Address, ExtendedPrivkey, PayloadVersion, Privkey, PublicKey, Transaction, MAX_KEY_EPOCH,
+};
+use keystore::{EncryptedKeypair, EncryptedPrivkey, KeystoreError};
+use rpc::{BitcoinRpcConfig, BtcRelay, FailoverRelay, RelayEndpoint};
+use signer::RemoteSignerConfig;
 
 /// Returns sufficient number of keys for the given validators number.
 pub fn byzantine_quorum(total: usize) -> usize {
@@ -39,10 +44,139 @@ pub struct GlobalConfig {
     pub public_keys: Vec<PublicKey>,
     /// Interval in blocks between anchored blocks.
     pub anchoring_interval: u64,
-    /// Fee per byte in satoshis.
-    pub transaction_fee: u64,
-    /// Funding transaction.
-    pub funding_transaction: Option<Transaction>,
+    /// Policy of the fee per virtual byte calculation. The legacy `transaction_fee`
+    /// field is read as the fixed fee policy.
+    #[serde(alias = "transaction_fee", deserialize_with = "legacy::fee_policy")]
+    pub fee_policy: FeePolicy,
+    /// Funding transactions which are consumed by the anchoring transactions
+    /// in the given order. The legacy `funding_transaction` field is read as the
+    /// list with the single transaction.
+    #[serde(
+        default,
+        alias = "funding_transaction",
+        deserialize_with = "legacy::funding_transactions"
+    )]
+    pub funding_transactions: Vec<Transaction>,
+    /// Enables the opt-in Replace-By-Fee signalling (BIP125) in the anchoring transactions.
+    #[serde(default)]
+    pub replace_by_fee: bool,
+    /// Parameters of the stuck anchoring transactions replacement.
+    /// It takes effect only if `replace_by_fee` is enabled.
+    #[serde(default)]
+    pub fee_bump: Option<FeeBumpConfig>,
+    /// Epoch of the anchoring keys. Validators with the extended private key use the
+    /// anchoring key derived for this epoch, thus it should be increased along with
+    /// the change of the public keys.
+    #[serde(default)]
+    pub key_epoch: u32,
+    /// Version of the payload of the anchoring transactions.
+    #[serde(default)]
+    pub payload_version: PayloadVersion,
+    /// Enables the batch anchoring, in which the payload commits to the Merkle root of
+    /// the hashes of all blocks since the previous anchored block. The batch payload
+    /// takes precedence over the state commitment of the v.2 payload.
+    #[serde(default)]
+    pub batch_anchoring: bool,
+    /// Writes the identifier of the blockchain, which is the prefix of the genesis block
+    /// hash, into the regular payloads of the anchoring transactions instead of the state
+    /// commitment, since both of them don't fit into the `OP_RETURN` output. It can be
+    /// enabled only with the payload v.2 or v.3 without the batch anchoring.
+    #[serde(default)]
+    pub include_chain_id: bool,
+}
+
+/// Policy of the anchoring transactions fee per virtual byte calculation.
+///
+/// The fee is resolved deterministically from the blockchain state, thus all
+/// validators build identical anchoring transactions.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeePolicy {
+    /// Fixed fee per virtual byte.
+    Fixed {
+        /// Fee per virtual byte in satoshis.
+        fee: u64,
+    },
+    /// Fee per virtual byte follows the fee rate of the latest anchoring transaction
+    /// within the given bounds. The fee rate grows with the replacements of the stuck
+    /// anchoring transactions and decreases by one eighth with each following one.
+    Bounded {
+        /// Minimal fee per virtual byte in satoshis.
+        min: u64,
+        /// Maximal fee per virtual byte in satoshis.
+        max: u64,
+    },
+    /// Fee per virtual byte changes at the given anchoring heights.
+    Schedule {
+        /// Fee per virtual byte in satoshis before the first change.
+        initial: u64,
+        /// Scheduled changes of the fee.
+        changes: Vec<FeeChange>,
+    },
+}
+
+/// Scheduled change of the fee per virtual byte.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct FeeChange {
+    /// Anchoring height from which the fee is used.
+    pub height: Height,
+    /// Fee per virtual byte in satoshis.
+    pub fee: u64,
+}
+
+impl Default for FeePolicy {
+    fn default() -> Self {
+        FeePolicy::Fixed { fee: 10 }
+    }
+}
+
+impl FeePolicy {
+    /// Returns the fee per virtual byte for the anchoring transaction at the given height.
+    /// The fee rate of the latest anchoring transaction is used only by the bounded policy.
+    pub fn resolve(&self, anchoring_height: Height, latest_fee_rate: Option<u64>) -> u64 {
+        match *self {
+            FeePolicy::Fixed { fee } => fee,
+            FeePolicy::Bounded { min, max } => {
+                let fee = latest_fee_rate.map_or(min, |rate| rate - rate / 8);
+                cmp::min(cmp::max(fee, min), max)
+            }
+            FeePolicy::Schedule {
+                initial,
+                ref changes,
+            } => changes
+                .iter()
+                .filter(|change| change.height <= anchoring_height)
+                .max_by_key(|change| change.height)
+                .map_or(initial, |change| change.fee),
+        }
+    }
+}
+
+/// Parameters of the replacement of the anchoring transaction which is stuck
+/// in the Bitcoin mempool.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct FeeBumpConfig {
+    /// Number of Bitcoin blocks during which the latest anchoring transaction may remain
+    /// unconfirmed before validators vote to replace it. The blocks are counted by the
+    /// confirmations of the transaction spent by the latest anchoring transaction.
+    pub stuck_after: u64,
+    /// Additional fee per virtual byte in satoshis paid by each replacement.
+    /// It should be at least the incremental relay fee of the Bitcoin nodes.
+    pub fee_increment: u64,
+}
+
+/// Errors of the anchoring configuration validation.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum ConfigError {
+    /// Key epoch is out of the hardened indexes range of the extended keys.
+    #[fail(display = "Key epoch {} is out of the hardened indexes range.", _0)]
+    KeyEpochOutOfRange(u32),
+    /// Chain identifier is written only into the regular payload v.2 or v.3.
+    #[fail(
+        display = "Chain identifier requires the payload v.2 or v.3 without the batch \
+                   anchoring."
+    )]
+    ChainIdNotSupported,
 }
 
 impl Default for GlobalConfig {
@@ -51,8 +185,14 @@ impl Default for GlobalConfig {
             network: Network::Testnet,
             public_keys: vec![],
             anchoring_interval: 5_000,
-            transaction_fee: 10,
-            funding_transaction: None,
+            fee_policy: FeePolicy::default(),
+            funding_transactions: Vec::new(),
+            replace_by_fee: false,
+            fee_bump: None,
+            key_epoch: 0,
+            payload_version: PayloadVersion::default(),
+            batch_anchoring: false,
+            include_chain_id: false,
         }
     }
 }
@@ -76,6 +216,21 @@ impl GlobalConfig {
         })
     }
 
+    /// Checks that the configuration parameters are consistent with each other.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if self.key_epoch > MAX_KEY_EPOCH {
+            Err(ConfigError::KeyEpochOutOfRange(self.key_epoch))?;
+        }
+        // The payload v.1 has no room for the chain identifier, and the batch root
+        // can't be replaced by it.
+        let is_chain_id_supported =
+            self.payload_version != PayloadVersion::V1 && !self.batch_anchoring;
+        if self.include_chain_id && !is_chain_id_supported {
+            Err(ConfigError::ChainIdNotSupported)?;
+        }
+        Ok(())
+    }
+
     /// Returns the corresponding Bitcoin address.
     pub fn anchoring_address(&self) -> Address {
         p2wsh::address(&self.redeem_script(), self.network).into()
@@ -90,6 +245,16 @@ impl GlobalConfig {
             .unwrap()
     }
 
+    /// Returns the fee bumping parameters if the stuck anchoring transactions
+    /// can be replaced.
+    pub fn active_fee_bump(&self) -> Option<FeeBumpConfig> {
+        if self.replace_by_fee {
+            self.fee_bump
+        } else {
+            None
+        }
+    }
+
     /// Returns the latest height below the given height which must be anchored.
     pub fn previous_anchoring_height(&self, current_height: Height) -> Height {
         Height(current_height.0 - current_height.0 % self.anchoring_interval)
@@ -107,9 +272,89 @@ pub struct LocalConfig {
     /// Bitcoin RPC client configuration, which used to send an anchoring transactions
     /// to the Bitcoin network.
     pub rpc: Option<BitcoinRpcConfig>,
+    /// Additional Bitcoin relay endpoints, which are used if the main one fails.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub backup_rpc: Vec<RelayEndpoint>,
     /// Set of private keys for each anchoring address.
     #[serde(with = "flatten_keypairs")]
     pub private_keys: HashMap<Address, Privkey>,
+    /// Private keys encrypted with the passphrase, which are decrypted on the node start.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub encrypted_private_keys: Vec<EncryptedKeypair>,
+    /// Extended private key, from which the anchoring private keys are derived for
+    /// the epochs of the anchoring configurations that are absent in `private_keys`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extended_private_key: Option<ExtendedPrivkey>,
+    /// Extended private key encrypted with the passphrase, which is decrypted on the node start.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encrypted_extended_private_key: Option<EncryptedPrivkey>,
+    /// Remote signer service configuration. If set, the anchoring transactions are signed
+    /// by this service instead of the private keys stored in the configuration.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub remote_signer: Option<RemoteSignerConfig>,
+}
+
+impl LocalConfig {
+    /// Creates Bitcoin relay for the configured endpoints. The main endpoint is requested
+    /// first, and the backup ones are requested in the order of their priority.
+    pub fn btc_relay(&self) -> Result<Option<Box<dyn BtcRelay>>, failure::Error> {
+        let mut backup_rpc = self.backup_rpc.iter().collect::<Vec<_>>();
+        backup_rpc.sort_by(|a, b| b.priority.cmp(&a.priority));
+
+        let mut endpoints = self
+            .rpc
+            .iter()
+            .chain(backup_rpc.into_iter().map(|endpoint| &endpoint.rpc))
+            .map(BitcoinRpcConfig::btc_relay)
+            .collect::<Result<Vec<_>, _>>()?;
+        let relay = match endpoints.len() {
+            0 => None,
+            1 => endpoints.pop(),
+            _ => Some(FailoverRelay::new(endpoints).into()),
+        };
+        Ok(relay)
+    }
+
+    /// Encrypts all plain private keys including the extended one with the given passphrase.
+    pub fn encrypt_private_keys(&mut self, passphrase: &str) {
+        for (address, privkey) in self.private_keys.drain() {
+            self.encrypted_private_keys.push(EncryptedKeypair {
+                address,
+                encrypted_private_key: EncryptedPrivkey::encrypt(&privkey, passphrase),
+            });
+        }
+        if let Some(extended_private_key) = self.extended_private_key.take() {
+            self.encrypted_extended_private_key = Some(EncryptedPrivkey::encrypt_extended(
+                &extended_private_key,
+                passphrase,
+            ));
+        }
+    }
+
+    /// Returns `true` if the configuration contains the encrypted private keys.
+    pub fn has_encrypted_keys(&self) -> bool {
+        !self.encrypted_private_keys.is_empty() || self.encrypted_extended_private_key.is_some()
+    }
+
+    /// Decrypts the encrypted private keys with the given passphrase and adds them
+    /// to the plain ones.
+    pub fn decrypt_private_keys(&mut self, passphrase: &str) -> Result<(), KeystoreError> {
+        let private_keys = self
+            .encrypted_private_keys
+            .iter()
+            .map(|keypair| {
+                let privkey = keypair.encrypted_private_key.decrypt(passphrase)?;
+                Ok((keypair.address.clone(), privkey))
+            })
+            .collect::<Result<Vec<_>, KeystoreError>>()?;
+        if let Some(ref encrypted_key) = self.encrypted_extended_private_key {
+            self.extended_private_key = Some(encrypted_key.decrypt_extended(passphrase)?);
+        }
+        self.private_keys.extend(private_keys);
+        self.encrypted_private_keys.clear();
+        self.encrypted_extended_private_key = None;
+        Ok(())
+    }
 }
 
 /// BTC anchoring configuration.
@@ -169,15 +414,62 @@ mod flatten_keypairs {
     }
 }
 
+/// Deserializers which also accept the values of the legacy configuration fields.
+mod legacy {
+    use super::FeePolicy;
+    use btc::Transaction;
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum FeePolicyRepr {
+        TransactionFee(u64),
+        Policy(FeePolicy),
+    }
+
+    pub fn fee_policy<'de, D>(deserializer: D) -> Result<FeePolicy, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        use serde::Deserialize;
+        FeePolicyRepr::deserialize(deserializer).map(|repr| match repr {
+            FeePolicyRepr::TransactionFee(fee) => FeePolicy::Fixed { fee },
+            FeePolicyRepr::Policy(policy) => policy,
+        })
+    }
+
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum FundingTransactionsRepr {
+        List(Vec<Transaction>),
+        FundingTransaction(Option<Transaction>),
+    }
+
+    pub fn funding_transactions<'de, D>(deserializer: D) -> Result<Vec<Transaction>, D::Error>
+    where
+        D: ::serde::Deserializer<'de>,
+    {
+        use serde::Deserialize;
+        FundingTransactionsRepr::deserialize(deserializer).map(|repr| match repr {
+            FundingTransactionsRepr::List(transactions) => transactions,
+            FundingTransactionsRepr::FundingTransaction(transaction) => {
+                transaction.into_iter().collect()
+            }
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use exonum::helpers::Height;
 
     use bitcoin::network::constants::Network;
     use btc_transaction_utils::test_data::secp_gen_keypair;
+    use hex::FromHex;
 
-    use super::{GlobalConfig, LocalConfig};
-    use rpc::BitcoinRpcConfig;
+    use super::{ConfigError, FeeChange, FeePolicy, GlobalConfig, LocalConfig};
+    use btc::{ExtendedPrivkey, PayloadVersion, Transaction, MAX_KEY_EPOCH};
+    use keystore::KeystoreError;
+    use rpc::{BitcoinRpcConfig, BtcRelayKind, RelayEndpoint};
 
     #[test]
     fn test_global_config() {
@@ -193,6 +485,84 @@ mod tests {
         assert_eq!(config2, config);
     }
 
+    #[test]
+    fn test_global_config_validate() {
+        let public_keys = (0..4)
+            .map(|_| secp_gen_keypair().0.into())
+            .collect::<Vec<_>>();
+        let mut config = GlobalConfig::with_public_keys(Network::Bitcoin, public_keys).unwrap();
+        assert_eq!(config.validate(), Ok(()));
+
+        config.key_epoch = MAX_KEY_EPOCH;
+        assert_eq!(config.validate(), Ok(()));
+        config.key_epoch = MAX_KEY_EPOCH + 1;
+        assert_eq!(
+            config.validate(),
+            Err(ConfigError::KeyEpochOutOfRange(MAX_KEY_EPOCH + 1))
+        );
+        config.key_epoch = 0;
+
+        // Chain identifier replaces the state commitment of the regular payloads v.2 and v.3.
+        config.include_chain_id = true;
+        assert_eq!(config.validate(), Err(ConfigError::ChainIdNotSupported));
+        for &version in &[PayloadVersion::V2, PayloadVersion::V3] {
+            config.payload_version = version;
+            assert_eq!(config.validate(), Ok(()));
+            config.batch_anchoring = true;
+            assert_eq!(config.validate(), Err(ConfigError::ChainIdNotSupported));
+            config.batch_anchoring = false;
+        }
+    }
+
+    #[test]
+    fn test_global_config_legacy_transaction_fee() {
+        let public_keys = (0..4)
+            .map(|_| secp_gen_keypair().0.into())
+            .collect::<Vec<_>>();
+        let config = GlobalConfig::with_public_keys(Network::Bitcoin, public_keys).unwrap();
+
+        let mut json = ::serde_json::to_value(&config).unwrap();
+        {
+            let fields = json.as_object_mut().unwrap();
+            fields.remove("fee_policy");
+            fields.insert("transaction_fee".to_owned(), json!(20));
+        }
+        let config2: GlobalConfig = ::serde_json::from_value(json).unwrap();
+        assert_eq!(config2.fee_policy, FeePolicy::Fixed { fee: 20 });
+        assert_eq!(config2.public_keys, config.public_keys);
+    }
+
+    #[test]
+    fn test_global_config_legacy_funding_transaction() {
+        let public_keys = (0..4)
+            .map(|_| secp_gen_keypair().0.into())
+            .collect::<Vec<_>>();
+        let config = GlobalConfig::with_public_keys(Network::Bitcoin, public_keys).unwrap();
+        let funding_tx = Transaction::from_hex(
+            "02000000000101140b3f5da041f173d938b8fe778d39cb2ef801f75f2946e490e34d6bb47bb9ce000000\
+             0000feffffff023002540000000000160014169fa44a9159f281122bb7f3d43d88d56dfa937e7011010000\
+             0000002200203abcf8339d06564a151942c35e4a59eee2581e3880bceb84a324e2237f19ceb502483045\
+             022100e91d46b565f26641b353591d0c403a05ada5735875fb0f055538bf9df4986165022044b5336772\
+             de8c5f6cbf83bcc7099e31d7dce22ba1f3d1badc2fdd7f8013a12201210254053f15b44b825bc5dabfe8\
+             8f8b94cd217372f3f297d2696a32835b43497397358d1400",
+        )
+        .unwrap();
+
+        let mut json = ::serde_json::to_value(&config).unwrap();
+        json.as_object_mut().unwrap().remove("funding_transactions");
+        for (value, expected) in vec![
+            (json!(null), vec![]),
+            (json!(funding_tx), vec![funding_tx.clone()]),
+        ] {
+            let mut json = json.clone();
+            json.as_object_mut()
+                .unwrap()
+                .insert("funding_transaction".to_owned(), value);
+            let config2: GlobalConfig = ::serde_json::from_value(json).unwrap();
+            assert_eq!(config2.funding_transactions, expected);
+        }
+    }
+
     #[test]
     fn test_local_config() {
         let cfg_str = r#"
@@ -210,11 +580,118 @@ mod tests {
                 host: String::from("http://localhost"),
                 username: None,
                 password: None,
+                kind: BtcRelayKind::BitcoinRpc,
+                ..BitcoinRpcConfig::default()
             }
         );
         assert!(local_config.private_keys.len() == 1);
     }
 
+    #[test]
+    fn test_local_config_esplora_relay() {
+        let cfg_str = r#"
+            private_keys = []
+            [rpc]
+            host = "https://blockstream.info/testnet/api"
+            kind = "esplora"
+        "#;
+
+        let local_config: LocalConfig = ::toml::from_str(cfg_str).unwrap();
+        assert_eq!(local_config.rpc.unwrap().kind, BtcRelayKind::Esplora);
+    }
+
+    #[test]
+    fn test_local_config_encrypted_private_keys() {
+        let cfg_str = r#"
+            [[private_keys]]
+            address = 'bc1qxfhtyn4l3hztytwvd4h6l9ah8qgz3ycfa86mq85qnqdff5kdzg2sdv6e82'
+            private_key = 'L58cq7TgbA6RpJ1KGsj9h5sfXuAeY6GqA197Qrpepw3boRdXqYBS'
+        "#;
+        let mut local_config: LocalConfig = ::toml::from_str(cfg_str).unwrap();
+        local_config.extended_private_key = Some(ExtendedPrivkey::generate(Network::Bitcoin));
+        assert!(!local_config.has_encrypted_keys());
+
+        let mut encrypted_config = local_config.clone();
+        encrypted_config.encrypt_private_keys("passphrase");
+        assert!(encrypted_config.private_keys.is_empty());
+        assert_eq!(encrypted_config.encrypted_private_keys.len(), 1);
+        assert!(encrypted_config.extended_private_key.is_none());
+        assert!(encrypted_config.has_encrypted_keys());
+
+        let encrypted_str = ::toml::Value::try_from(&encrypted_config)
+            .unwrap()
+            .to_string();
+        assert!(!encrypted_str.contains("L58cq7TgbA6RpJ1KGsj9h5sfXuAeY6GqA197Qrpepw3boRdXqYBS"));
+        assert!(!encrypted_str.contains(
+            &local_config
+                .extended_private_key
+                .as_ref()
+                .unwrap()
+                .to_string()
+        ));
+
+        let mut decrypted_config: LocalConfig = ::toml::from_str(&encrypted_str).unwrap();
+        assert_eq!(
+            decrypted_config.decrypt_private_keys("wrong"),
+            Err(KeystoreError::InvalidPassphrase)
+        );
+        decrypted_config.decrypt_private_keys("passphrase").unwrap();
+        assert_eq!(decrypted_config, local_config);
+    }
+
+    #[test]
+    fn test_local_config_backup_rpc() {
+        let cfg_str = r#"
+            private_keys = []
+            [rpc]
+            host = "http://localhost:18332"
+            [[backup_rpc]]
+            host = "https://blockstream.info/testnet/api"
+            kind = "esplora"
+            priority = 1
+            [[backup_rpc]]
+            host = "http://backup:18332"
+            username = "user"
+            priority = 2
+        "#;
+
+        let local_config: LocalConfig = ::toml::from_str(cfg_str).unwrap();
+        assert_eq!(
+            local_config.backup_rpc[1],
+            RelayEndpoint {
+                rpc: BitcoinRpcConfig {
+                    host: String::from("http://backup:18332"),
+                    username: Some(String::from("user")),
+                    password: None,
+                    kind: BtcRelayKind::BitcoinRpc,
+                    ..BitcoinRpcConfig::default()
+                },
+                priority: 2,
+            }
+        );
+        // Only the main endpoint is configured.
+        let relay = LocalConfig {
+            backup_rpc: Vec::new(),
+            ..local_config.clone()
+        }
+        .btc_relay()
+        .unwrap()
+        .unwrap();
+        assert_eq!(relay.config().host, "http://localhost:18332");
+        // Backup endpoints are used in addition to the main one.
+        let relay = local_config.btc_relay().unwrap().unwrap();
+        assert_eq!(relay.config().host, "http://localhost:18332");
+        // Backup endpoints can be used without the main one.
+        let relay = LocalConfig {
+            rpc: None,
+            ..local_config
+        }
+        .btc_relay()
+        .unwrap()
+        .unwrap();
+        assert_eq!(relay.config().host, "http://backup:18332");
+    }
+
     #[test]
     fn test_global_config_anchoring_height() {
         let public_keys = (0..4)
@@ -240,4 +717,39 @@ mod tests {
             Height(2000)
         );
     }
+
+    #[test]
+    fn test_fee_policy_resolve() {
+        let policy = FeePolicy::Fixed { fee: 10 };
+        assert_eq!(policy.resolve(Height(0), None), 10);
+        assert_eq!(policy.resolve(Height(1000), Some(100)), 10);
+
+        let policy = FeePolicy::Bounded { min: 10, max: 50 };
+        assert_eq!(policy.resolve(Height(0), None), 10);
+        assert_eq!(policy.resolve(Height(1000), Some(40)), 35);
+        assert_eq!(policy.resolve(Height(1000), Some(11)), 10);
+        assert_eq!(policy.resolve(Height(1000), Some(100)), 50);
+
+        let policy = FeePolicy::Schedule {
+            initial: 10,
+            changes: vec![
+                FeeChange {
+                    height: Height(2000),
+                    fee: 30,
+                },
+                FeeChange {
+                    height: Height(1000),
+                    fee: 20,
+                },
+            ],
+        };
+        assert_eq!(policy.resolve(Height(999), None), 10);
+        assert_eq!(policy.resolve(Height(1000), None), 20);
+        assert_eq!(policy.resolve(Height(1999), Some(100)), 20);
+        assert_eq!(policy.resolve(Height(5000), None), 30);
+
+        let json = ::serde_json::to_value(&policy).unwrap();
+        let policy2: FeePolicy = ::serde_json::from_value(json).unwrap();
+        assert_eq!(policy2, policy);
+    }
 }
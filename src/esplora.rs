@@ -0,0 +1,379 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Bitcoin relay which uses the Esplora-compatible REST API.
+//!
+//! Unlike the `bitcoind` JSON-RPC API, the Esplora API doesn't require the node
+//! to run a full Bitcoin node with the enabled wallet, but it is unable to send
+//! funds to the address.
+
+use exonum::crypto::Hash;
+
+use bitcoin::blockdata::block::BlockHeader;
+use bitcoin::consensus;
+use bitcoin::util::address::Address;
+use bitcoin::util::hash::Sha256dHash;
+use failure;
+use hex::{self, FromHex};
+use reqwest::{self, StatusCode};
+use serde::de::DeserializeOwned;
+use serde_json;
+
+use std::fs;
+use std::time::Duration;
+
+use btc::{MerkleProof, Transaction};
+use rpc::{BitcoinRpcConfig, BtcRelay, TransactionInfo};
+
+/// Timeout of the single HTTP request.
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Confirmation status of the transaction.
+#[derive(Debug, Deserialize)]
+struct TxStatus {
+    confirmed: bool,
+    block_height: Option<u64>,
+    block_hash: Option<String>,
+}
+
+/// Merkle branch of the transaction in the block.
+#[derive(Debug, Deserialize)]
+struct TxMerkleBranch {
+    merkle: Vec<String>,
+    pos: u32,
+}
+
+/// Client for the Esplora-compatible REST API.
+#[derive(Debug)]
+pub struct EsploraClient {
+    config: BitcoinRpcConfig,
+    base_url: String,
+    client: reqwest::Client,
+}
+
+impl EsploraClient {
+    /// Creates a new client for the API located at the host from the given configuration.
+    /// If credentials are configured, they are used for the HTTP basic authentication.
+    ///
+    /// Returns an error if the configured CA certificate cannot be loaded.
+    pub fn new(config: BitcoinRpcConfig) -> Result<Self, failure::Error> {
+        let client = http_client(&config)?;
+        Ok(Self {
+            base_url: config.host.trim_end_matches('/').to_owned(),
+            config,
+            client,
+        })
+    }
+
+    /// Sends the given request and returns the response status and body.
+    fn send(
+        &self,
+        mut request: reqwest::RequestBuilder,
+    ) -> Result<(StatusCode, String), failure::Error> {
+        if let (Some(username), password) = self.config.credentials()? {
+            request = request.basic_auth(username, password);
+        }
+        let mut response = request.send()?;
+        let body = response.text()?;
+        Ok((response.status(), body))
+    }
+
+    /// Performs GET request to the given API path. Returns `None` if the requested
+    /// resource is not found.
+    fn get(&self, path: &str) -> Result<Option<String>, failure::Error> {
+        let url = format!("{}/{}", self.base_url, path);
+        match self.send(self.client.get(&url))? {
+            (StatusCode::NOT_FOUND, _) => Ok(None),
+            (status, body) if status.is_success() => Ok(Some(body)),
+            (status, body) => bail!("Esplora request {} failed with {}: {}", url, status, body),
+        }
+    }
+
+    fn get_json<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, failure::Error> {
+        match self.get(path)? {
+            Some(body) => Ok(Some(serde_json::from_str(&body)?)),
+            None => Ok(None),
+        }
+    }
+
+    fn tip_height(&self) -> Result<u64, failure::Error> {
+        let body = self
+            .get("blocks/tip/height")?
+            .ok_or_else(|| format_err!("Unable to get the height of the Bitcoin blockchain"))?;
+        body.trim().parse().map_err(From::from)
+    }
+
+    fn block_header(&self, block_hash: &str) -> Result<BlockHeader, failure::Error> {
+        let header_hex = self
+            .get(&format!("block/{}/header", block_hash))?
+            .ok_or_else(|| format_err!("Unable to find block header {}", block_hash))?;
+        let bytes = hex::decode(header_hex.trim())?;
+        consensus::deserialize(&bytes).map_err(From::from)
+    }
+}
+
+/// Creates HTTP client which trusts the CA certificate from the given configuration
+/// in addition to the system ones.
+pub(crate) fn http_client(config: &BitcoinRpcConfig) -> Result<reqwest::Client, failure::Error> {
+    let mut builder = reqwest::Client::builder().timeout(REQUEST_TIMEOUT);
+    if let Some(ref path) = config.ca_certificate {
+        let pem = fs::read(path)
+            .map_err(|e| format_err!("Unable to read CA certificate {}: {}", path.display(), e))?;
+        builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+    }
+    builder.build().map_err(From::from)
+}
+
+impl From<EsploraClient> for Box<dyn BtcRelay> {
+    fn from(client: EsploraClient) -> Self {
+        Box::new(client) as Self
+    }
+}
+
+impl BtcRelay for EsploraClient {
+    fn send_to_address(
+        &self,
+        _addr: &Address,
+        _satoshis: u64,
+    ) -> Result<Transaction, failure::Error> {
+        bail!("Esplora API doesn't support sending funds to the address")
+    }
+
+    fn transaction_info(&self, id: &Hash) -> Result<Option<TransactionInfo>, failure::Error> {
+        let txid = id.to_hex();
+        let tx_hex = match self.get(&format!("tx/{}/hex", txid))? {
+            Some(tx_hex) => tx_hex,
+            None => return Ok(None),
+        };
+        let content = Transaction::from_hex(tx_hex.trim())?;
+
+        let status: TxStatus = self
+            .get_json(&format!("tx/{}/status", txid))?
+            .ok_or_else(|| format_err!("Unable to get status of the transaction {}", txid))?;
+        let confirmations = match status.block_height {
+            Some(block_height) if status.confirmed => {
+                (self.tip_height()? + 1).saturating_sub(block_height)
+            }
+            _ => 0,
+        };
+
+        Ok(Some(TransactionInfo {
+            content,
+            confirmations,
+        }))
+    }
+
+    fn merkle_proof(&self, id: &Hash) -> Result<Option<MerkleProof>, failure::Error> {
+        let txid = id.to_hex();
+        let status: TxStatus = match self.get_json(&format!("tx/{}/status", txid))? {
+            Some(status) => status,
+            None => return Ok(None),
+        };
+        let block_hash = match status.block_hash {
+            Some(block_hash) if status.confirmed => block_hash,
+            _ => return Ok(None),
+        };
+
+        let merkle_branch: TxMerkleBranch = self
+            .get_json(&format!("tx/{}/merkle-proof", txid))?
+            .ok_or_else(|| format_err!("Unable to get merkle proof of the transaction {}", txid))?;
+        let branch = merkle_branch
+            .merkle
+            .iter()
+            .map(|hash| Sha256dHash::from_hex(hash))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| format_err!("Invalid merkle branch hash: {:?}", e))?;
+
+        Ok(Some(MerkleProof {
+            block_header: self.block_header(&block_hash)?,
+            index: merkle_branch.pos,
+            branch,
+        }))
+    }
+
+    fn send_transaction(&self, transaction: &Transaction) -> Result<Hash, failure::Error> {
+        let url = format!("{}/tx", self.base_url);
+        let (status, body) = self.send(self.client.post(&url).body(transaction.to_string()))?;
+        ensure!(
+            status.is_success(),
+            "Unable to send transaction {}: {}",
+            transaction.id().to_hex(),
+            body
+        );
+        Ok(transaction.id())
+    }
+
+    fn watch_address(&self, _addr: &Address, _rescan: bool) -> Result<(), failure::Error> {
+        // Esplora indexes all addresses, so there is nothing to do.
+        Ok(())
+    }
+
+    fn config(&self) -> BitcoinRpcConfig {
+        self.config.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::Hash;
+
+    use bitcoin::blockdata::block::BlockHeader;
+    use bitcoin::consensus;
+    use bitcoin::network::constants::Network;
+    use bitcoin::util::hash::{BitcoinHash, Sha256dHash};
+    use hex::{self, FromHex};
+
+    use super::EsploraClient;
+    use btc::{pow_limit, Transaction};
+    use rpc::{BitcoinRpcConfig, BtcRelay, BtcRelayKind};
+    use test_helpers::http::{exchange, mock_server};
+
+    fn client(url: String) -> EsploraClient {
+        EsploraClient::new(BitcoinRpcConfig {
+            host: url,
+            kind: BtcRelayKind::Esplora,
+            ..BitcoinRpcConfig::default()
+        })
+        .unwrap()
+    }
+
+    fn transaction() -> Transaction {
+        Transaction::from_hex(
+            "02000000000101a4fe140f92eb5fa5a4788b6271a22f07fa91cb2f8ac328cd0065bfc43adb16c90100000\
+             01716001446decf32d70ee1fad5aa11d02158810316e6790bfeffffff02a08601000000000017a9147f14\
+             23e3359d1754ae9427e313c1d9581f3f280a87e8e520070000000017a914b83c7a100c7ff491e5edb5f1d\
+             fcd39e298e50f4b87024830450221008f9378080defdb2029f9c96e149e85e93d8fb860a1c06a7c988908\
+             09077eec8b02206049967206a4bd35f8fa4c59a8cd9f46b08e48f794a6b325986b4e9227b9d8d30121037\
+             f72563a0750831ab4fb762e01cfe368ddd412042be6b78af5ee5a9bd38d0ed093a81300",
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn test_esplora_transaction_info() {
+        let tx = transaction();
+        let txid = tx.id().to_hex();
+        let status = r#"{"confirmed":true,"block_height":100,"block_hash":"00"}"#;
+
+        let (url, server) = mock_server(vec![
+            exchange(
+                format!("GET /tx/{}/hex HTTP/1.1", txid),
+                200,
+                tx.to_string(),
+            ),
+            exchange(format!("GET /tx/{}/status HTTP/1.1", txid), 200, status),
+            exchange("GET /blocks/tip/height HTTP/1.1", 200, "105"),
+        ]);
+        let info = client(url).transaction_info(&tx.id()).unwrap().unwrap();
+        assert_eq!(info.content, tx);
+        assert_eq!(info.confirmations, 6);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_esplora_transaction_info_unconfirmed() {
+        let tx = transaction();
+        let txid = tx.id().to_hex();
+
+        let (url, server) = mock_server(vec![
+            exchange(
+                format!("GET /tx/{}/hex HTTP/1.1", txid),
+                200,
+                tx.to_string(),
+            ),
+            exchange(
+                format!("GET /tx/{}/status HTTP/1.1", txid),
+                200,
+                r#"{"confirmed":false}"#,
+            ),
+        ]);
+        let info = client(url).transaction_info(&tx.id()).unwrap().unwrap();
+        assert_eq!(info.confirmations, 0);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_esplora_transaction_info_unknown() {
+        let txid = Hash::zero();
+        let (url, server) = mock_server(vec![exchange(
+            format!("GET /tx/{}/hex HTTP/1.1", txid.to_hex()),
+            404,
+            "Transaction not found",
+        )]);
+        assert_eq!(client(url).transaction_info(&txid).unwrap(), None);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_esplora_merkle_proof() {
+        let tx = transaction();
+        let txid = tx.id().to_hex();
+        // Block which contains the single transaction.
+        let mut header = BlockHeader {
+            version: 1,
+            prev_blockhash: Sha256dHash::default(),
+            merkle_root: tx.0.txid(),
+            time: 0,
+            bits: 0x207f_ffff,
+            nonce: 0,
+        };
+        while header.spv_validate(&header.target()).is_err() {
+            header.nonce += 1;
+        }
+        let block_hash = header.bitcoin_hash().be_hex_string();
+        let status = format!(
+            r#"{{"confirmed":true,"block_height":100,"block_hash":"{}"}}"#,
+            block_hash
+        );
+
+        let (url, server) = mock_server(vec![
+            exchange(format!("GET /tx/{}/status HTTP/1.1", txid), 200, status),
+            exchange(
+                format!("GET /tx/{}/merkle-proof HTTP/1.1", txid),
+                200,
+                r#"{"block_height":100,"merkle":[],"pos":0}"#,
+            ),
+            exchange(
+                format!("GET /block/{}/header HTTP/1.1", block_hash),
+                200,
+                hex::encode(consensus::serialize(&header)),
+            ),
+        ]);
+        let proof = client(url).merkle_proof(&tx.id()).unwrap().unwrap();
+        assert_eq!(proof.block_header, header);
+        assert_eq!(proof.verify(&tx.id(), &pow_limit(Network::Regtest)), Ok(()));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn test_esplora_send_transaction() {
+        let tx = transaction();
+        let (url, server) = mock_server(vec![exchange("POST /tx HTTP/1.1", 200, tx.id().to_hex())]);
+        assert_eq!(client(url).send_transaction(&tx).unwrap(), tx.id());
+        assert_eq!(server.join().unwrap(), vec![tx.to_string()]);
+    }
+
+    #[test]
+    fn test_esplora_send_transaction_rejected() {
+        let tx = transaction();
+        let (url, server) = mock_server(vec![exchange(
+            "POST /tx HTTP/1.1",
+            400,
+            "sendrawtransaction RPC error: bad-txns-inputs-missingorspent",
+        )]);
+        let error = client(url).send_transaction(&tx).unwrap_err();
+        assert!(error.to_string().contains("bad-txns-inputs-missingorspent"));
+        server.join().unwrap();
+    }
+}
@@ -27,10 +27,14 @@ use {BtcAnchoringService, BTC_ANCHORING_SERVICE_NAME};
 use std::collections::{BTreeMap, HashMap};
 
 use self::args::{Hash, NamedArgumentOptional, NamedArgumentRequired, TypedArgument};
-use btc::{gen_keypair, Privkey, PublicKey};
-use config::{Config, GlobalConfig, LocalConfig};
-use rpc::{BitcoinRpcClient, BitcoinRpcConfig, BtcRelay};
-
+use btc::{gen_keypair, ExtendedPrivkey, Privkey, PublicKey};
+use config::{Config, FeePolicy, GlobalConfig, LocalConfig};
+use keystore::{self, EncryptedKeypair, EncryptedPrivkey};
+use rpc::{BitcoinRpcConfig, BtcRelayKind};
+use signer::{BtcSigner, LocalSigner, RemoteSigner};
+use sync::SyncMode;
+
+use std::path::PathBuf;
 use std::sync::{Arc, RwLock};
 mod args;
 
@@ -54,7 +58,7 @@ const BTC_ANCHORING_FEE: NamedArgumentRequired<u64> = NamedArgumentRequired {
     name: "btc_anchoring_fee",
     short_key: None,
     long_key: "btc-anchoring-fee",
-    help: "Transaction fee per byte in satoshi that anchoring nodes should use.",
+    help: "Transaction fee per virtual byte in satoshi that anchoring nodes should use.",
     default: Some(100),
 };
 
@@ -124,12 +128,75 @@ const BTC_ANCHORING_RPC_PASSWORD: NamedArgumentOptional<String> = NamedArgumentO
     default: None,
 };
 
+const BTC_ANCHORING_RPC_COOKIE_FILE: NamedArgumentOptional<PathBuf> = NamedArgumentOptional {
+    name: "btc_anchoring_rpc_cookie_file",
+    short_key: None,
+    long_key: "btc-anchoring-rpc-cookie-file",
+    help: "Path to the bitcoind cookie file used instead of the user and password.",
+    default: None,
+};
+
+const BTC_ANCHORING_RPC_SECRETS_FILE: NamedArgumentOptional<PathBuf> = NamedArgumentOptional {
+    name: "btc_anchoring_rpc_secrets_file",
+    short_key: None,
+    long_key: "btc-anchoring-rpc-secrets-file",
+    help: "Path to the TOML file with the user and password to login into bitcoind.",
+    default: None,
+};
+
+const BTC_ANCHORING_RPC_USERNAME_ENV: NamedArgumentOptional<String> = NamedArgumentOptional {
+    name: "btc_anchoring_rpc_user_env",
+    short_key: None,
+    long_key: "btc-anchoring-rpc-user-env",
+    help: "Environment variable with the user to login into bitcoind.",
+    default: None,
+};
+
+const BTC_ANCHORING_RPC_PASSWORD_ENV: NamedArgumentOptional<String> = NamedArgumentOptional {
+    name: "btc_anchoring_rpc_password_env",
+    short_key: None,
+    long_key: "btc-anchoring-rpc-password-env",
+    help: "Environment variable with the password to login into bitcoind.",
+    default: None,
+};
+
+const BTC_ANCHORING_RPC_CA_CERTIFICATE: NamedArgumentOptional<PathBuf> = NamedArgumentOptional {
+    name: "btc_anchoring_rpc_ca_certificate",
+    short_key: None,
+    long_key: "btc-anchoring-rpc-ca-certificate",
+    help: "Path to the PEM-encoded CA certificate of the HTTPS relay endpoint.",
+    default: None,
+};
+
+const BTC_ANCHORING_ENCRYPT_KEYS: NamedArgumentOptional<bool> = NamedArgumentOptional {
+    name: "btc_anchoring_encrypt_keys",
+    short_key: None,
+    long_key: "btc-anchoring-encrypt-keys",
+    help: "Encrypt the anchoring private key with the passphrase (true or false).",
+    default: None,
+};
+
+const BTC_ANCHORING_EXTENDED_KEY: NamedArgumentOptional<bool> = NamedArgumentOptional {
+    name: "btc_anchoring_extended_key",
+    short_key: None,
+    long_key: "btc-anchoring-extended-key",
+    help: "Derive the anchoring keys from the extended private key (true or false).",
+    default: None,
+};
+
 impl CommandExtension for GenerateNodeConfig {
     fn args(&self) -> Vec<Argument> {
         vec![
             BTC_ANCHORING_RPC_HOST.to_argument(),
             BTC_ANCHORING_RPC_USERNAME.to_argument(),
             BTC_ANCHORING_RPC_PASSWORD.to_argument(),
+            BTC_ANCHORING_RPC_COOKIE_FILE.to_argument(),
+            BTC_ANCHORING_RPC_SECRETS_FILE.to_argument(),
+            BTC_ANCHORING_RPC_USERNAME_ENV.to_argument(),
+            BTC_ANCHORING_RPC_PASSWORD_ENV.to_argument(),
+            BTC_ANCHORING_RPC_CA_CERTIFICATE.to_argument(),
+            BTC_ANCHORING_ENCRYPT_KEYS.to_argument(),
+            BTC_ANCHORING_EXTENDED_KEY.to_argument(),
         ]
     }
 
@@ -144,25 +211,61 @@ impl CommandExtension for GenerateNodeConfig {
 
         // Inserts Bitcoin keypair.
         let network = BTC_ANCHORING_NETWORK.output_value(&common_config.services_config)?;
-        let keypair = gen_keypair(network);
+        let extended_key = BTC_ANCHORING_EXTENDED_KEY
+            .input_value(&context)?
+            .unwrap_or_default();
+        let encrypt_keys = BTC_ANCHORING_ENCRYPT_KEYS
+            .input_value(&context)?
+            .unwrap_or_default();
+        let passphrase = if encrypt_keys {
+            Some(keystore::new_passphrase()?)
+        } else {
+            None
+        };
+        let keypair = if extended_key {
+            // The initial anchoring key is derived for the zero epoch.
+            let extended_private_key = ExtendedPrivkey::generate(network);
+            // Inserts the extended private key encrypted with the passphrase if requested.
+            if let Some(ref passphrase) = passphrase {
+                services_secret_config.insert(
+                    "btc_anchoring_encrypted_extended_private_key".to_owned(),
+                    toml::Value::try_from(EncryptedPrivkey::encrypt_extended(
+                        &extended_private_key,
+                        passphrase,
+                    ))?,
+                );
+            } else {
+                services_secret_config.insert(
+                    "btc_anchoring_extended_private_key".to_owned(),
+                    toml::Value::try_from(extended_private_key.clone())?,
+                );
+            }
+            extended_private_key.derive_keypair(0)?
+        } else {
+            gen_keypair(network)
+        };
 
         services_public_config.insert(
             "btc_anchoring_public_key".to_owned(),
             toml::Value::try_from(keypair.0)?,
         );
-        services_secret_config.extend(
-            vec![
-                (
-                    "btc_anchoring_public_key".to_owned(),
-                    toml::Value::try_from(keypair.0)?,
-                ),
-                (
-                    "btc_anchoring_private_key".to_owned(),
-                    toml::Value::try_from(keypair.1)?,
-                ),
-            ]
-            .into_iter(),
+        services_secret_config.insert(
+            "btc_anchoring_public_key".to_owned(),
+            toml::Value::try_from(keypair.0)?,
         );
+        // Inserts the private key encrypted with the passphrase if requested.
+        if let Some(ref passphrase) = passphrase {
+            let encrypted_key = EncryptedPrivkey::encrypt(&keypair.1, passphrase);
+            services_secret_config.insert(
+                "btc_anchoring_encrypted_private_key".to_owned(),
+                toml::Value::try_from(encrypted_key)?,
+            );
+        } else {
+            services_secret_config.insert(
+                "btc_anchoring_private_key".to_owned(),
+                toml::Value::try_from(keypair.1)?,
+            );
+        }
 
         // Inserts RPC host.
         let host = BTC_ANCHORING_RPC_HOST.input_value(&context)?;
@@ -170,6 +273,12 @@ impl CommandExtension for GenerateNodeConfig {
             host,
             username: BTC_ANCHORING_RPC_USERNAME.input_value(&context)?,
             password: BTC_ANCHORING_RPC_PASSWORD.input_value(&context)?,
+            kind: BtcRelayKind::default(),
+            cookie_file: BTC_ANCHORING_RPC_COOKIE_FILE.input_value(&context)?,
+            secrets_file: BTC_ANCHORING_RPC_SECRETS_FILE.input_value(&context)?,
+            username_env: BTC_ANCHORING_RPC_USERNAME_ENV.input_value(&context)?,
+            password_env: BTC_ANCHORING_RPC_PASSWORD_ENV.input_value(&context)?,
+            ca_certificate: BTC_ANCHORING_RPC_CA_CERTIFICATE.input_value(&context)?,
         };
         services_secret_config.insert(
             "btc_anchoring_rpc_config".to_owned(),
@@ -224,11 +333,33 @@ impl CommandExtension for Finalize {
             BTC_ANCHORING_UTXO_CONFIRMATIONS.output_value(&common_config.services_config)?;
 
         // Private part.
-        let private_key: Privkey = services_secret_config
-            .get("btc_anchoring_private_key")
-            .ok_or_else(|| format_err!("BTC private key not found"))?
-            .clone()
-            .try_into()?;
+        let private_key: Option<Privkey> =
+            match services_secret_config.get("btc_anchoring_private_key") {
+                Some(value) => Some(value.clone().try_into()?),
+                None => None,
+            };
+        let encrypted_private_key: Option<EncryptedPrivkey> =
+            match services_secret_config.get("btc_anchoring_encrypted_private_key") {
+                Some(value) => Some(value.clone().try_into()?),
+                None => None,
+            };
+        let extended_private_key: Option<ExtendedPrivkey> =
+            match services_secret_config.get("btc_anchoring_extended_private_key") {
+                Some(value) => Some(value.clone().try_into()?),
+                None => None,
+            };
+        let encrypted_extended_private_key: Option<EncryptedPrivkey> =
+            match services_secret_config.get("btc_anchoring_encrypted_extended_private_key") {
+                Some(value) => Some(value.clone().try_into()?),
+                None => None,
+            };
+        ensure!(
+            private_key.is_some()
+                || encrypted_private_key.is_some()
+                || extended_private_key.is_some()
+                || encrypted_extended_private_key.is_some(),
+            "BTC private key not found"
+        );
         let rpc_config: BitcoinRpcConfig = services_secret_config
             .get("btc_anchoring_rpc_config")
             .ok_or_else(|| format_err!("Bitcoin RPC configuration not found"))?
@@ -259,7 +390,7 @@ impl CommandExtension for Finalize {
         // Creates global configuration.
         let mut global_config = GlobalConfig::with_public_keys(network, public_keys)?;
         // Generates initial funding transaction.
-        let relay = BitcoinRpcClient::from(rpc_config.clone());
+        let relay = rpc_config.btc_relay()?;
         let addr = global_config.anchoring_address();
         let funding_tx = if let Some(funding_txid) = funding_txid {
             let info = relay.transaction_info(&funding_txid)?.ok_or_else(|| {
@@ -285,17 +416,31 @@ impl CommandExtension for Finalize {
 
         info!("BTC anchoring address is {}", addr);
 
-        global_config.funding_transaction = Some(funding_tx);
+        global_config.funding_transactions = vec![funding_tx];
         global_config.anchoring_interval = interval;
-        global_config.transaction_fee = fee;
+        global_config.fee_policy = FeePolicy::Fixed { fee };
+        global_config.validate()?;
 
         // Creates local configuration.
         let mut private_keys = HashMap::new();
-        private_keys.insert(addr, private_key);
+        let mut encrypted_private_keys = Vec::new();
+        if let Some(private_key) = private_key {
+            private_keys.insert(addr, private_key);
+        } else if let Some(encrypted_private_key) = encrypted_private_key {
+            encrypted_private_keys.push(EncryptedKeypair {
+                address: addr,
+                encrypted_private_key,
+            });
+        }
 
         let local_config = LocalConfig {
             rpc: Some(rpc_config),
+            backup_rpc: Vec::new(),
             private_keys,
+            encrypted_private_keys,
+            extended_private_key,
+            encrypted_extended_private_key,
+            remote_signer: None,
         };
 
         // Writes complete configuration to node_config.
@@ -332,23 +477,50 @@ impl ServiceFactory for BtcAnchoringFactory {
 
     fn make_service(&mut self, context: &Context) -> Box<dyn Service> {
         let node_config = context.get(keys::NODE_CONFIG).unwrap();
-        let btc_anchoring_config: Config = node_config
+        let mut btc_anchoring_config: Config = node_config
             .services_configs
             .get(BTC_ANCHORING_SERVICE_NAME)
             .expect("BTC anchoring config not found")
             .clone()
             .try_into()
             .unwrap();
+        btc_anchoring_config
+            .global
+            .validate()
+            .unwrap_or_else(|e| panic!("Invalid anchoring configuration: {}", e));
+        if btc_anchoring_config.local.has_encrypted_keys() {
+            keystore::passphrase()
+                .and_then(|passphrase| {
+                    btc_anchoring_config
+                        .local
+                        .decrypt_private_keys(&passphrase)
+                        .map_err(From::from)
+                })
+                .unwrap_or_else(|e| panic!("Unable to decrypt anchoring private keys: {}", e));
+        }
 
         let btc_relay = btc_anchoring_config
             .local
-            .rpc
-            .map(BitcoinRpcClient::from)
-            .map(Box::<dyn BtcRelay>::from);
-        let service = BtcAnchoringService::new(
+            .btc_relay()
+            .unwrap_or_else(|e| panic!("Unable to create Bitcoin relay: {}", e));
+        let signer: Arc<dyn BtcSigner> = match btc_anchoring_config.local.remote_signer {
+            Some(config) => Arc::new(
+                RemoteSigner::new(&config)
+                    .unwrap_or_else(|e| panic!("Unable to create remote signer: {}", e)),
+            ),
+            None => {
+                let private_keys = Arc::new(RwLock::new(btc_anchoring_config.local.private_keys));
+                match btc_anchoring_config.local.extended_private_key {
+                    Some(key) => Arc::new(LocalSigner::with_extended_key(private_keys, key)),
+                    None => Arc::new(LocalSigner::new(private_keys)),
+                }
+            }
+        };
+        let service = BtcAnchoringService::with_signer(
             btc_anchoring_config.global,
-            Arc::new(RwLock::new(btc_anchoring_config.local.private_keys)),
+            signer,
             btc_relay,
+            SyncMode::default(),
         );
         Box::new(service)
     }
@@ -15,36 +15,38 @@
 use exonum::blockchain::ServiceContext;
 use exonum::helpers::ValidatorId;
 
-use btc_transaction_utils::p2wsh;
-use btc_transaction_utils::TxInRef;
 use failure;
 
 use std::cmp;
-use std::collections::HashMap;
 
+use audit::{AuditLog, Discrepancy};
 use blockchain::data_layout::TxInputId;
-use blockchain::transactions::TxSignature;
-use blockchain::{BtcAnchoringSchema, BtcAnchoringState};
-use btc::{Address, Privkey};
+use blockchain::{batch_first_height, BtcAnchoringSchema, BtcAnchoringState};
+use btc::Transaction;
 use rpc::BtcRelay;
+use signer::{SigningRequest, SigningWorker};
 
 /// The goal of this task is to create anchoring transactions for the corresponding heights.
 pub struct UpdateAnchoringChainTask<'a> {
     context: &'a ServiceContext,
     anchoring_state: BtcAnchoringState,
-    private_keys: &'a HashMap<Address, Privkey>,
+    signing_worker: &'a SigningWorker,
+    audit_log: &'a AuditLog,
 }
 
 impl<'a> UpdateAnchoringChainTask<'a> {
-    /// Creates the anchoring chain updater for the given context and private keys.
+    /// Creates the anchoring chain updater for the given context and signing worker.
+    /// Non-validator nodes use the given audit log to audit the anchoring chain.
     pub fn new(
         context: &'a ServiceContext,
-        private_keys: &'a HashMap<Address, Privkey>,
+        signing_worker: &'a SigningWorker,
+        audit_log: &'a AuditLog,
     ) -> UpdateAnchoringChainTask<'a> {
         UpdateAnchoringChainTask {
             context,
             anchoring_state: BtcAnchoringSchema::new(context.snapshot()).actual_state(),
-            private_keys,
+            signing_worker,
+            audit_log,
         }
     }
 
@@ -52,31 +54,35 @@ impl<'a> UpdateAnchoringChainTask<'a> {
     /// the corresponding anchoring transaction if there is such a need.
     pub fn run(self) -> Result<(), failure::Error> {
         if let Some(validator_id) = self.context.validator_id() {
-            let address = self.anchoring_state.output_address();
-
-            let privkey = self
-                .private_keys
-                .get(&address)
-                .ok_or_else(|| format_err!("Private key for the address {} is absent.", address))?;
-
-            self.handle_as_validator(validator_id, &privkey)
+            self.handle_as_validator(validator_id)
         } else {
             self.handle_as_auditor()
         }
     }
 
-    fn handle_as_validator(
-        self,
-        validator_id: ValidatorId,
-        privkey: &Privkey,
-    ) -> Result<(), failure::Error> {
+    fn handle_as_validator(self, validator_id: ValidatorId) -> Result<(), failure::Error> {
+        // The configuration service doesn't check the anchoring configuration, so the invalid
+        // one can be installed by the configuration update. Such configuration isn't signed.
+        let configs = Some(self.anchoring_state.actual_configuration())
+            .into_iter()
+            .chain(self.anchoring_state.following_configuration());
+        for config in configs {
+            config
+                .validate()
+                .map_err(|e| format_err!("Invalid anchoring configuration: {}", e))?;
+        }
+
         let schema = BtcAnchoringSchema::new(self.context.snapshot());
         let latest_anchored_height = schema.latest_anchored_height();
         let anchoring_height = self
             .anchoring_state
             .following_anchoring_height(latest_anchored_height);
 
-        if self.context.height() < anchoring_height {
+        // Replacement of the stuck anchoring transaction doesn't wait for the anchoring height.
+        let is_fee_bump = schema
+            .proposed_fee_bump(&self.anchoring_state)
+            .map_or(false, |replacement| replacement.is_ok());
+        if self.context.height() < anchoring_height && !is_fee_bump {
             return Ok(());
         }
 
@@ -88,13 +94,9 @@ impl<'a> UpdateAnchoringChainTask<'a> {
                 return Ok(());
             };
 
-        let config = self.anchoring_state.actual_configuration();
-        let redeem_script = config.redeem_script();
-        // Creates `Signature` transactions.
-        let pubkey = redeem_script.content().public_keys[validator_id.0 as usize];
-        let mut signer = p2wsh::InputSigner::new(redeem_script);
-
-        for (index, proposal_input) in proposal_inputs.iter().enumerate() {
+        // Requests signatures only for the inputs which haven't been signed yet.
+        let mut inputs = Vec::new();
+        for (index, proposal_input) in proposal_inputs.into_iter().enumerate() {
             let input_id = TxInputId::new(proposal.id(), index as u32);
 
             if let Some(input_signatures) = schema.transaction_signatures().get(&input_id) {
@@ -107,91 +109,187 @@ impl<'a> UpdateAnchoringChainTask<'a> {
                     continue;
                 }
             }
-
-            let signature = signer.sign_input(
-                TxInRef::new(proposal.as_ref(), index),
-                proposal_inputs[index].as_ref(),
-                privkey.0.secret_key(),
-            )?;
-
-            signer
-                .verify_input(
-                    TxInRef::new(proposal.as_ref(), index),
-                    proposal_input.as_ref(),
-                    &pubkey,
-                    &signature,
-                )
-                .unwrap();
-
-            self.context.broadcast_transaction(TxSignature {
-                validator: validator_id,
-                transaction: proposal.clone(),
-                input: index as u32,
-                input_signature: signature.into(),
-            });
+            inputs.push((index, proposal_input));
+        }
+        if inputs.is_empty() {
+            return Ok(());
         }
 
-        Ok(())
+        self.signing_worker.sign(
+            self.context,
+            SigningRequest {
+                validator_id,
+                address: self.anchoring_state.output_address(),
+                config: self.anchoring_state.actual_configuration().clone(),
+                proposal,
+                inputs,
+            },
+        )
     }
 
+    /// Checks the finalized anchoring transactions against the anchored blocks. The checks
+    /// against the Bitcoin blockchain are performed by the synchronization worker,
+    /// so the block processing never waits for the Bitcoin relay.
+    ///
+    /// The following anchoring transaction may legitimately differ from the one that could
+    /// be built in advance, for example, because of the approved funding proposal or the fee
+    /// bump, thus each finalized transaction is only checked to be a valid continuation
+    /// of the anchoring chain.
     fn handle_as_auditor(self) -> Result<(), failure::Error> {
-        // TODO Think about corresponding business logic.
+        let schema = BtcAnchoringSchema::new(self.context.snapshot());
+        let mut report = self.audit_log.write().unwrap();
+        let anchoring_txs = schema.anchoring_transactions_chain();
+        // The stuck anchoring transaction in the tail of the chain may have been replaced.
+        if let Some(index) = report.checked_transactions.checked_sub(1) {
+            if anchoring_txs.get(index).map(|tx| tx.id()) != report.latest_checked {
+                report.checked_transactions = index;
+                report.confirmed_transactions = cmp::min(report.confirmed_transactions, index);
+            }
+        }
+
+        let anchored_blocks = schema.anchored_blocks();
+        let chain_id = schema.chain_id();
+        for index in report.checked_transactions..anchoring_txs.len() {
+            let tx = anchoring_txs.get(index).unwrap();
+            let txid = tx.id();
+
+            if index > 0 {
+                let prev_txid = anchoring_txs.get(index - 1).unwrap().id();
+                let is_recovery = tx
+                    .anchoring_payload()
+                    .map_or(false, |payload| payload.prev_tx_chain == Some(prev_txid));
+                // Anchoring transaction should spend the change output of the previous one.
+                let is_continuation = tx.spent_outputs().first() == Some(&(prev_txid, 0));
+                if !is_continuation && !is_recovery {
+                    report.record(Discrepancy::BrokenChain {
+                        index,
+                        txid,
+                        prev_txid,
+                    });
+                }
+            }
+
+            if let Some(payload) = tx.anchoring_payload() {
+                let is_state_mismatch = payload.state_commitment.is_some()
+                    && payload.state_commitment != schema.state_commitment(payload.block_height);
+                let is_batch_mismatch = payload.batch_root.is_some() && {
+                    let prev_height = index
+                        .checked_sub(1)
+                        .and_then(|prev_index| anchoring_txs.get(prev_index))
+                        .and_then(|prev_tx| prev_tx.anchoring_payload())
+                        .map(|prev_payload| prev_payload.block_height);
+                    let first_height = batch_first_height(prev_height, payload.block_height);
+                    payload.batch_root != schema.batch_root(first_height, payload.block_height)
+                };
+                let is_foreign = chain_id.map_or(false, |id| !payload.matches_chain(&id));
+                if anchored_blocks.get(payload.block_height.0) != Some(payload.block_hash)
+                    || is_state_mismatch
+                    || is_batch_mismatch
+                    || is_foreign
+                {
+                    report.record(Discrepancy::PayloadMismatch {
+                        index,
+                        txid,
+                        block_height: payload.block_height,
+                        block_hash: payload.block_hash,
+                    });
+                }
+            } else {
+                report.record(Discrepancy::MissingPayload { index, txid });
+            }
+
+            report.checked_transactions = index + 1;
+            report.latest_checked = Some(txid);
+        }
         Ok(())
     }
 }
 
+/// Outcome of the synchronization with the Bitcoin blockchain.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct SyncReport {
+    /// Index of the first uncommitted anchoring transaction if it exists.
+    pub first_uncommitted: Option<u64>,
+    /// Number of confirmations of the latest anchoring transaction if it has been
+    /// found out during the synchronization.
+    pub latest_confirmations: Option<u64>,
+    /// Number of confirmations of the transaction spent by the latest anchoring
+    /// transaction if it has been found out during the synchronization. It grows with
+    /// each Bitcoin block, so it is used to measure the time in the Bitcoin blocks.
+    pub prev_confirmations: Option<u64>,
+}
+
 /// The goal of this task is to push uncommitted anchoring transactions to the Bitcoin blockchain.
 #[derive(Debug)]
 pub struct SyncWithBtcRelayTask<'a> {
-    context: &'a ServiceContext,
     relay: &'a dyn BtcRelay,
+    anchoring_txs: &'a [Transaction],
 }
 
 impl<'a> SyncWithBtcRelayTask<'a> {
-    /// Creates synchronization task instance for the given context and the Bitcoin RPC relay.
-    pub fn new(context: &'a ServiceContext, relay: &'a dyn BtcRelay) -> SyncWithBtcRelayTask<'a> {
-        SyncWithBtcRelayTask { context, relay }
+    /// Creates synchronization task instance for the given anchoring transactions
+    /// and the Bitcoin RPC relay.
+    pub fn new(
+        relay: &'a dyn BtcRelay,
+        anchoring_txs: &'a [Transaction],
+    ) -> SyncWithBtcRelayTask<'a> {
+        SyncWithBtcRelayTask {
+            relay,
+            anchoring_txs,
+        }
     }
 
     /// Performs anchoring transactions synchronization with the Bitcoin blockchain.
     /// That is, it finds the first uncommitted anchoring transaction in the Bitcoin
     /// blockchain and sequentially sends it and the subsequent ones to the Bitcoin mempool.
-    pub fn run(self) -> Result<(), failure::Error> {
-        let schema = BtcAnchoringSchema::new(self.context.snapshot());
-        let sync_interval = cmp::max(1, schema.actual_configuration().anchoring_interval / 2);
-
-        if self.context.height().0 % sync_interval == 0 {
-            if let Some(index) = self.find_index_of_first_uncommitted_transaction()? {
-                let anchoring_txs = schema.anchoring_transactions_chain();
-                for tx in anchoring_txs.iter_from(index) {
-                    trace!(
-                        "Send anchoring transaction to btc relay: {}",
-                        tx.id().to_hex()
-                    );
-                    self.relay.send_transaction(&tx)?;
-                }
+    ///
+    /// Returns the index of the first uncommitted anchoring transaction if it exists.
+    pub fn run(&self) -> Result<SyncReport, failure::Error> {
+        let report = self.find_index_of_first_uncommitted_transaction()?;
+        if let Some(index) = report.first_uncommitted {
+            for tx in &self.anchoring_txs[index as usize..] {
+                trace!(
+                    "Send anchoring transaction to btc relay: {}",
+                    tx.id().to_hex()
+                );
+                self.relay.send_transaction(tx)?;
             }
         }
-
-        Ok(())
+        Ok(report)
     }
 
-    fn find_index_of_first_uncommitted_transaction(&self) -> Result<Option<u64>, failure::Error> {
-        let schema = BtcAnchoringSchema::new(self.context.snapshot());
-        let anchoring_txs = schema.anchoring_transactions_chain();
+    /// Returns the number of confirmations of the latest anchoring transaction.
+    /// Transactions which are unknown to the Bitcoin network have no confirmations.
+    pub fn latest_confirmations(&self) -> Result<Option<u64>, failure::Error> {
+        if let Some(tx) = self.anchoring_txs.last() {
+            let info = self.relay.transaction_info(&tx.id())?;
+            Ok(Some(info.map_or(0, |info| info.confirmations)))
+        } else {
+            Ok(None)
+        }
+    }
 
-        let anchoring_txs_len = anchoring_txs.len();
-        let tx_indices = (0..anchoring_txs_len).rev();
+    fn find_index_of_first_uncommitted_transaction(&self) -> Result<SyncReport, failure::Error> {
+        let mut report = SyncReport::default();
+        let tx_indices = (0..self.anchoring_txs.len()).rev();
         for index in tx_indices {
-            let tx = anchoring_txs.get(index).unwrap();
-            let info = self.relay.transaction_info(&tx.prev_tx_id())?;
-            if info.is_some() {
+            let tx = &self.anchoring_txs[index];
+            let prev_info = self.relay.transaction_info(&tx.prev_tx_id())?;
+            if let Some(prev_info) = prev_info {
                 let info = self.relay.transaction_info(&tx.id())?;
+                // The latest transaction is requested first, so its confirmations
+                // are known without the additional request.
+                if index + 1 == self.anchoring_txs.len() {
+                    report.latest_confirmations =
+                        Some(info.as_ref().map_or(0, |info| info.confirmations));
+                    report.prev_confirmations = Some(prev_info.confirmations);
+                }
                 if info.is_none() {
-                    return Ok(Some(index));
+                    report.first_uncommitted = Some(index as u64);
+                    return Ok(report);
                 }
             }
         }
-        Ok(None)
+        Ok(report)
     }
 }
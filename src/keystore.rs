@@ -0,0 +1,258 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Passphrase-based encryption of the anchoring private keys.
+//!
+//! The key for the `xsalsa20poly1305` cipher is derived from the passphrase
+//! with the `scrypt` function.
+
+use rpassword;
+use sodiumoxide::crypto::pwhash::{self, MemLimit, OpsLimit, Salt};
+use sodiumoxide::crypto::secretbox::{self, Key, Nonce};
+
+use std::env;
+use std::str::{self, FromStr};
+
+use btc::{Address, ExtendedPrivkey, Privkey};
+
+/// Name of the environment variable with the passphrase for the anchoring private keys.
+pub const PASSPHRASE_ENV: &str = "BTC_ANCHORING_PASSPHRASE";
+
+/// Errors that occur while decrypting the private key.
+#[derive(Debug, Clone, PartialEq, Fail)]
+pub enum KeystoreError {
+    /// The passphrase is wrong or the encrypted key has been corrupted.
+    #[fail(display = "Invalid passphrase or corrupted private key.")]
+    InvalidPassphrase,
+    /// Encrypted key has the invalid format.
+    #[fail(display = "Malformed encrypted private key: {}.", _0)]
+    Malformed(String),
+}
+
+/// Private key encrypted with the passphrase.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptedPrivkey {
+    /// Operations limit of the `scrypt` function. Values above the libsodium
+    /// `OPSLIMIT_SENSITIVE` are rejected.
+    pub opslimit: usize,
+    /// Memory limit of the `scrypt` function. Values above the libsodium
+    /// `MEMLIMIT_SENSITIVE` are rejected.
+    pub memlimit: usize,
+    /// Salt of the `scrypt` function.
+    #[serde(with = "::btc::hex_bytes")]
+    pub salt: Vec<u8>,
+    /// Nonce of the cipher.
+    #[serde(with = "::btc::hex_bytes")]
+    pub nonce: Vec<u8>,
+    /// Encrypted private key in the WIF format or the extended private key in the Base58
+    /// format along with the authentication tag.
+    #[serde(with = "::btc::hex_bytes")]
+    pub ciphertext: Vec<u8>,
+}
+
+impl EncryptedPrivkey {
+    /// Encrypts the private key with the given passphrase.
+    pub fn encrypt(privkey: &Privkey, passphrase: &str) -> Self {
+        Self::seal(&privkey.to_string(), passphrase)
+    }
+
+    /// Encrypts the extended private key with the given passphrase.
+    pub fn encrypt_extended(extended_privkey: &ExtendedPrivkey, passphrase: &str) -> Self {
+        Self::seal(&extended_privkey.to_string(), passphrase)
+    }
+
+    /// Decrypts the private key with the given passphrase.
+    pub fn decrypt(&self, passphrase: &str) -> Result<Privkey, KeystoreError> {
+        let plaintext = self.open(passphrase)?;
+        str::from_utf8(&plaintext)
+            .ok()
+            .and_then(|wif| Privkey::from_str(wif).ok())
+            .ok_or_else(|| KeystoreError::Malformed("invalid private key".to_owned()))
+    }
+
+    /// Decrypts the extended private key with the given passphrase.
+    pub fn decrypt_extended(&self, passphrase: &str) -> Result<ExtendedPrivkey, KeystoreError> {
+        let plaintext = self.open(passphrase)?;
+        str::from_utf8(&plaintext)
+            .ok()
+            .and_then(|base58| ExtendedPrivkey::from_str(base58).ok())
+            .ok_or_else(|| KeystoreError::Malformed("invalid extended private key".to_owned()))
+    }
+
+    fn seal(plaintext: &str, passphrase: &str) -> Self {
+        let opslimit = pwhash::OPSLIMIT_INTERACTIVE;
+        let memlimit = pwhash::MEMLIMIT_INTERACTIVE;
+        let salt = pwhash::gen_salt();
+        let nonce = secretbox::gen_nonce();
+
+        let key = derive_key(passphrase, &salt, opslimit, memlimit)
+            .expect("Unable to derive key with the default parameters");
+        let ciphertext = secretbox::seal(plaintext.as_bytes(), &nonce, &key);
+        EncryptedPrivkey {
+            opslimit: opslimit.0,
+            memlimit: memlimit.0,
+            salt: salt.0.to_vec(),
+            nonce: nonce.0.to_vec(),
+            ciphertext,
+        }
+    }
+
+    fn open(&self, passphrase: &str) -> Result<Vec<u8>, KeystoreError> {
+        // The parameters come from the configuration file, so they are bounded to prevent
+        // the key derivation from exhausting the node resources.
+        if self.opslimit > pwhash::OPSLIMIT_SENSITIVE.0 {
+            return Err(KeystoreError::Malformed("too large opslimit".to_owned()));
+        }
+        if self.memlimit > pwhash::MEMLIMIT_SENSITIVE.0 {
+            return Err(KeystoreError::Malformed("too large memlimit".to_owned()));
+        }
+        let salt = Salt::from_slice(&self.salt)
+            .ok_or_else(|| KeystoreError::Malformed("invalid salt length".to_owned()))?;
+        let nonce = Nonce::from_slice(&self.nonce)
+            .ok_or_else(|| KeystoreError::Malformed("invalid nonce length".to_owned()))?;
+
+        let key = derive_key(
+            passphrase,
+            &salt,
+            OpsLimit(self.opslimit),
+            MemLimit(self.memlimit),
+        )?;
+        secretbox::open(&self.ciphertext, &nonce, &key)
+            .map_err(|_| KeystoreError::InvalidPassphrase)
+    }
+}
+
+/// The anchoring address and the corresponding encrypted private key.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EncryptedKeypair {
+    /// Bitcoin address.
+    pub address: Address,
+    /// Corresponding encrypted private key.
+    pub encrypted_private_key: EncryptedPrivkey,
+}
+
+/// Returns the passphrase for the anchoring private keys from the `BTC_ANCHORING_PASSPHRASE`
+/// environment variable or, if the variable is not set, prompts it from the terminal.
+pub fn passphrase() -> Result<String, ::failure::Error> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    rpassword::prompt_password_stderr("Passphrase for the anchoring private keys: ")
+        .map_err(From::from)
+}
+
+/// Returns the new passphrase for the anchoring private keys from the `BTC_ANCHORING_PASSPHRASE`
+/// environment variable or, if the variable is not set, prompts it twice from the terminal.
+pub fn new_passphrase() -> Result<String, ::failure::Error> {
+    if let Ok(passphrase) = env::var(PASSPHRASE_ENV) {
+        return Ok(passphrase);
+    }
+    let passphrase =
+        rpassword::prompt_password_stderr("New passphrase for the anchoring private keys: ")?;
+    let confirmation = rpassword::prompt_password_stderr("Repeat the passphrase: ")?;
+    ensure!(passphrase == confirmation, "Passphrases do not match");
+    Ok(passphrase)
+}
+
+fn derive_key(
+    passphrase: &str,
+    salt: &Salt,
+    opslimit: OpsLimit,
+    memlimit: MemLimit,
+) -> Result<Key, KeystoreError> {
+    let mut key = Key([0; secretbox::KEYBYTES]);
+    pwhash::derive_key(&mut key.0, passphrase.as_bytes(), salt, opslimit, memlimit)
+        .map_err(|_| KeystoreError::Malformed("invalid key derivation parameters".to_owned()))?;
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use bitcoin::network::constants::Network;
+    use sodiumoxide::crypto::pwhash;
+
+    use super::{EncryptedPrivkey, KeystoreError};
+    use btc::{gen_keypair, ExtendedPrivkey};
+
+    #[test]
+    fn test_encrypted_privkey_roundtrip() {
+        let (_, privkey) = gen_keypair(Network::Testnet);
+        let encrypted = EncryptedPrivkey::encrypt(&privkey, "correct horse battery staple");
+        assert_ne!(encrypted.ciphertext, privkey.to_string().into_bytes());
+
+        let decrypted = encrypted.decrypt("correct horse battery staple").unwrap();
+        assert_eq!(decrypted.to_string(), privkey.to_string());
+        assert_eq!(
+            encrypted.decrypt("wrong passphrase").unwrap_err(),
+            KeystoreError::InvalidPassphrase
+        );
+    }
+
+    #[test]
+    fn test_encrypted_extended_privkey_roundtrip() {
+        let extended_privkey = ExtendedPrivkey::generate(Network::Testnet);
+        let encrypted = EncryptedPrivkey::encrypt_extended(&extended_privkey, "passphrase");
+
+        let decrypted = encrypted.decrypt_extended("passphrase").unwrap();
+        assert_eq!(decrypted.to_string(), extended_privkey.to_string());
+        assert_eq!(
+            encrypted.decrypt_extended("wrong passphrase").unwrap_err(),
+            KeystoreError::InvalidPassphrase
+        );
+        assert_matches!(
+            encrypted.decrypt("passphrase"),
+            Err(KeystoreError::Malformed(_))
+        );
+    }
+
+    #[test]
+    fn test_encrypted_privkey_serde() {
+        let (_, privkey) = gen_keypair(Network::Testnet);
+        let encrypted = EncryptedPrivkey::encrypt(&privkey, "passphrase");
+
+        let toml = ::toml::to_string(&encrypted).unwrap();
+        let encrypted2: EncryptedPrivkey = ::toml::from_str(&toml).unwrap();
+        assert_eq!(encrypted2, encrypted);
+
+        let mut corrupted = encrypted;
+        corrupted.nonce.pop();
+        assert_matches!(
+            corrupted.decrypt("passphrase"),
+            Err(KeystoreError::Malformed(_))
+        );
+    }
+
+    #[test]
+    fn test_encrypted_privkey_kdf_limits() {
+        let (_, privkey) = gen_keypair(Network::Testnet);
+        let encrypted = EncryptedPrivkey::encrypt(&privkey, "passphrase");
+
+        let corrupted = EncryptedPrivkey {
+            opslimit: pwhash::OPSLIMIT_SENSITIVE.0 + 1,
+            ..encrypted.clone()
+        };
+        assert_eq!(
+            corrupted.decrypt("passphrase").unwrap_err(),
+            KeystoreError::Malformed("too large opslimit".to_owned())
+        );
+        let corrupted = EncryptedPrivkey {
+            memlimit: usize::max_value(),
+            ..encrypted
+        };
+        assert_eq!(
+            corrupted.decrypt("passphrase").unwrap_err(),
+            KeystoreError::Malformed("too large memlimit".to_owned())
+        );
+    }
+}
@@ -79,21 +79,28 @@ extern crate serde_json;
 #[macro_use]
 extern crate maplit;
 
+#[cfg(test)]
+extern crate futures;
 #[cfg(test)]
 #[macro_use]
 extern crate matches;
 #[cfg(test)]
 #[macro_use]
 extern crate proptest;
+#[cfg(test)]
+extern crate tempfile;
 
+extern crate base64;
 extern crate bitcoin;
 extern crate btc_transaction_utils;
 extern crate byteorder;
 extern crate exonum;
-extern crate exonum_bitcoinrpc as bitcoin_rpc;
+extern crate exonum_sodiumoxide as sodiumoxide;
 extern crate hex;
 extern crate protobuf;
 extern crate rand;
+extern crate reqwest;
+extern crate rpassword;
 extern crate secp256k1;
 extern crate serde;
 extern crate serde_str;
@@ -105,12 +112,17 @@ pub use factory::BtcAnchoringFactory as ServiceFactory;
 pub use service::{BtcAnchoringService, BTC_ANCHORING_SERVICE_ID, BTC_ANCHORING_SERVICE_NAME};
 
 pub mod api;
+pub mod audit;
 pub mod blockchain;
 pub mod btc;
 pub mod config;
+pub mod esplora;
 pub(crate) mod factory;
+pub mod keystore;
 pub mod rpc;
 pub(crate) mod service;
+pub mod signer;
+pub mod sync;
 
 pub mod test_helpers;
 
@@ -18,7 +18,7 @@
 #![allow(bare_trait_objects)]
 #![allow(renamed_and_removed_lints)]
 
-pub use self::btc_anchoring::TxSignature;
+pub use self::btc_anchoring::{FundingProposal, TxFeeBump, TxFundingProposal, TxSignature};
 
 use bitcoin;
 use btc_transaction_utils;
@@ -30,6 +30,7 @@ use exonum::proto::ProtobufConvert;
 use btc;
 
 include!(concat!(env!("OUT_DIR"), "/protobuf_mod.rs"));
+use exonum::proto::schema::*;
 
 impl ProtobufConvert for btc::Transaction {
     type ProtoStruct = btc_anchoring::BtcTransaction;
@@ -17,11 +17,19 @@
 use exonum::crypto::Hash;
 
 use bitcoin::util::address::Address;
-use bitcoin_rpc;
 use failure;
-use hex::FromHex;
+use hex::{self, FromHex};
+use reqwest;
+use serde::de::DeserializeOwned;
+use serde_json;
+use toml;
 
-use btc::Transaction;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use btc::{MerkleProof, Transaction};
+use esplora::{http_client, EsploraClient};
 
 /// Short information about bitcoin transaction.
 #[derive(Debug, Clone, PartialEq)]
@@ -39,6 +47,9 @@ pub trait BtcRelay: Send + Sync + ::std::fmt::Debug {
         -> Result<Transaction, failure::Error>;
     /// Retrieves information about transaction with the given id.
     fn transaction_info(&self, id: &Hash) -> Result<Option<TransactionInfo>, failure::Error>;
+    /// Retrieves the proof of inclusion of the transaction with the given id in the Bitcoin
+    /// blockchain. Returns `None` if the transaction has not been included in a block yet.
+    fn merkle_proof(&self, id: &Hash) -> Result<Option<MerkleProof>, failure::Error>;
     /// Sends raw transaction to the bitcoin network.
     fn send_transaction(&self, transaction: &Transaction) -> Result<Hash, failure::Error>;
     /// Observes the changes on given address.
@@ -47,6 +58,22 @@ pub trait BtcRelay: Send + Sync + ::std::fmt::Debug {
     fn config(&self) -> BitcoinRpcConfig;
 }
 
+/// Kind of the API used to communicate with the Bitcoin network.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BtcRelayKind {
+    /// `Bitcoind` JSON-RPC API.
+    BitcoinRpc,
+    /// Esplora REST API.
+    Esplora,
+}
+
+impl Default for BtcRelayKind {
+    fn default() -> Self {
+        BtcRelayKind::BitcoinRpc
+    }
+}
+
 /// `Bitcoind` rpc configuration.
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
 pub struct BitcoinRpcConfig {
@@ -56,6 +83,89 @@ pub struct BitcoinRpcConfig {
     pub username: Option<String>,
     /// Rpc password.
     pub password: Option<String>,
+    /// Kind of the API available at the given url.
+    #[serde(default)]
+    pub kind: BtcRelayKind,
+    /// Path to the `.cookie` file of bitcoind, which contains credentials in the
+    /// `username:password` format. The file is read before each request, since bitcoind
+    /// regenerates it on restart.
+    pub cookie_file: Option<PathBuf>,
+    /// Path to the TOML file with the `username` and `password` fields.
+    pub secrets_file: Option<PathBuf>,
+    /// Name of the environment variable which contains the rpc username.
+    pub username_env: Option<String>,
+    /// Name of the environment variable which contains the rpc password.
+    pub password_env: Option<String>,
+    /// Path to the PEM-encoded certificate of the CA which is used to verify
+    /// the HTTPS endpoint in addition to the system ones.
+    pub ca_certificate: Option<PathBuf>,
+}
+
+/// Content of the file with rpc credentials.
+#[derive(Debug, Deserialize)]
+struct RpcSecrets {
+    username: Option<String>,
+    password: Option<String>,
+}
+
+impl BitcoinRpcConfig {
+    /// Creates Bitcoin relay of the configured kind for this endpoint.
+    pub fn btc_relay(&self) -> Result<Box<dyn BtcRelay>, failure::Error> {
+        let relay = match self.kind {
+            BtcRelayKind::BitcoinRpc => BitcoinRpcClient::new(self.clone())?.into(),
+            BtcRelayKind::Esplora => EsploraClient::new(self.clone())?.into(),
+        };
+        Ok(relay)
+    }
+
+    /// Returns the rpc username and password. Credentials from the cookie file take
+    /// precedence over the ones from the secrets file, which in turn take precedence
+    /// over the environment variables and the values from the configuration itself.
+    pub fn credentials(&self) -> Result<(Option<String>, Option<String>), failure::Error> {
+        if let Some(ref path) = self.cookie_file {
+            let cookie = fs::read_to_string(path)
+                .map_err(|e| format_err!("Unable to read cookie file {}: {}", path.display(), e))?;
+            let mut parts = cookie.trim().splitn(2, ':');
+            let username = parts.next().unwrap_or_default();
+            let password = parts
+                .next()
+                .ok_or_else(|| format_err!("Malformed cookie file {}", path.display()))?;
+            return Ok((Some(username.to_owned()), Some(password.to_owned())));
+        }
+
+        let mut username = self.username.clone();
+        let mut password = self.password.clone();
+        if let Some(ref name) = self.username_env {
+            username = Some(env_var(name)?);
+        }
+        if let Some(ref name) = self.password_env {
+            password = Some(env_var(name)?);
+        }
+        if let Some(ref path) = self.secrets_file {
+            let content = fs::read_to_string(path).map_err(|e| {
+                format_err!("Unable to read secrets file {}: {}", path.display(), e)
+            })?;
+            let secrets: RpcSecrets = toml::from_str(&content)?;
+            username = secrets.username.or(username);
+            password = secrets.password.or(password);
+        }
+        Ok((username, password))
+    }
+}
+
+fn env_var(name: &str) -> Result<String, failure::Error> {
+    env::var(name).map_err(|e| format_err!("Unable to read environment variable {}: {}", name, e))
+}
+
+/// Additional Bitcoin relay endpoint with the priority.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RelayEndpoint {
+    /// Endpoint configuration.
+    #[serde(flatten)]
+    pub rpc: BitcoinRpcConfig,
+    /// Priority of the endpoint. Endpoints with the greater priority are requested first.
+    #[serde(default)]
+    pub priority: u32,
 }
 
 /// Number of satoshis in a bitcoin.
@@ -64,21 +174,95 @@ pub struct BitcoinRpcConfig {
 /// which measures amounts in bitcoins (rather than satoshis).
 const SATOSHI_DIVISOR: f64 = 100_000_000.0;
 
+/// Error code returned by `bitcoind` if the requested transaction or block is unknown.
+const RPC_INVALID_ADDRESS_OR_KEY: i64 = -5;
+
+/// Error of the `bitcoind` JSON-RPC call.
+#[derive(Debug, Deserialize, Fail)]
+#[fail(display = "Bitcoind rpc error {}: {}", code, message)]
+struct RpcError {
+    code: i64,
+    message: String,
+}
+
+/// Response of the `bitcoind` JSON-RPC call.
+#[derive(Debug, Deserialize)]
+struct RpcResponse {
+    #[serde(default)]
+    result: serde_json::Value,
+    error: Option<RpcError>,
+}
+
+/// Verbose information about the transaction returned by the `getrawtransaction` call.
+#[derive(Debug, Deserialize)]
+struct RawTransactionInfo {
+    hex: String,
+    confirmations: Option<u64>,
+}
+
 /// Client for the `Bitcoind` rpc api.
 #[derive(Debug)]
-pub struct BitcoinRpcClient(bitcoin_rpc::Client);
+pub struct BitcoinRpcClient {
+    config: BitcoinRpcConfig,
+    client: reqwest::Client,
+}
 
 impl BitcoinRpcClient {
-    /// Creates a new rpc client for the given configuration.
-    pub fn new(config: BitcoinRpcConfig) -> Self {
-        let inner = bitcoin_rpc::Client::new(config.host, config.username, config.password);
-        BitcoinRpcClient(inner)
+    /// Creates a new rpc client for the given configuration. The configured CA certificate
+    /// is trusted in addition to the system ones.
+    ///
+    /// Returns an error if the configured CA certificate cannot be loaded.
+    pub fn new(config: BitcoinRpcConfig) -> Result<Self, failure::Error> {
+        let client = http_client(&config)?;
+        Ok(BitcoinRpcClient { config, client })
+    }
+
+    /// Performs the JSON-RPC call with the actual credentials.
+    fn call<T>(&self, method: &str, params: serde_json::Value) -> Result<T, failure::Error>
+    where
+        T: DeserializeOwned,
+    {
+        let request = json!({
+            "jsonrpc": "1.0",
+            "id": "btc-anchoring",
+            "method": method,
+            "params": params,
+        });
+        let mut request = self.client.post(&self.config.host).json(&request);
+        if let (Some(username), password) = self.config.credentials()? {
+            request = request.basic_auth(username, password);
+        }
+        // `bitcoind` responds with the error status along with the error description
+        // in the body, so the status itself is not checked.
+        let mut response = request.send()?;
+        let status = response.status();
+        let body = response.text()?;
+        let response: RpcResponse = serde_json::from_str(&body)
+            .map_err(|e| format_err!("Unexpected response to {} with {}: {}", method, status, e))?;
+        match response.error {
+            Some(error) => Err(error.into()),
+            None => serde_json::from_value(response.result).map_err(From::from),
+        }
     }
-}
 
-impl From<BitcoinRpcConfig> for BitcoinRpcClient {
-    fn from(cfg: BitcoinRpcConfig) -> Self {
-        Self::new(cfg)
+    /// Performs the JSON-RPC call, which returns `None` if `bitcoind` reports that
+    /// the requested object is unknown.
+    fn call_optional<T>(
+        &self,
+        method: &str,
+        params: serde_json::Value,
+    ) -> Result<Option<T>, failure::Error>
+    where
+        T: DeserializeOwned,
+    {
+        match self.call(method, params) {
+            Ok(value) => Ok(Some(value)),
+            Err(e) => match e.downcast::<RpcError>() {
+                Ok(ref error) if error.code == RPC_INVALID_ADDRESS_OR_KEY => Ok(None),
+                Ok(error) => Err(error.into()),
+                Err(e) => Err(e),
+            },
+        }
     }
 }
 
@@ -95,27 +279,21 @@ impl BtcRelay for BitcoinRpcClient {
         satoshis: u64,
     ) -> Result<Transaction, failure::Error> {
         let amount = satoshis as f64 / SATOSHI_DIVISOR;
-        let txid = self
-            .0
-            .sendtoaddress(&addr.to_string(), &amount.to_string())?;
-        let tx_hex = self.0.getrawtransaction(&txid)?;
+        let txid: String = self.call("sendtoaddress", json!([addr.to_string(), amount]))?;
+        let tx_hex: String = self.call("getrawtransaction", json!([txid]))?;
 
         Transaction::from_hex(tx_hex).map_err(From::from)
     }
 
     fn transaction_info(&self, id: &Hash) -> Result<Option<TransactionInfo>, failure::Error> {
-        let txid = id.to_hex();
-        let txinfo = match self.0.getrawtransaction_verbose(&txid) {
-            Ok(info) => info,
-            Err(bitcoin_rpc::Error::NoInformation(_)) => return Ok(None),
-            Err(e) => Err(e)?,
-        };
+        let txinfo: RawTransactionInfo =
+            match self.call_optional("getrawtransaction", json!([id.to_hex(), true]))? {
+                Some(info) => info,
+                None => return Ok(None),
+            };
 
-        let tx_hex = txinfo
-            .hex
-            .ok_or_else(|| bitcoin_rpc::Error::NoInformation(txid))?;
-        let content = Transaction::from_hex(tx_hex)?;
-        // TODO Check attentively documentation of `getrawtransaction` rpc call.
+        let content = Transaction::from_hex(txinfo.hex)?;
+        // Transactions from the mempool have no confirmations field.
         let confirmations = txinfo.confirmations.unwrap_or_default();
 
         Ok(Some(TransactionInfo {
@@ -124,23 +302,468 @@ impl BtcRelay for BitcoinRpcClient {
         }))
     }
 
+    fn merkle_proof(&self, id: &Hash) -> Result<Option<MerkleProof>, failure::Error> {
+        let merkle_block: String =
+            match self.call_optional("gettxoutproof", json!([[id.to_hex()]]))? {
+                Some(merkle_block) => merkle_block,
+                None => return Ok(None),
+            };
+
+        let bytes = hex::decode(merkle_block)?;
+        let proof = MerkleProof::from_merkle_block(&bytes, id)?;
+        Ok(Some(proof))
+    }
+
+    fn send_transaction(&self, transaction: &Transaction) -> Result<Hash, failure::Error> {
+        let _txid: String = self.call("sendrawtransaction", json!([transaction.to_string()]))?;
+        Ok(transaction.id())
+    }
+
+    fn watch_address(&self, addr: &Address, rescan: bool) -> Result<(), failure::Error> {
+        self.call(
+            "importaddress",
+            json!([addr.to_string(), "multisig", false, rescan]),
+        )
+    }
+
+    fn config(&self) -> BitcoinRpcConfig {
+        self.config.clone()
+    }
+}
+
+/// Bitcoin relay which distributes requests between several endpoints.
+///
+/// Read requests are sent to the endpoints in the order of their priority until
+/// the first successful response. Transactions are broadcast to all endpoints, and
+/// the transaction information is requested from all endpoints to take the answer
+/// of the majority.
+#[derive(Debug)]
+pub struct FailoverRelay {
+    endpoints: Vec<Box<dyn BtcRelay>>,
+}
+
+impl FailoverRelay {
+    /// Creates a new relay for the given endpoints ordered by decreasing priority.
+    ///
+    /// # Panics
+    ///
+    /// - If the endpoints list is empty.
+    pub fn new(endpoints: Vec<Box<dyn BtcRelay>>) -> Self {
+        assert!(
+            !endpoints.is_empty(),
+            "Failover relay requires at least one endpoint"
+        );
+        FailoverRelay { endpoints }
+    }
+
+    /// Performs the request to the endpoints in turn until the first successful response.
+    fn failover<T, F>(&self, f: F) -> Result<T, failure::Error>
+    where
+        F: Fn(&dyn BtcRelay) -> Result<T, failure::Error>,
+    {
+        let mut last_error = None;
+        for endpoint in &self.endpoints {
+            match f(endpoint.as_ref()) {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    warn!("Bitcoin relay {} failed: {}", endpoint.config().host, e);
+                    last_error = Some(e);
+                }
+            }
+        }
+        Err(last_error.unwrap())
+    }
+
+    /// Performs the request to all endpoints.
+    fn broadcast<T, F>(&self, f: F) -> Vec<Result<T, failure::Error>>
+    where
+        F: Fn(&dyn BtcRelay) -> Result<T, failure::Error>,
+    {
+        self.endpoints
+            .iter()
+            .map(|endpoint| {
+                f(endpoint.as_ref()).map_err(|e| {
+                    warn!("Bitcoin relay {} failed: {}", endpoint.config().host, e);
+                    e
+                })
+            })
+            .collect()
+    }
+
+    /// Returns the first successful result or the last error if all requests have failed.
+    fn any_success<T>(results: Vec<Result<T, failure::Error>>) -> Result<T, failure::Error> {
+        let mut last_error = None;
+        for result in results {
+            match result {
+                Ok(value) => return Ok(value),
+                Err(e) => last_error = Some(e),
+            }
+        }
+        Err(last_error.unwrap())
+    }
+}
+
+impl From<FailoverRelay> for Box<dyn BtcRelay> {
+    fn from(relay: FailoverRelay) -> Self {
+        Box::new(relay) as Self
+    }
+}
+
+impl BtcRelay for FailoverRelay {
+    fn send_to_address(
+        &self,
+        addr: &Address,
+        satoshis: u64,
+    ) -> Result<Transaction, failure::Error> {
+        // Funds must be sent only once.
+        self.failover(|relay| relay.send_to_address(addr, satoshis))
+    }
+
+    fn transaction_info(&self, id: &Hash) -> Result<Option<TransactionInfo>, failure::Error> {
+        // Answers grouped by the transaction content along with the reported confirmations.
+        let mut answers: Vec<(Option<Transaction>, Vec<u64>)> = Vec::new();
+        let mut last_error = None;
+        for result in self.broadcast(|relay| relay.transaction_info(id)) {
+            let (content, confirmations) = match result {
+                Ok(Some(info)) => (Some(info.content), info.confirmations),
+                Ok(None) => (None, 0),
+                Err(e) => {
+                    last_error = Some(e);
+                    continue;
+                }
+            };
+
+            if let Some(answer) = answers.iter_mut().find(|answer| answer.0 == content) {
+                answer.1.push(confirmations);
+                continue;
+            }
+            answers.push((content, vec![confirmations]));
+        }
+
+        if answers.len() > 1 {
+            warn!(
+                "Bitcoin relays gave inconsistent information about transaction {}",
+                id.to_hex()
+            );
+        }
+        // Among the answers with the same number of votes, the one given by
+        // the endpoint with the greater priority wins.
+        let mut majority: Option<(Option<Transaction>, Vec<u64>)> = None;
+        for answer in answers {
+            if majority
+                .as_ref()
+                .map_or(true, |m| m.1.len() < answer.1.len())
+            {
+                majority = Some(answer);
+            }
+        }
+        match majority {
+            Some((Some(content), confirmations)) => Ok(Some(TransactionInfo {
+                content,
+                // Takes the most conservative number of confirmations.
+                confirmations: confirmations.into_iter().min().unwrap_or_default(),
+            })),
+            Some((None, _)) => Ok(None),
+            None => Err(last_error.unwrap()),
+        }
+    }
+
+    fn merkle_proof(&self, id: &Hash) -> Result<Option<MerkleProof>, failure::Error> {
+        self.failover(|relay| relay.merkle_proof(id))
+    }
+
     fn send_transaction(&self, transaction: &Transaction) -> Result<Hash, failure::Error> {
-        let txid = transaction.id();
-        self.0.sendrawtransaction(&transaction.to_string())?;
-        Ok(txid)
+        Self::any_success(self.broadcast(|relay| relay.send_transaction(transaction)))
     }
 
     fn watch_address(&self, addr: &Address, rescan: bool) -> Result<(), failure::Error> {
-        self.0
-            .importaddress(&addr.to_string(), "multisig", false, rescan)
-            .map_err(From::from)
+        Self::any_success(self.broadcast(|relay| relay.watch_address(addr, rescan)))
     }
 
     fn config(&self) -> BitcoinRpcConfig {
-        BitcoinRpcConfig {
-            host: self.0.url().to_string(),
-            username: self.0.username().clone(),
-            password: self.0.password().clone(),
+        self.endpoints[0].config()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::hash;
+
+    use bitcoin::blockdata::block::BlockHeader;
+    use bitcoin::consensus;
+    use bitcoin::util::address::Address;
+    use bitcoin::util::hash::Sha256dHash;
+    use hex;
+
+    use tempfile::NamedTempFile;
+
+    use std::env;
+    use std::io::Write;
+    use std::str::FromStr;
+
+    use super::{BitcoinRpcClient, BitcoinRpcConfig, BtcRelay, FailoverRelay, TransactionInfo};
+    use btc::Transaction;
+    use test_helpers::http::{exchange, mock_server};
+    use test_helpers::rpc::{FakeBtcRelay, FakeRelayRequest, FakeRelayResponse, TestRequests};
+    use test_helpers::testkit::create_fake_funding_transaction;
+
+    fn failover_relay(count: usize) -> (FailoverRelay, Vec<TestRequests>) {
+        let endpoints = (0..count)
+            .map(|_| FakeBtcRelay::default())
+            .collect::<Vec<_>>();
+        let requests = endpoints.iter().map(|e| e.requests.clone()).collect();
+        let endpoints = endpoints
+            .into_iter()
+            .map(|e| Box::new(e) as Box<dyn BtcRelay>)
+            .collect();
+        (FailoverRelay::new(endpoints), requests)
+    }
+
+    fn transaction() -> Transaction {
+        let address =
+            Address::from_str("tb1q8270svuaqety59gegtp4ujjeam39s83csz7whp9ryn3zxlcee66setkyq0")
+                .unwrap();
+        create_fake_funding_transaction(&address, 100_000)
+    }
+
+    fn transaction_info(content: &Transaction, confirmations: u64) -> FakeRelayResponse {
+        FakeRelayResponse::TransactionInfo(Ok(Some(TransactionInfo {
+            content: content.clone(),
+            confirmations,
+        })))
+    }
+
+    fn temp_file(content: &str) -> NamedTempFile {
+        let mut file = NamedTempFile::new().unwrap();
+        file.write_all(content.as_bytes()).unwrap();
+        file
+    }
+
+    #[test]
+    fn test_rpc_credentials_cookie_file() {
+        let cookie_file = temp_file("__cookie__:5b7c5e1a2d\n");
+        let config = BitcoinRpcConfig {
+            host: String::from("http://localhost:18332"),
+            username: Some(String::from("user")),
+            password: Some(String::from("password")),
+            cookie_file: Some(cookie_file.path().to_owned()),
+            ..BitcoinRpcConfig::default()
+        };
+        assert_eq!(
+            config.credentials().unwrap(),
+            (
+                Some(String::from("__cookie__")),
+                Some(String::from("5b7c5e1a2d"))
+            )
+        );
+
+        let malformed_cookie_file = temp_file("__cookie__");
+        let config = BitcoinRpcConfig {
+            cookie_file: Some(malformed_cookie_file.path().to_owned()),
+            ..config
+        };
+        assert!(config.credentials().is_err());
+    }
+
+    #[test]
+    fn test_rpc_credentials_secrets() {
+        env::set_var("BTC_ANCHORING_TEST_RPC_USER", "env_user");
+        env::set_var("BTC_ANCHORING_TEST_RPC_PASSWORD", "env_password");
+
+        let config = BitcoinRpcConfig {
+            host: String::from("http://localhost:18332"),
+            username: Some(String::from("user")),
+            password: Some(String::from("password")),
+            username_env: Some(String::from("BTC_ANCHORING_TEST_RPC_USER")),
+            password_env: Some(String::from("BTC_ANCHORING_TEST_RPC_PASSWORD")),
+            ..BitcoinRpcConfig::default()
+        };
+        assert_eq!(
+            config.credentials().unwrap(),
+            (
+                Some(String::from("env_user")),
+                Some(String::from("env_password"))
+            )
+        );
+        // Secrets file overrides only the specified values.
+        let secrets_file = temp_file("password = \"file_password\"");
+        let config = BitcoinRpcConfig {
+            secrets_file: Some(secrets_file.path().to_owned()),
+            ..config
+        };
+        assert_eq!(
+            config.credentials().unwrap(),
+            (
+                Some(String::from("env_user")),
+                Some(String::from("file_password"))
+            )
+        );
+        // Missing environment variable.
+        let config = BitcoinRpcConfig {
+            username_env: Some(String::from("BTC_ANCHORING_TEST_RPC_MISSING")),
+            ..config
+        };
+        assert!(config.credentials().is_err());
+    }
+
+    #[test]
+    fn test_failover_relay_merkle_proof() {
+        let id = hash(&[1, 2, 3]);
+        let (relay, requests) = failover_relay(3);
+        requests[0].expect(vec![(
+            FakeRelayRequest::MerkleProof { id },
+            FakeRelayResponse::MerkleProof(Err(format_err!("Connection refused"))),
+        )]);
+        requests[1].expect(vec![(
+            FakeRelayRequest::MerkleProof { id },
+            FakeRelayResponse::MerkleProof(Ok(None)),
+        )]);
+        // The third endpoint is not requested.
+        assert_eq!(relay.merkle_proof(&id).unwrap(), None);
+
+        for endpoint_requests in &requests[..2] {
+            endpoint_requests.expect(vec![(
+                FakeRelayRequest::MerkleProof { id },
+                FakeRelayResponse::MerkleProof(Err(format_err!("Connection refused"))),
+            )]);
+        }
+        requests[2].expect(vec![(
+            FakeRelayRequest::MerkleProof { id },
+            FakeRelayResponse::MerkleProof(Err(format_err!("Service unavailable"))),
+        )]);
+        let error = relay.merkle_proof(&id).unwrap_err();
+        assert_eq!(error.to_string(), "Service unavailable");
+    }
+
+    #[test]
+    fn test_failover_relay_broadcasts_transaction() {
+        let tx = transaction();
+        let (relay, requests) = failover_relay(3);
+        requests[0].expect(vec![(
+            FakeRelayRequest::SendTransaction {
+                transaction: tx.clone(),
+            },
+            FakeRelayResponse::SendTransaction(Err(format_err!("Connection refused"))),
+        )]);
+        for endpoint_requests in &requests[1..] {
+            endpoint_requests.expect(vec![(
+                FakeRelayRequest::SendTransaction {
+                    transaction: tx.clone(),
+                },
+                FakeRelayResponse::SendTransaction(Ok(tx.id())),
+            )]);
+        }
+        assert_eq!(relay.send_transaction(&tx).unwrap(), tx.id());
+    }
+
+    #[test]
+    fn test_failover_relay_transaction_info_majority() {
+        let tx = transaction();
+        let id = tx.id();
+        let (relay, requests) = failover_relay(3);
+        // The endpoint with the greatest priority hasn't seen the transaction.
+        requests[0].expect(vec![(
+            FakeRelayRequest::TransactionInfo { id },
+            FakeRelayResponse::TransactionInfo(Ok(None)),
+        )]);
+        requests[1].expect(vec![(
+            FakeRelayRequest::TransactionInfo { id },
+            transaction_info(&tx, 3),
+        )]);
+        requests[2].expect(vec![(
+            FakeRelayRequest::TransactionInfo { id },
+            transaction_info(&tx, 2),
+        )]);
+        assert_eq!(
+            relay.transaction_info(&id).unwrap(),
+            Some(TransactionInfo {
+                content: tx.clone(),
+                confirmations: 2,
+            })
+        );
+
+        // Ties are resolved in favor of the endpoint with the greater priority.
+        requests[0].expect(vec![(
+            FakeRelayRequest::TransactionInfo { id },
+            FakeRelayResponse::TransactionInfo(Ok(None)),
+        )]);
+        requests[1].expect(vec![(
+            FakeRelayRequest::TransactionInfo { id },
+            FakeRelayResponse::TransactionInfo(Err(format_err!("Connection refused"))),
+        )]);
+        requests[2].expect(vec![(
+            FakeRelayRequest::TransactionInfo { id },
+            transaction_info(&tx, 1),
+        )]);
+        assert_eq!(relay.transaction_info(&id).unwrap(), None);
+
+        // All endpoints have failed.
+        for endpoint_requests in &requests {
+            endpoint_requests.expect(vec![(
+                FakeRelayRequest::TransactionInfo { id },
+                FakeRelayResponse::TransactionInfo(Err(format_err!("Connection refused"))),
+            )]);
+        }
+        assert!(relay.transaction_info(&id).is_err());
+    }
+
+    #[test]
+    fn test_rpc_merkle_proof() {
+        let tx = transaction();
+        // Block which contains the single transaction.
+        let mut header = BlockHeader {
+            version: 1,
+            prev_blockhash: Sha256dHash::default(),
+            merkle_root: tx.0.txid(),
+            time: 0,
+            bits: 0x207f_ffff,
+            nonce: 0,
+        };
+        while header.spv_validate(&header.target()).is_err() {
+            header.nonce += 1;
         }
+        let mut merkle_block = consensus::serialize(&header);
+        merkle_block.extend_from_slice(&[1, 0, 0, 0, 1]);
+        merkle_block.extend_from_slice(&tx.0.txid()[..]);
+        merkle_block.extend_from_slice(&[1, 1]);
+
+        let (url, server) = mock_server(vec![
+            exchange(
+                "POST / HTTP/1.1",
+                200,
+                format!(
+                    r#"{{"result":"{}","error":null,"id":"btc-anchoring"}}"#,
+                    hex::encode(&merkle_block)
+                ),
+            ),
+            exchange(
+                "POST / HTTP/1.1",
+                500,
+                r#"{"result":null,"error":{"code":-5,"message":"Transaction not yet in block"}}"#,
+            ),
+            exchange(
+                "POST / HTTP/1.1",
+                500,
+                r#"{"result":null,"error":{"code":-28,"message":"Loading block index..."}}"#,
+            ),
+        ]);
+        let client = BitcoinRpcClient::new(BitcoinRpcConfig {
+            host: format!("{}/", url),
+            username: Some(String::from("user")),
+            password: Some(String::from("password")),
+            ..BitcoinRpcConfig::default()
+        })
+        .unwrap();
+
+        let proof = client.merkle_proof(&tx.id()).unwrap().unwrap();
+        assert_eq!(proof.block_header, header);
+        assert_eq!(proof.index, 0);
+        assert_eq!(client.merkle_proof(&tx.id()).unwrap(), None);
+        let error = client.merkle_proof(&tx.id()).unwrap_err();
+        assert!(error.to_string().contains("Loading block index"));
+
+        let requests = server.join().unwrap();
+        assert!(requests[0].contains(r#""method":"gettxoutproof""#));
+        assert!(requests[0].contains(&format!(r#""params":[["{}"]]"#, tx.id().to_hex())));
     }
 }
@@ -28,11 +28,14 @@ use std::sync::{Arc, RwLock};
 use std::collections::HashMap;
 
 use api;
+use audit::AuditLog;
 use blockchain::{BtcAnchoringSchema, Transactions};
 use btc::{Address, Privkey};
 use config::GlobalConfig;
-use handler::{SyncWithBtcRelayTask, UpdateAnchoringChainTask};
+use handler::UpdateAnchoringChainTask;
 use rpc::BtcRelay;
+use signer::{BtcSigner, LocalSigner, SigningWorker};
+use sync::{SyncMode, SyncWorker};
 use ResultEx;
 
 /// Anchoring service id.
@@ -45,8 +48,9 @@ pub(crate) type KeyPool = Arc<RwLock<HashMap<Address, Privkey>>>;
 /// Btc anchoring service implementation for the Exonum blockchain.
 pub struct BtcAnchoringService {
     global_config: GlobalConfig,
-    private_keys: KeyPool,
-    btc_relay: Option<Box<dyn BtcRelay>>,
+    signing_worker: SigningWorker,
+    sync_worker: Option<SyncWorker>,
+    audit_log: AuditLog,
 }
 
 impl ::std::fmt::Debug for BtcAnchoringService {
@@ -56,16 +60,35 @@ impl ::std::fmt::Debug for BtcAnchoringService {
 }
 
 impl BtcAnchoringService {
-    /// Creates a new btc anchoring service instance.
+    /// Creates a new btc anchoring service instance. If the Bitcoin relay is specified,
+    /// the anchoring transactions are pushed to the Bitcoin network in the given mode.
     pub fn new(
         global_config: GlobalConfig,
         private_keys: KeyPool,
         btc_relay: Option<Box<dyn BtcRelay>>,
+        sync_mode: SyncMode,
     ) -> Self {
+        let signer = Arc::new(LocalSigner::new(private_keys));
+        Self::with_signer(global_config, signer, btc_relay, sync_mode)
+    }
+
+    /// Creates a new btc anchoring service instance, which signs the anchoring
+    /// transactions with the given signer.
+    pub fn with_signer(
+        global_config: GlobalConfig,
+        signer: Arc<dyn BtcSigner>,
+        btc_relay: Option<Box<dyn BtcRelay>>,
+        sync_mode: SyncMode,
+    ) -> Self {
+        let audit_log = AuditLog::default();
+        let signing_worker = SigningWorker::new(signer, sync_mode);
+        let sync_worker = btc_relay
+            .map(|relay| SyncWorker::new(Arc::from(relay), sync_mode, Arc::clone(&audit_log)));
         Self {
             global_config,
-            private_keys,
-            btc_relay,
+            signing_worker,
+            sync_worker,
+            audit_log,
         }
     }
 }
@@ -104,17 +127,21 @@ impl Service for BtcAnchoringService {
     }
 
     fn after_commit(&self, context: &ServiceContext) {
-        let keys = &self.private_keys.read().unwrap();
-        let task = UpdateAnchoringChainTask::new(context, keys);
+        let task = UpdateAnchoringChainTask::new(context, &self.signing_worker, &self.audit_log);
         task.run().log_error();
-        // TODO make this task async via tokio core or something else.
-        if let Some(ref relay) = self.btc_relay.as_ref() {
-            let task = SyncWithBtcRelayTask::new(context, relay.as_ref());
-            task.run().log_error();
+        // Synchronization with the Bitcoin network doesn't block the block processing.
+        if let Some(ref sync_worker) = self.sync_worker {
+            sync_worker.notify(context);
         }
     }
 
     fn wire_api(&self, builder: &mut ServiceApiBuilder) {
-        api::wire(builder);
+        // Signatures of the anchoring proposals are broadcast by the signing worker.
+        if let Some(api_sender) = builder.api_sender() {
+            self.signing_worker.set_api_sender(api_sender.clone());
+        }
+        let sync_state = self.sync_worker.as_ref().map(SyncWorker::state);
+        let proofs = self.sync_worker.as_ref().map(SyncWorker::proofs);
+        api::wire(builder, Arc::clone(&self.audit_log), sync_state, proofs);
     }
 }
@@ -0,0 +1,686 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Signers of the anchoring transaction inputs.
+//!
+//! Validators may keep the anchoring private keys out of the node process by using
+//! the [`RemoteSigner`](struct.RemoteSigner.html), which delegates signing to the external
+//! service, e.g. a bridge to the hardware security module. The service should accept
+//! `POST` requests to the `sign` path with the JSON body:
+//!
+//! ```json
+//! {
+//!     "address": "<anchoring address>",
+//!     "redeem_script": "<hex of the redeem script>",
+//!     "transaction": "<hex of the transaction to sign>",
+//!     "input": 0,
+//!     "spent_transaction": "<hex of the transaction with the spent output>"
+//! }
+//! ```
+//!
+//! and respond with the JSON object `{ "input_signature": "<hex>" }`, which contains
+//! the DER-encoded signature with the sighash type byte. If the shared secret is configured,
+//! requests contain it in the `Authorization: Bearer <secret>` header.
+//!
+//! Signing is performed in the separate thread, so the slow signer service doesn't delay
+//! the block processing. The requests are still limited by the timeout, and if the request
+//! fails, the node retries it after the next block.
+
+use exonum::blockchain::ServiceContext;
+use exonum::crypto::{PublicKey, SecretKey};
+use exonum::helpers::ValidatorId;
+use exonum::messages::Message;
+use exonum::node::ApiSender;
+
+use btc_transaction_utils::multisig::RedeemScript;
+use btc_transaction_utils::{p2wsh, TxInRef};
+use failure;
+use hex;
+use reqwest;
+
+use std::collections::HashMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use blockchain::transactions::TxSignature;
+use btc::{Address, ExtendedPrivkey, InputSignature, Privkey, Transaction};
+use config::GlobalConfig;
+use sync::SyncMode;
+use BTC_ANCHORING_SERVICE_ID;
+
+/// Default timeout of the single request to the remote signer in milliseconds.
+const DEFAULT_REQUEST_TIMEOUT: u64 = 3_000;
+/// Maximum number of requests waiting for the signing worker. Further requests are dropped,
+/// since the unsigned inputs are requested again after the next block.
+const MAX_PENDING_REQUESTS: usize = 1;
+
+/// Signer of the anchoring transaction inputs.
+pub trait BtcSigner: Send + Sync + ::std::fmt::Debug {
+    /// Signs the given input of the transaction, which spends the output of the given
+    /// transaction locked by the redeem script, with the private key for the given address.
+    fn sign_input(
+        &self,
+        address: &Address,
+        redeem_script: &RedeemScript,
+        transaction: &Transaction,
+        input: usize,
+        spent_transaction: &Transaction,
+    ) -> Result<InputSignature, failure::Error>;
+
+    /// Prepares the signer to sign the inputs with the key of the given validator for
+    /// the given address, which belongs to the given anchoring configuration.
+    /// Does nothing by default.
+    fn prepare_key(
+        &self,
+        _address: &Address,
+        _config: &GlobalConfig,
+        _validator_id: ValidatorId,
+    ) -> Result<(), failure::Error> {
+        Ok(())
+    }
+}
+
+/// Signer which uses the private keys stored in the node memory.
+#[derive(Debug, Clone)]
+pub struct LocalSigner {
+    private_keys: Arc<RwLock<HashMap<Address, Privkey>>>,
+    extended_private_key: Option<ExtendedPrivkey>,
+}
+
+impl LocalSigner {
+    /// Creates a new signer with the given private keys for each anchoring address.
+    pub fn new(private_keys: Arc<RwLock<HashMap<Address, Privkey>>>) -> Self {
+        LocalSigner {
+            private_keys,
+            extended_private_key: None,
+        }
+    }
+
+    /// Creates a new signer with the given private keys for each anchoring address, which
+    /// derives the private keys for the other addresses from the given extended private key
+    /// by the epoch of the anchoring configuration.
+    pub fn with_extended_key(
+        private_keys: Arc<RwLock<HashMap<Address, Privkey>>>,
+        extended_private_key: ExtendedPrivkey,
+    ) -> Self {
+        LocalSigner {
+            private_keys,
+            extended_private_key: Some(extended_private_key),
+        }
+    }
+}
+
+impl BtcSigner for LocalSigner {
+    fn sign_input(
+        &self,
+        address: &Address,
+        redeem_script: &RedeemScript,
+        transaction: &Transaction,
+        input: usize,
+        spent_transaction: &Transaction,
+    ) -> Result<InputSignature, failure::Error> {
+        let private_keys = self.private_keys.read().unwrap();
+        let privkey = private_keys
+            .get(address)
+            .ok_or_else(|| format_err!("Private key for the address {} is absent.", address))?;
+
+        let mut signer = p2wsh::InputSigner::new(redeem_script.clone());
+        let signature = signer.sign_input(
+            TxInRef::new(transaction.as_ref(), input),
+            spent_transaction.as_ref(),
+            privkey.0.secret_key(),
+        )?;
+        Ok(signature.into())
+    }
+
+    fn prepare_key(
+        &self,
+        address: &Address,
+        config: &GlobalConfig,
+        validator_id: ValidatorId,
+    ) -> Result<(), failure::Error> {
+        let extended_private_key = match self.extended_private_key {
+            Some(ref key) => key,
+            None => return Ok(()),
+        };
+        if self.private_keys.read().unwrap().contains_key(address) {
+            return Ok(());
+        }
+
+        let (public_key, privkey) = extended_private_key.derive_keypair(config.key_epoch)?;
+        ensure!(
+            config.public_keys.get(validator_id.0 as usize) == Some(&public_key),
+            "Public key {} derived for the epoch {} differs from the key of the validator {} \
+             in the anchoring configuration.",
+            public_key,
+            config.key_epoch,
+            validator_id
+        );
+        self.private_keys
+            .write()
+            .unwrap()
+            .insert(address.clone(), privkey);
+        Ok(())
+    }
+}
+
+/// Request to the remote signer.
+#[derive(Debug, Serialize)]
+struct SignRequest<'a> {
+    address: &'a Address,
+    redeem_script: String,
+    transaction: &'a Transaction,
+    input: usize,
+    spent_transaction: &'a Transaction,
+}
+
+/// Response of the remote signer.
+#[derive(Debug, Deserialize)]
+struct SignResponse {
+    input_signature: InputSignature,
+}
+
+/// Remote signer configuration.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RemoteSignerConfig {
+    /// URL of the signer service.
+    pub url: String,
+    /// Shared secret, which is sent in the `Authorization` header.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token: Option<String>,
+    /// Name of the environment variable which contains the shared secret.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub auth_token_env: Option<String>,
+    /// Path to the PEM-encoded certificate of the CA which is used to verify
+    /// the HTTPS endpoint in addition to the system ones.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ca_certificate: Option<PathBuf>,
+    /// Timeout of the single request in milliseconds.
+    #[serde(default = "default_request_timeout")]
+    pub request_timeout: u64,
+}
+
+fn default_request_timeout() -> u64 {
+    DEFAULT_REQUEST_TIMEOUT
+}
+
+impl RemoteSignerConfig {
+    /// Creates a configuration for the service located at the given url with the default
+    /// request timeout and without the shared secret.
+    pub fn new(url: impl Into<String>) -> Self {
+        RemoteSignerConfig {
+            url: url.into(),
+            auth_token: None,
+            auth_token_env: None,
+            ca_certificate: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+        }
+    }
+
+    /// Returns the shared secret. The value of the environment variable takes precedence
+    /// over the value from the configuration itself.
+    pub fn auth_token(&self) -> Result<Option<String>, failure::Error> {
+        match self.auth_token_env {
+            Some(ref name) => env::var(name)
+                .map(Some)
+                .map_err(|e| format_err!("Unable to read environment variable {}: {}", name, e)),
+            None => Ok(self.auth_token.clone()),
+        }
+    }
+}
+
+/// Signer which delegates signing to the external service over HTTP.
+#[derive(Debug)]
+pub struct RemoteSigner {
+    url: String,
+    auth_token: Option<String>,
+    client: reqwest::Client,
+}
+
+impl RemoteSigner {
+    /// Creates a new signer for the service with the given configuration.
+    pub fn new(config: &RemoteSignerConfig) -> Result<Self, failure::Error> {
+        let mut builder =
+            reqwest::Client::builder().timeout(Duration::from_millis(config.request_timeout));
+        if let Some(ref path) = config.ca_certificate {
+            let pem = fs::read(path).map_err(|e| {
+                format_err!("Unable to read CA certificate {}: {}", path.display(), e)
+            })?;
+            builder = builder.add_root_certificate(reqwest::Certificate::from_pem(&pem)?);
+        }
+        Ok(RemoteSigner {
+            url: config.url.trim_end_matches('/').to_owned(),
+            auth_token: config.auth_token()?,
+            client: builder.build()?,
+        })
+    }
+}
+
+impl BtcSigner for RemoteSigner {
+    fn sign_input(
+        &self,
+        address: &Address,
+        redeem_script: &RedeemScript,
+        transaction: &Transaction,
+        input: usize,
+        spent_transaction: &Transaction,
+    ) -> Result<InputSignature, failure::Error> {
+        let request = SignRequest {
+            address,
+            redeem_script: hex::encode(redeem_script.as_ref().as_bytes()),
+            transaction,
+            input,
+            spent_transaction,
+        };
+
+        let url = format!("{}/sign", self.url);
+        let mut request = self.client.post(&url).json(&request);
+        if let Some(ref token) = self.auth_token {
+            request = request.bearer_auth(token);
+        }
+        let mut response = request.send()?;
+        ensure!(
+            response.status().is_success(),
+            "Remote signer {} failed with {}: {}",
+            url,
+            response.status(),
+            response.text()?
+        );
+        let response: SignResponse = response.json()?;
+        Ok(response.input_signature)
+    }
+}
+
+/// Request to sign the inputs of the anchoring proposal by the validator.
+#[derive(Debug)]
+pub(crate) struct SigningRequest {
+    /// Identifier of the validator which signs the inputs.
+    pub validator_id: ValidatorId,
+    /// Anchoring address, which key is used for signing.
+    pub address: Address,
+    /// Actual anchoring configuration.
+    pub config: GlobalConfig,
+    /// Anchoring proposal to sign.
+    pub proposal: Transaction,
+    /// Indices of the inputs to sign along with the transactions spent by them.
+    pub inputs: Vec<(usize, Transaction)>,
+}
+
+impl SigningRequest {
+    /// Signs the requested inputs and passes the resulting `TxSignature` transactions
+    /// to the given function. Signing stops on the first error.
+    fn sign<F>(self, signer: &dyn BtcSigner, mut send: F) -> Result<(), failure::Error>
+    where
+        F: FnMut(TxSignature),
+    {
+        let SigningRequest {
+            validator_id,
+            address,
+            config,
+            proposal,
+            inputs,
+        } = self;
+        let redeem_script = config.redeem_script();
+        let pubkey = redeem_script.content().public_keys[validator_id.0 as usize];
+        let verifier = p2wsh::InputSigner::new(redeem_script.clone());
+        signer.prepare_key(&address, &config, validator_id)?;
+
+        for (index, spent_transaction) in inputs {
+            let signature = signer.sign_input(
+                &address,
+                &redeem_script,
+                &proposal,
+                index,
+                &spent_transaction,
+            )?;
+
+            verifier
+                .verify_input(
+                    TxInRef::new(proposal.as_ref(), index),
+                    spent_transaction.as_ref(),
+                    &pubkey,
+                    signature.as_ref(),
+                )
+                .map_err(|e| {
+                    format_err!(
+                        "Signer produced invalid signature for the input {} of {}: {}",
+                        index,
+                        proposal.id().to_hex(),
+                        e
+                    )
+                })?;
+
+            send(TxSignature {
+                validator: validator_id,
+                transaction: proposal.clone(),
+                input: index as u32,
+                input_signature: signature,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Service keys which are used to sign the `TxSignature` transactions.
+type ServiceKeypair = (PublicKey, SecretKey);
+
+#[derive(Debug)]
+enum SigningHandle {
+    Background {
+        requests: Mutex<SyncSender<(SigningRequest, ServiceKeypair)>>,
+        api_sender: Arc<RwLock<Option<ApiSender>>>,
+    },
+    Blocking(Arc<dyn BtcSigner>),
+}
+
+/// Signs the anchoring proposals and broadcasts the signatures, so the requests
+/// to the signer don't block the block processing.
+#[derive(Debug)]
+pub(crate) struct SigningWorker {
+    handle: SigningHandle,
+}
+
+impl SigningWorker {
+    /// Creates a signing worker for the given signer. In the background mode it also
+    /// spawns the corresponding thread, otherwise the inputs are signed directly in
+    /// the `after_commit` handler.
+    pub fn new(signer: Arc<dyn BtcSigner>, mode: SyncMode) -> Self {
+        let handle = match mode {
+            SyncMode::Background(_) => {
+                let api_sender = Arc::default();
+                let (sender, receiver) = mpsc::sync_channel(MAX_PENDING_REQUESTS);
+                let worker_api_sender = Arc::clone(&api_sender);
+                thread::Builder::new()
+                    .name("btc-anchoring-signer".to_owned())
+                    .spawn(move || run_signing(signer.as_ref(), &worker_api_sender, &receiver))
+                    .expect("Unable to spawn the signing thread");
+                SigningHandle::Background {
+                    requests: Mutex::new(sender),
+                    api_sender,
+                }
+            }
+            SyncMode::Blocking => SigningHandle::Blocking(signer),
+        };
+        Self { handle }
+    }
+
+    /// Sets the sender which is used to broadcast the signatures from the background thread.
+    pub fn set_api_sender(&self, sender: ApiSender) {
+        if let SigningHandle::Background { ref api_sender, .. } = self.handle {
+            *api_sender.write().unwrap() = Some(sender);
+        }
+    }
+
+    /// Signs the requested inputs and broadcasts the resulting signatures
+    /// on behalf of the node.
+    pub fn sign(
+        &self,
+        context: &ServiceContext,
+        request: SigningRequest,
+    ) -> Result<(), failure::Error> {
+        match self.handle {
+            SigningHandle::Background { ref requests, .. } => {
+                let keypair = (*context.public_key(), context.secret_key().clone());
+                match requests.lock().unwrap().try_send((request, keypair)) {
+                    Ok(()) => Ok(()),
+                    Err(TrySendError::Full(_)) => {
+                        trace!("Signing worker is busy, the request is skipped.");
+                        Ok(())
+                    }
+                    Err(TrySendError::Disconnected(_)) => {
+                        bail!("Signing thread has been unexpectedly stopped.")
+                    }
+                }
+            }
+            SigningHandle::Blocking(ref signer) => {
+                request.sign(signer.as_ref(), |tx| context.broadcast_transaction(tx))
+            }
+        }
+    }
+}
+
+fn run_signing(
+    signer: &dyn BtcSigner,
+    api_sender: &RwLock<Option<ApiSender>>,
+    requests: &Receiver<(SigningRequest, ServiceKeypair)>,
+) {
+    while let Ok((request, (public_key, secret_key))) = requests.recv() {
+        let result = request.sign(signer, |tx| {
+            let message =
+                Message::sign_transaction(tx, BTC_ANCHORING_SERVICE_ID, public_key, &secret_key);
+            let result = match *api_sender.read().unwrap() {
+                Some(ref sender) => sender.broadcast_transaction(message),
+                None => Err(format_err!("API sender is not set")),
+            };
+            if let Err(e) = result {
+                error!("Unable to broadcast anchoring signature: {}", e);
+            }
+        });
+        if let Err(e) = result {
+            error!("Unable to sign anchoring proposal: {}", e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::{self, Hash};
+    use exonum::helpers::{Height, ValidatorId};
+    use exonum::messages::Message;
+    use exonum::node::{ApiSender, ExternalMessage};
+
+    use bitcoin::network::constants::Network;
+    use btc_transaction_utils::multisig::RedeemScriptBuilder;
+    use btc_transaction_utils::{p2wsh, TxInRef};
+    use futures::{self, Stream};
+    use serde_json;
+
+    use std::sync::{mpsc, Arc, RwLock};
+
+    use super::{
+        run_signing, BtcSigner, LocalSigner, RemoteSigner, RemoteSignerConfig, SigningRequest,
+    };
+    use blockchain::transactions::TxSignature;
+    use btc::{gen_keypair, Address, BtcAnchoringTransactionBuilder, ExtendedPrivkey};
+    use config::GlobalConfig;
+    use test_helpers::http::{exchange, mock_server};
+    use test_helpers::testkit::create_fake_funding_transaction;
+    use BTC_ANCHORING_SERVICE_ID;
+
+    #[test]
+    fn test_local_and_remote_signers() {
+        let (public_key, privkey) = gen_keypair(Network::Testnet);
+        let redeem_script = RedeemScriptBuilder::with_public_keys(vec![public_key.0])
+            .quorum(1)
+            .to_script()
+            .unwrap();
+        let address = Address(p2wsh::address(&redeem_script, Network::Testnet));
+        let funding_tx = create_fake_funding_transaction(&address, 100_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&redeem_script);
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(1);
+        builder.payload(Height::zero(), Hash::zero());
+        let (transaction, _) = builder.create().unwrap();
+
+        let local_signer = LocalSigner::new(Arc::new(RwLock::new(
+            hashmap! { address.clone() => privkey },
+        )));
+        let signature = local_signer
+            .sign_input(&address, &redeem_script, &transaction, 0, &funding_tx)
+            .unwrap();
+        p2wsh::InputSigner::new(redeem_script.clone())
+            .verify_input(
+                TxInRef::new(transaction.as_ref(), 0),
+                funding_tx.as_ref(),
+                &public_key.0,
+                signature.as_ref(),
+            )
+            .unwrap();
+
+        let (url, server) = mock_server(vec![exchange(
+            "POST /sign HTTP/1.1",
+            200,
+            json!({ "input_signature": signature }).to_string(),
+        )
+        .header("Authorization: Bearer secret")]);
+        let config = RemoteSignerConfig {
+            auth_token: Some("secret".to_owned()),
+            ..RemoteSignerConfig::new(url)
+        };
+        let remote_signature = RemoteSigner::new(&config)
+            .unwrap()
+            .sign_input(&address, &redeem_script, &transaction, 0, &funding_tx)
+            .unwrap();
+        assert_eq!(remote_signature, signature);
+
+        let request: serde_json::Value = serde_json::from_str(&server.join().unwrap()[0]).unwrap();
+        assert_eq!(request["address"], json!(address));
+        assert_eq!(request["transaction"], json!(transaction));
+        assert_eq!(request["input"], json!(0));
+        assert_eq!(request["spent_transaction"], json!(funding_tx));
+
+        // Other addresses are unknown for the local signer.
+        let (other_public_key, _) = gen_keypair(Network::Testnet);
+        let other_address = Address(p2wsh::address(
+            &RedeemScriptBuilder::with_public_keys(vec![other_public_key.0])
+                .quorum(1)
+                .to_script()
+                .unwrap(),
+            Network::Testnet,
+        ));
+        assert!(local_signer
+            .sign_input(&other_address, &redeem_script, &transaction, 0, &funding_tx)
+            .is_err());
+    }
+
+    #[test]
+    fn test_signing_worker_broadcasts_signatures() {
+        let (public_key, privkey) = gen_keypair(Network::Testnet);
+        let config = GlobalConfig::with_public_keys(Network::Testnet, vec![public_key]).unwrap();
+        let address = config.anchoring_address();
+        let funding_tx = create_fake_funding_transaction(&address, 100_000);
+
+        let mut builder = BtcAnchoringTransactionBuilder::new(&config.redeem_script());
+        builder.additional_funds(funding_tx.clone()).unwrap();
+        builder.fee(1);
+        builder.payload(Height::zero(), Hash::zero());
+        let (proposal, _) = builder.create().unwrap();
+
+        let signer = LocalSigner::new(Arc::new(RwLock::new(
+            hashmap! { address.clone() => privkey },
+        )));
+        let (api_sender, messages) = futures::sync::mpsc::channel(1);
+        let api_sender = RwLock::new(Some(ApiSender::new(api_sender)));
+        let (requests, receiver) = mpsc::sync_channel(1);
+        let (service_public_key, service_secret_key) = crypto::gen_keypair();
+        requests
+            .send((
+                SigningRequest {
+                    validator_id: ValidatorId(0),
+                    address: address.clone(),
+                    config: config.clone(),
+                    proposal: proposal.clone(),
+                    inputs: vec![(0, funding_tx.clone())],
+                },
+                (service_public_key, service_secret_key.clone()),
+            ))
+            .unwrap();
+        drop(requests);
+        run_signing(&signer, &api_sender, &receiver);
+
+        let input_signature = signer
+            .sign_input(&address, &config.redeem_script(), &proposal, 0, &funding_tx)
+            .unwrap();
+        let expected = Message::sign_transaction(
+            TxSignature {
+                validator: ValidatorId(0),
+                transaction: proposal,
+                input: 0,
+                input_signature,
+            },
+            BTC_ANCHORING_SERVICE_ID,
+            service_public_key,
+            &service_secret_key,
+        );
+        drop(api_sender);
+        let messages = messages.wait().collect::<Result<Vec<_>, _>>().unwrap();
+        assert_eq!(messages.len(), 1);
+        assert_matches!(messages[0], ExternalMessage::Transaction(ref tx) if *tx == expected);
+    }
+
+    #[test]
+    fn test_local_signer_derived_keys() {
+        let extended_private_key = ExtendedPrivkey::generate(Network::Testnet);
+        let (other_public_key, _) = gen_keypair(Network::Testnet);
+
+        let mut config = GlobalConfig::with_public_keys(
+            Network::Testnet,
+            vec![
+                extended_private_key.derive_keypair(0).unwrap().0,
+                other_public_key,
+            ],
+        )
+        .unwrap();
+        let signer = LocalSigner::with_extended_key(
+            Arc::new(RwLock::new(Default::default())),
+            extended_private_key.clone(),
+        );
+        let address = config.anchoring_address();
+        signer
+            .prepare_key(&address, &config, ValidatorId(0))
+            .unwrap();
+        assert_eq!(
+            signer.private_keys.read().unwrap()[&address],
+            extended_private_key.derive_keypair(0).unwrap().1
+        );
+
+        // The following configuration uses the key for the next epoch.
+        config.key_epoch = 1;
+        config.public_keys[0] = extended_private_key.derive_keypair(1).unwrap().0;
+        let following_address = config.anchoring_address();
+        signer
+            .prepare_key(&following_address, &config, ValidatorId(0))
+            .unwrap();
+        assert_eq!(
+            signer.private_keys.read().unwrap()[&following_address],
+            extended_private_key.derive_keypair(1).unwrap().1
+        );
+
+        // The derived key should be the key of the given validator.
+        config.key_epoch = 2;
+        config.public_keys = vec![
+            other_public_key,
+            extended_private_key.derive_keypair(2).unwrap().0,
+        ];
+        assert!(signer
+            .prepare_key(&config.anchoring_address(), &config, ValidatorId(0))
+            .is_err());
+        assert!(signer
+            .prepare_key(&config.anchoring_address(), &config, ValidatorId(1))
+            .is_ok());
+
+        // Epochs out of the hardened indexes range are rejected.
+        config.key_epoch = 1 << 31;
+        config.public_keys = vec![other_public_key];
+        assert!(signer
+            .prepare_key(&config.anchoring_address(), &config, ValidatorId(1))
+            .is_err());
+    }
+}
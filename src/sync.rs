@@ -0,0 +1,688 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Synchronization of the anchoring chain with the Bitcoin network.
+//!
+//! Requests to the Bitcoin relay may take a long time, thus by default they are
+//! performed in a separate thread, so the block processing does not depend on
+//! the relay latency.
+
+use exonum::blockchain::ServiceContext;
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+
+use bitcoin::util::address::Address;
+use failure;
+
+use std::cmp;
+use std::collections::{HashMap, HashSet};
+use std::sync::mpsc::{self, Receiver, SyncSender, TrySendError};
+use std::sync::{Arc, Mutex, RwLock};
+use std::thread;
+use std::time::Duration;
+
+use audit::{AuditLog, Discrepancy, AUDIT_CONFIRMATIONS};
+use blockchain::{transactions::TxFeeBump, BtcAnchoringSchema};
+use btc::{MerkleProof, Transaction};
+use config::FeeBumpConfig;
+use handler::SyncWithBtcRelayTask;
+use rpc::{BitcoinRpcConfig, BtcRelay, TransactionInfo};
+
+/// Shared state of the synchronization with the Bitcoin network.
+pub(crate) type SharedSyncState = Arc<RwLock<SyncState>>;
+/// Shared cache of the merkle proofs of the anchoring transactions.
+pub(crate) type SharedProofCache = Arc<RwLock<ProofCache>>;
+
+/// Maximum number of merkle proofs fetched during the single synchronization.
+const MAX_PROOF_REQUESTS: usize = 16;
+/// Maximum number of notifications waiting for the synchronization worker. Further
+/// notifications are dropped, since the following one contains all the missed changes.
+const MAX_PENDING_NOTIFICATIONS: usize = 4;
+
+/// Parameters of requests to the Bitcoin relay performed by the background worker.
+///
+/// The duration of the single request is limited by the timeout of the HTTP client
+/// used by the relay.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyncConfig {
+    /// Number of retries of the failed request.
+    pub retries: u32,
+    /// Delay between retries of the failed request.
+    pub retry_delay: Duration,
+}
+
+impl Default for SyncConfig {
+    fn default() -> Self {
+        Self {
+            retries: 3,
+            retry_delay: Duration::from_secs(1),
+        }
+    }
+}
+
+/// The way the anchoring chain is synchronized with the Bitcoin network. The anchoring
+/// proposals are signed in the same way.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SyncMode {
+    /// Requests to the Bitcoin relay are performed in the separate thread
+    /// with the given timeouts and retries. Proposals are signed in another thread.
+    Background(SyncConfig),
+    /// Requests to the Bitcoin relay and to the signer are performed directly in
+    /// the `after_commit` handler without timeouts and retries. Useful for testing.
+    Blocking,
+}
+
+impl Default for SyncMode {
+    fn default() -> Self {
+        SyncMode::Background(SyncConfig::default())
+    }
+}
+
+/// Current state of the synchronization with the Bitcoin network.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SyncState {
+    /// Height of the latest completed synchronization.
+    pub latest_height: Option<Height>,
+    /// Number of anchoring transactions received by the synchronization worker.
+    pub known_transactions: u64,
+    /// Index of the first anchoring transaction which has not been found in the
+    /// Bitcoin network during the latest synchronization.
+    pub first_uncommitted: Option<u64>,
+    /// Number of confirmations of the latest anchoring transaction known
+    /// after the latest synchronization.
+    pub latest_confirmations: Option<u64>,
+    /// Error occurred during the latest synchronization.
+    pub last_error: Option<String>,
+    /// Identifier of the latest anchoring transaction which has not been confirmed
+    /// for too long, so validators vote to replace it.
+    pub stuck_transaction: Option<Hash>,
+    /// Identifier of the latest anchoring transaction if it has been confirmed
+    /// according to the latest synchronization.
+    #[serde(default)]
+    pub confirmed_transaction: Option<Hash>,
+}
+
+/// Merkle proofs of the anchoring transactions inclusion in the Bitcoin blockchain.
+///
+/// Proofs are fetched by the synchronization worker on demand, so the API handlers
+/// never wait for the Bitcoin relay.
+#[derive(Debug, Default)]
+pub(crate) struct ProofCache {
+    proofs: HashMap<Hash, MerkleProof>,
+    requested: HashSet<Hash>,
+}
+
+impl ProofCache {
+    /// Returns the cached proof for the transaction with the given identifier.
+    /// If the proof is absent, it is fetched during the next synchronization.
+    pub fn get_or_request(&mut self, txid: &Hash) -> Option<MerkleProof> {
+        let proof = self.proofs.get(txid).cloned();
+        if proof.is_none() {
+            self.requested.insert(*txid);
+        }
+        proof
+    }
+
+    /// Takes the limited number of the requested transaction identifiers.
+    fn take_requested(&mut self) -> Vec<Hash> {
+        let txids = self
+            .requested
+            .iter()
+            .take(MAX_PROOF_REQUESTS)
+            .cloned()
+            .collect::<Vec<_>>();
+        for txid in &txids {
+            self.requested.remove(txid);
+        }
+        txids
+    }
+}
+
+/// Notification about the new anchoring transactions at the given height.
+#[derive(Debug)]
+struct SyncNotification {
+    height: Height,
+    first_index: u64,
+    transactions: Vec<Transaction>,
+    fee_bump: Option<FeeBumpConfig>,
+    audit: bool,
+}
+
+/// Owns the local copy of the anchoring chain and performs synchronization.
+#[derive(Debug)]
+struct SyncProcessor {
+    relay: Arc<dyn BtcRelay>,
+    transactions: Vec<Transaction>,
+    fee_bump: Option<FeeBumpConfig>,
+    audit: bool,
+    unconfirmed_since: Option<(Hash, u64)>,
+    state: SharedSyncState,
+    proofs: SharedProofCache,
+    audit_log: AuditLog,
+}
+
+impl SyncProcessor {
+    fn new(
+        relay: Arc<dyn BtcRelay>,
+        state: SharedSyncState,
+        proofs: SharedProofCache,
+        audit_log: AuditLog,
+    ) -> Self {
+        Self {
+            relay,
+            transactions: Vec::new(),
+            fee_bump: None,
+            audit: false,
+            unconfirmed_since: None,
+            state,
+            proofs,
+            audit_log,
+        }
+    }
+
+    fn apply(&mut self, notification: SyncNotification) -> Height {
+        self.transactions
+            .truncate(notification.first_index as usize);
+        self.transactions.extend(notification.transactions);
+        self.fee_bump = notification.fee_bump;
+        self.audit = notification.audit;
+        self.state.write().unwrap().known_transactions = self.transactions.len() as u64;
+        notification.height
+    }
+
+    fn sync(&mut self, height: Height) {
+        let result = {
+            let task = SyncWithBtcRelayTask::new(self.relay.as_ref(), &self.transactions);
+            let fee_bump = self.fee_bump;
+            task.run().and_then(|report| {
+                // Additional request is only needed to find out the stuck transactions.
+                let confirmations = match report.latest_confirmations {
+                    None if fee_bump.is_some() => task.latest_confirmations()?,
+                    confirmations => confirmations,
+                };
+                Ok((report, confirmations))
+            })
+        };
+        let result = result.map(|(report, confirmations)| {
+            let first_uncommitted = report.first_uncommitted;
+            let stuck_transaction =
+                self.stuck_transaction(confirmations, report.prev_confirmations);
+            (first_uncommitted, confirmations, stuck_transaction)
+        });
+
+        let latest_txid = self.transactions.last().map(|tx| tx.id());
+        let mut state = self.state.write().unwrap();
+        state.latest_height = Some(height);
+        match result {
+            Ok((first_uncommitted, confirmations, stuck_transaction)) => {
+                state.first_uncommitted = first_uncommitted;
+                state.latest_confirmations = confirmations;
+                state.stuck_transaction = stuck_transaction;
+                state.confirmed_transaction =
+                    latest_txid.filter(|_| confirmations.map_or(false, |c| c > 0));
+                state.last_error = None;
+            }
+            Err(e) => {
+                error!("{}", e);
+                state.last_error = Some(e.to_string());
+            }
+        }
+        drop(state);
+        if self.audit {
+            if let Err(e) = self.audit() {
+                error!("Unable to audit the anchoring chain: {}", e);
+            }
+        }
+        self.fetch_proofs();
+    }
+
+    /// Checks that the anchoring transactions are confirmed in the Bitcoin blockchain
+    /// in the same order as they follow in the anchoring chain.
+    fn audit(&self) -> Result<(), failure::Error> {
+        let mut confirmed = self.audit_log.read().unwrap().confirmed_transactions;
+        let mut discrepancy = None;
+        let mut result = Ok(());
+        for (index, tx) in self
+            .transactions
+            .iter()
+            .enumerate()
+            .skip(confirmed as usize)
+        {
+            let txid = tx.id();
+            match self.relay.transaction_info(&txid) {
+                Ok(Some(ref info)) if info.confirmations < AUDIT_CONFIRMATIONS => break,
+                Ok(Some(_)) => confirmed += 1,
+                Ok(None) => {
+                    if index + 1 < self.transactions.len() {
+                        let index = index as u64;
+                        discrepancy = Some(Discrepancy::MissingInBitcoin { index, txid });
+                    }
+                    break;
+                }
+                Err(e) => {
+                    result = Err(e);
+                    break;
+                }
+            }
+        }
+
+        let mut report = self.audit_log.write().unwrap();
+        report.confirmed_transactions = confirmed;
+        if let Some(discrepancy) = discrepancy {
+            report.record(discrepancy);
+        }
+        result
+    }
+
+    /// Fetches the merkle proofs requested since the previous synchronization. Proofs of
+    /// the unconfirmed transactions are requested again on the next API call.
+    fn fetch_proofs(&mut self) {
+        let txids = self.proofs.write().unwrap().take_requested();
+        for txid in txids {
+            match self.relay.merkle_proof(&txid) {
+                Ok(Some(proof)) => {
+                    self.proofs.write().unwrap().proofs.insert(txid, proof);
+                }
+                Ok(None) => {}
+                Err(e) => warn!(
+                    "Unable to get merkle proof of the transaction {}: {}",
+                    txid.to_hex(),
+                    e
+                ),
+            }
+        }
+    }
+
+    /// Tracks how long the latest anchoring transaction stays unconfirmed and returns
+    /// its identifier if it should be replaced.
+    ///
+    /// The time is measured in the Bitcoin blocks by the confirmations of the transaction
+    /// spent by the latest anchoring transaction.
+    fn stuck_transaction(
+        &mut self,
+        confirmations: Option<u64>,
+        prev_confirmations: Option<u64>,
+    ) -> Option<Hash> {
+        let (fee_bump, txid, depth) =
+            match (self.fee_bump, self.transactions.last(), prev_confirmations) {
+                (Some(fee_bump), Some(tx), Some(depth)) if confirmations == Some(0) => {
+                    (fee_bump, tx.id(), depth)
+                }
+                _ => {
+                    self.unconfirmed_since = None;
+                    return None;
+                }
+            };
+
+        let since = match self.unconfirmed_since {
+            Some((id, since)) if id == txid => since,
+            _ => depth,
+        };
+        self.unconfirmed_since = Some((txid, since));
+        // The depth may decrease in case of the Bitcoin blockchain reorganization.
+        if depth.saturating_sub(since) >= fee_bump.stuck_after {
+            Some(txid)
+        } else {
+            None
+        }
+    }
+
+    fn run(mut self, receiver: Receiver<SyncNotification>) {
+        while let Ok(notification) = receiver.recv() {
+            let mut height = self.apply(notification);
+            // Only the latest notification is relevant for synchronization.
+            while let Ok(notification) = receiver.try_recv() {
+                height = self.apply(notification);
+            }
+            self.sync(height);
+        }
+    }
+}
+
+#[derive(Debug)]
+enum SyncHandle {
+    Background(Mutex<SyncSender<SyncNotification>>),
+    Blocking(Mutex<SyncProcessor>),
+}
+
+/// Pushes uncommitted anchoring transactions to the Bitcoin network.
+#[derive(Debug)]
+pub(crate) struct SyncWorker {
+    state: SharedSyncState,
+    proofs: SharedProofCache,
+    handle: SyncHandle,
+}
+
+impl SyncWorker {
+    /// Creates a synchronization worker for the given relay. In the background mode
+    /// it also spawns the corresponding thread. On the auditor nodes the worker also
+    /// checks the anchoring chain against the Bitcoin blockchain and writes the found
+    /// discrepancies to the given audit log.
+    pub fn new(relay: Arc<dyn BtcRelay>, mode: SyncMode, audit_log: AuditLog) -> Self {
+        let state = SharedSyncState::default();
+        let proofs = SharedProofCache::default();
+        let handle = match mode {
+            SyncMode::Background(config) => {
+                let relay = Arc::new(ReliableRelay {
+                    inner: relay,
+                    config,
+                });
+                let processor =
+                    SyncProcessor::new(relay, Arc::clone(&state), Arc::clone(&proofs), audit_log);
+                let (sender, receiver) = mpsc::sync_channel(MAX_PENDING_NOTIFICATIONS);
+                thread::Builder::new()
+                    .name("btc-anchoring-sync".to_owned())
+                    .spawn(move || processor.run(receiver))
+                    .expect("Unable to spawn the synchronization thread");
+                SyncHandle::Background(Mutex::new(sender))
+            }
+            SyncMode::Blocking => {
+                let processor =
+                    SyncProcessor::new(relay, Arc::clone(&state), Arc::clone(&proofs), audit_log);
+                SyncHandle::Blocking(Mutex::new(processor))
+            }
+        };
+        Self {
+            state,
+            proofs,
+            handle,
+        }
+    }
+
+    /// Returns the shared synchronization state.
+    pub fn state(&self) -> SharedSyncState {
+        Arc::clone(&self.state)
+    }
+
+    /// Returns the shared cache of the merkle proofs fetched by the worker.
+    pub fn proofs(&self) -> SharedProofCache {
+        Arc::clone(&self.proofs)
+    }
+
+    /// Notifies the worker about the committed block. The synchronization is performed
+    /// every half of the anchoring interval.
+    ///
+    /// Validators also vote for the replacement of the stuck anchoring transaction
+    /// found during the previous synchronization and withdraw their votes if the
+    /// transaction has been confirmed before the replacement.
+    pub fn notify(&self, context: &ServiceContext) {
+        let schema = BtcAnchoringSchema::new(context.snapshot());
+        let config = schema.actual_configuration();
+        let sync_interval = cmp::max(1, config.anchoring_interval / 2);
+        if context.height().0 % sync_interval != 0 {
+            return;
+        }
+
+        let anchoring_txs = schema.anchoring_transactions_chain();
+        let (known_transactions, stuck_transaction, confirmed_transaction) = {
+            let state = self.state.read().unwrap();
+            (
+                state.known_transactions,
+                state.stuck_transaction,
+                state.confirmed_transaction,
+            )
+        };
+
+        if let (Some(validator_id), Some(latest_tx)) =
+            (context.validator_id(), anchoring_txs.last())
+        {
+            let txid = latest_tx.id();
+            let has_voted = schema
+                .fee_bump_votes()
+                .get(&txid)
+                .map_or(false, |votes| votes.contains(validator_id));
+            let cancel = if confirmed_transaction == Some(txid) {
+                // The confirmed transaction should not be replaced, otherwise the anchoring
+                // chain would conflict with the Bitcoin blockchain.
+                Some(true).filter(|_| has_voted)
+            } else if stuck_transaction == Some(txid) && latest_tx.signals_rbf() && !has_voted {
+                Some(false)
+            } else {
+                None
+            };
+            if let Some(cancel) = cancel {
+                context.broadcast_transaction(TxFeeBump {
+                    validator: validator_id,
+                    txid,
+                    cancel,
+                });
+            }
+        }
+
+        // Sends only the anchoring transactions which are unknown for the worker and
+        // the latest known one, since it may have been replaced.
+        let first_index = cmp::min(known_transactions.saturating_sub(1), anchoring_txs.len());
+        let notification = SyncNotification {
+            height: context.height(),
+            first_index,
+            transactions: anchoring_txs.iter_from(first_index).collect(),
+            fee_bump: config.active_fee_bump(),
+            audit: context.validator_id().is_none(),
+        };
+
+        match self.handle {
+            SyncHandle::Background(ref sender) => {
+                match sender.lock().unwrap().try_send(notification) {
+                    Ok(()) => {}
+                    // The worker applies the changes from the following notification, since it
+                    // starts from the latest transaction known to the worker.
+                    Err(TrySendError::Full(_)) => {
+                        trace!("Synchronization worker is busy, the notification is skipped.")
+                    }
+                    Err(TrySendError::Disconnected(_)) => {
+                        error!("Synchronization thread has been unexpectedly stopped.")
+                    }
+                }
+            }
+            SyncHandle::Blocking(ref processor) => {
+                let mut processor = processor.lock().unwrap();
+                let height = processor.apply(notification);
+                processor.sync(height);
+            }
+        }
+    }
+}
+
+/// Bitcoin relay wrapper which performs requests with the retries.
+#[derive(Debug)]
+struct ReliableRelay {
+    inner: Arc<dyn BtcRelay>,
+    config: SyncConfig,
+}
+
+impl ReliableRelay {
+    fn request<T, F>(&self, f: F) -> Result<T, failure::Error>
+    where
+        F: Fn(&dyn BtcRelay) -> Result<T, failure::Error>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f(self.inner.as_ref()) {
+                Ok(value) => return Ok(value),
+                Err(e) => {
+                    if attempt >= self.config.retries {
+                        return Err(e);
+                    }
+                    warn!("{}, retrying", e);
+                    attempt += 1;
+                    thread::sleep(self.config.retry_delay);
+                }
+            }
+        }
+    }
+}
+
+impl BtcRelay for ReliableRelay {
+    fn send_to_address(
+        &self,
+        addr: &Address,
+        satoshis: u64,
+    ) -> Result<Transaction, failure::Error> {
+        self.inner.send_to_address(addr, satoshis)
+    }
+
+    fn transaction_info(&self, id: &Hash) -> Result<Option<TransactionInfo>, failure::Error> {
+        self.request(|relay| relay.transaction_info(id))
+    }
+
+    fn merkle_proof(&self, id: &Hash) -> Result<Option<MerkleProof>, failure::Error> {
+        self.request(|relay| relay.merkle_proof(id))
+    }
+
+    fn send_transaction(&self, transaction: &Transaction) -> Result<Hash, failure::Error> {
+        self.request(|relay| relay.send_transaction(transaction))
+    }
+
+    fn watch_address(&self, addr: &Address, rescan: bool) -> Result<(), failure::Error> {
+        self.inner.watch_address(addr, rescan)
+    }
+
+    fn config(&self) -> BitcoinRpcConfig {
+        self.inner.config()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::hash;
+
+    use bitcoin::blockdata::block::BlockHeader;
+    use bitcoin::util::address::Address;
+    use bitcoin::util::hash::Sha256dHash;
+
+    use std::str::FromStr;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    use super::{ReliableRelay, SharedProofCache, SharedSyncState, SyncConfig, SyncProcessor};
+    use audit::AuditLog;
+    use btc::MerkleProof;
+    use config::FeeBumpConfig;
+    use rpc::BtcRelay;
+    use test_helpers::rpc::{FakeBtcRelay, FakeRelayRequest, FakeRelayResponse};
+    use test_helpers::testkit::create_fake_funding_transaction;
+
+    #[test]
+    fn test_reliable_relay_retries() {
+        let id = hash(&[1, 2, 3]);
+        let fake_relay = FakeBtcRelay::default();
+        fake_relay.requests.expect(vec![
+            (
+                FakeRelayRequest::TransactionInfo { id },
+                FakeRelayResponse::TransactionInfo(Err(format_err!("Connection refused"))),
+            ),
+            (
+                FakeRelayRequest::TransactionInfo { id },
+                FakeRelayResponse::TransactionInfo(Ok(None)),
+            ),
+            (
+                FakeRelayRequest::TransactionInfo { id },
+                FakeRelayResponse::TransactionInfo(Err(format_err!("Connection refused"))),
+            ),
+            (
+                FakeRelayRequest::TransactionInfo { id },
+                FakeRelayResponse::TransactionInfo(Err(format_err!("Connection refused"))),
+            ),
+        ]);
+
+        let relay = ReliableRelay {
+            inner: Arc::new(fake_relay),
+            config: SyncConfig {
+                retries: 1,
+                retry_delay: Duration::from_millis(0),
+            },
+        };
+        assert_eq!(relay.transaction_info(&id).unwrap(), None);
+        // All attempts are exhausted.
+        assert!(relay.transaction_info(&id).is_err());
+    }
+
+    #[test]
+    fn test_proof_cache_fetches_requested() {
+        let (confirmed, unconfirmed) = (hash(&[1]), hash(&[2]));
+        let fake_relay = FakeBtcRelay::default();
+        let requests = fake_relay.requests.clone();
+        let proofs = SharedProofCache::default();
+        let mut processor = SyncProcessor::new(
+            Arc::new(fake_relay),
+            SharedSyncState::default(),
+            Arc::clone(&proofs),
+            AuditLog::default(),
+        );
+        let proof = MerkleProof {
+            block_header: BlockHeader {
+                version: 1,
+                prev_blockhash: Sha256dHash::default(),
+                merkle_root: Sha256dHash::default(),
+                time: 0,
+                bits: 0x207f_ffff,
+                nonce: 0,
+            },
+            index: 0,
+            branch: Vec::new(),
+        };
+
+        // Nothing is fetched until the API requests the proofs.
+        processor.fetch_proofs();
+        assert_eq!(proofs.write().unwrap().get_or_request(&confirmed), None);
+        requests.expect(vec![(
+            FakeRelayRequest::MerkleProof { id: confirmed },
+            FakeRelayResponse::MerkleProof(Ok(Some(proof.clone()))),
+        )]);
+        processor.fetch_proofs();
+        assert_eq!(
+            proofs.write().unwrap().get_or_request(&confirmed),
+            Some(proof)
+        );
+
+        // The proof of the unconfirmed transaction is requested again on the next call.
+        assert_eq!(proofs.write().unwrap().get_or_request(&unconfirmed), None);
+        requests.expect(vec![(
+            FakeRelayRequest::MerkleProof { id: unconfirmed },
+            FakeRelayResponse::MerkleProof(Ok(None)),
+        )]);
+        processor.fetch_proofs();
+        assert!(proofs.read().unwrap().requested.is_empty());
+        assert_eq!(proofs.write().unwrap().get_or_request(&unconfirmed), None);
+        assert!(proofs.read().unwrap().requested.contains(&unconfirmed));
+    }
+
+    #[test]
+    fn test_stuck_transaction_counts_bitcoin_blocks() {
+        let address =
+            Address::from_str("tb1q8270svuaqety59gegtp4ujjeam39s83csz7whp9ryn3zxlcee66setkyq0")
+                .unwrap();
+        let tx = create_fake_funding_transaction(&address, 1000);
+        let mut processor = SyncProcessor::new(
+            Arc::new(FakeBtcRelay::default()),
+            SharedSyncState::default(),
+            SharedProofCache::default(),
+            AuditLog::default(),
+        );
+        processor.transactions.push(tx.clone());
+        processor.fee_bump = Some(FeeBumpConfig {
+            stuck_after: 2,
+            fee_increment: 1,
+        });
+
+        // The time is measured by the confirmations of the spent transaction.
+        assert_eq!(processor.stuck_transaction(Some(0), Some(5)), None);
+        assert_eq!(processor.stuck_transaction(Some(0), Some(5)), None);
+        assert_eq!(processor.stuck_transaction(Some(0), Some(6)), None);
+        assert_eq!(processor.stuck_transaction(Some(0), Some(7)), Some(tx.id()));
+        // Confirmed transaction is not stuck anymore.
+        assert_eq!(processor.stuck_transaction(Some(1), Some(8)), None);
+        assert_eq!(processor.stuck_transaction(Some(0), Some(8)), None);
+    }
+}
@@ -0,0 +1,99 @@
+// Copyright 2018 The Exonum Team
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Mock HTTP server for testing the clients of the external services.
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::thread::{self, JoinHandle};
+
+/// Expected request line and the corresponding response of the mock server.
+#[derive(Debug)]
+pub struct MockExchange {
+    request: String,
+    headers: Vec<String>,
+    status: u16,
+    body: String,
+}
+
+impl MockExchange {
+    /// Requires the request to contain the given header, e.g. `Authorization: Bearer token`.
+    pub fn header(mut self, header: impl Into<String>) -> Self {
+        self.headers.push(header.into().to_lowercase());
+        self
+    }
+}
+
+/// Creates the exchange with the given request line, e.g. `GET /path HTTP/1.1`,
+/// and the response status and body.
+pub fn exchange(request: impl Into<String>, status: u16, body: impl Into<String>) -> MockExchange {
+    MockExchange {
+        request: request.into(),
+        headers: Vec::new(),
+        status,
+        body: body.into(),
+    }
+}
+
+/// Starts HTTP server which serves the given exchanges in order, one per connection.
+/// Returns the server URL and the handle which yields bodies of the received requests.
+///
+/// The server thread panics if the received request line differs from the expected one
+/// or the expected headers are absent, so the handle should be joined at the end of the test.
+pub fn mock_server(exchanges: Vec<MockExchange>) -> (String, JoinHandle<Vec<String>>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let url = format!("http://{}", listener.local_addr().unwrap());
+    let handle = thread::spawn(move || {
+        let mut bodies = Vec::new();
+        for exchange in exchanges {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+
+            let mut request_line = String::new();
+            reader.read_line(&mut request_line).unwrap();
+            assert_eq!(request_line.trim(), exchange.request);
+
+            let mut content_length = 0;
+            let mut headers = Vec::new();
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).unwrap();
+                let header = header.trim().to_lowercase();
+                if header.is_empty() {
+                    break;
+                }
+                if header.starts_with("content-length:") {
+                    content_length = header["content-length:".len()..].trim().parse().unwrap();
+                }
+                headers.push(header);
+            }
+            for header in &exchange.headers {
+                assert!(headers.contains(header), "Header {} is absent", header);
+            }
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body).unwrap();
+            bodies.push(String::from_utf8(body).unwrap());
+
+            let response = format!(
+                "HTTP/1.1 {} Mock\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                exchange.status,
+                exchange.body.len(),
+                exchange.body
+            );
+            reader.get_mut().write_all(response.as_bytes()).unwrap();
+        }
+        bodies
+    });
+    (url, handle)
+}
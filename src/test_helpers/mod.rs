@@ -14,6 +14,7 @@
 
 //! Set of helpers for btc anchoring testing.
 
+pub mod http;
 #[macro_use]
 pub mod rpc;
 pub mod testkit;
@@ -43,6 +43,11 @@ pub enum FakeRelayRequest {
         /// Transaction id.
         id: Hash,
     },
+    /// Transaction inclusion proof request.
+    MerkleProof {
+        /// Transaction id.
+        id: Hash,
+    },
     /// Send transaction to bitcoin mempool request.
     SendTransaction {
         /// Raw bitcoin transaction
@@ -64,6 +69,8 @@ pub enum FakeRelayResponse {
     SendToAddress(Result<btc::Transaction, failure::Error>),
     /// Response to the transaction info request.
     TransactionInfo(Result<Option<BtcTransactionInfo>, failure::Error>),
+    /// Response to the transaction inclusion proof request.
+    MerkleProof(Result<Option<btc::MerkleProof>, failure::Error>),
     /// Response to the send transaction request.
     SendTransaction(Result<Hash, failure::Error>),
     /// Response to the watch address request.
@@ -143,6 +150,16 @@ impl BtcRelay for FakeBtcRelay {
         }
     }
 
+    fn merkle_proof(&self, id: &Hash) -> Result<Option<btc::MerkleProof>, failure::Error> {
+        if let FakeRelayResponse::MerkleProof(r) =
+            self.request(&FakeRelayRequest::MerkleProof { id: *id })
+        {
+            r
+        } else {
+            panic!(UNEXPECTED_RESPONSE);
+        }
+    }
+
     fn send_transaction(&self, transaction: &btc::Transaction) -> Result<Hash, failure::Error> {
         if let FakeRelayResponse::SendTransaction(r) =
             self.request(&FakeRelayRequest::SendTransaction {
@@ -35,12 +35,21 @@ use std::str::FromStr;
 use std::sync::{Arc, RwLock};
 
 use {
-    api::{BlockHeaderProof, FindTransactionQuery, HeightQuery, PublicApi, TransactionProof},
-    blockchain::{transactions::TxSignature, BtcAnchoringSchema, BtcAnchoringState},
+    api::{
+        AnchoredBlockProof, AnchoringStatus, BatchProof, BlockHeaderProof, FindTransactionQuery,
+        HeightQuery, PendingProposal, PublicApi, TransactionProof, TransactionsPage,
+        TransactionsQuery,
+    },
+    audit::AuditReport,
+    blockchain::{
+        transactions::{TxFeeBump, TxFundingProposal, TxSignature},
+        BtcAnchoringSchema, BtcAnchoringState,
+    },
     btc,
     config::{GlobalConfig, LocalConfig},
     rpc::BtcRelay,
     service::KeyPool,
+    sync::{SyncMode, SyncState},
     test_helpers::rpc::*,
     BtcAnchoringService, BTC_ANCHORING_SERVICE_ID, BTC_ANCHORING_SERVICE_NAME,
 };
@@ -89,7 +98,7 @@ pub fn gen_anchoring_config<R: Rng>(
     let mut global = GlobalConfig {
         network,
         public_keys,
-        funding_transaction: None,
+        funding_transactions: Vec::new(),
         anchoring_interval,
         ..GlobalConfig::default()
     };
@@ -99,7 +108,12 @@ pub fn gen_anchoring_config<R: Rng>(
         .iter()
         .map(|sk| LocalConfig {
             rpc: rpc.map(BtcRelay::config),
+            backup_rpc: Vec::new(),
             private_keys: hashmap! { address.clone() => sk.clone() },
+            encrypted_private_keys: Vec::new(),
+            extended_private_key: None,
+            encrypted_extended_private_key: None,
+            remote_signer: None,
         })
         .collect();
 
@@ -113,7 +127,7 @@ pub fn gen_anchoring_config<R: Rng>(
         )
     };
 
-    global.funding_transaction = Some(tx);
+    global.funding_transactions = vec![tx];
     (global, local_cfgs)
 }
 
@@ -145,6 +159,7 @@ impl DerefMut for AnchoringTestKit {
 impl AnchoringTestKit {
     /// Creates a new testkit instance with the extensions for anchoring.
     fn new<R: Rng>(
+        builder: TestKitBuilder,
         rpc: Option<Box<dyn BtcRelay>>,
         validators_num: u16,
         total_funds: u64,
@@ -164,9 +179,15 @@ impl AnchoringTestKit {
 
         let local = locals[0].clone();
         let private_keys = Arc::new(RwLock::new(local.private_keys));
-        let service = BtcAnchoringService::new(global.clone(), Arc::clone(&private_keys), rpc);
+        // Requests to the fake relay should be performed in the strict order.
+        let service = BtcAnchoringService::new(
+            global.clone(),
+            Arc::clone(&private_keys),
+            rpc,
+            SyncMode::Blocking,
+        );
 
-        let testkit = TestKitBuilder::validator()
+        let testkit = builder
             .with_service(service)
             .with_validators(validators_num)
             .with_logger()
@@ -183,6 +204,16 @@ impl AnchoringTestKit {
     /// Creates an anchoring testkit for the four validators with the fake rpc client
     /// under the hood.
     pub fn new_with_fake_rpc(anchoring_interval: u64) -> Self {
+        Self::with_fake_rpc(TestKitBuilder::validator(), anchoring_interval)
+    }
+
+    /// Creates an anchoring testkit for the four validators with the fake rpc client
+    /// under the hood which operates from the perspective of the auditor node.
+    pub fn new_auditor_with_fake_rpc(anchoring_interval: u64) -> Self {
+        Self::with_fake_rpc(TestKitBuilder::auditor(), anchoring_interval)
+    }
+
+    fn with_fake_rpc(builder: TestKitBuilder, anchoring_interval: u64) -> Self {
         let validators_num = 4;
         let total_funds = 7_000;
 
@@ -222,6 +253,7 @@ impl AnchoringTestKit {
         ]);
 
         Self::new(
+            builder,
             Some(Box::from(fake_relay)),
             validators_num,
             total_funds,
@@ -237,6 +269,28 @@ impl AnchoringTestKit {
         let rng: StdRng = SeedableRng::from_seed(seed);
 
         Self::new(
+            TestKitBuilder::validator(),
+            None,
+            validators_num,
+            total_funds,
+            anchoring_interval,
+            rng,
+            None,
+        )
+    }
+
+    /// Creates an anchoring testkit without rpc client which operates
+    /// from the perspective of the auditor node.
+    pub fn new_auditor_without_rpc(
+        validators_num: u16,
+        total_funds: u64,
+        anchoring_interval: u64,
+    ) -> Self {
+        let seed: &[_] = &[1, 2, 3, 9];
+        let rng: StdRng = SeedableRng::from_seed(seed);
+
+        Self::new(
+            TestKitBuilder::auditor(),
             None,
             validators_num,
             total_funds,
@@ -373,6 +427,84 @@ impl AnchoringTestKit {
         Ok(signatures)
     }
 
+    /// Creates fee bump transactions for the latest anchoring transaction
+    /// for the given number of validators.
+    pub fn create_fee_bump_tx_for_validators(
+        &self,
+        validators_num: u16,
+    ) -> Vec<Signed<RawTransaction>> {
+        self.create_fee_bump_votes(validators_num, false)
+    }
+
+    /// Creates transactions which withdraw the fee bump votes for the latest anchoring
+    /// transaction for the given number of validators.
+    pub fn create_fee_bump_cancel_tx_for_validators(
+        &self,
+        validators_num: u16,
+    ) -> Vec<Signed<RawTransaction>> {
+        self.create_fee_bump_votes(validators_num, true)
+    }
+
+    fn create_fee_bump_votes(
+        &self,
+        validators_num: u16,
+        cancel: bool,
+    ) -> Vec<Signed<RawTransaction>> {
+        let txid = self
+            .last_anchoring_tx()
+            .expect("Anchoring transactions chain is empty")
+            .id();
+
+        self.network()
+            .validators()
+            .iter()
+            .filter(|v| v != &self.us())
+            .take(validators_num as usize)
+            .map(|validator| {
+                let (public_key, private_key) = validator.service_keypair();
+                Message::sign_transaction(
+                    TxFeeBump {
+                        validator: validator.validator_id().unwrap(),
+                        txid,
+                        cancel,
+                    },
+                    BTC_ANCHORING_SERVICE_ID,
+                    *public_key,
+                    &private_key,
+                )
+            })
+            .collect()
+    }
+
+    /// Creates funding proposal transactions for the given funding transaction output
+    /// for the given number of validators.
+    pub fn create_funding_proposal_tx_for_validators(
+        &self,
+        transaction: &btc::Transaction,
+        output: u32,
+        validators_num: u16,
+    ) -> Vec<Signed<RawTransaction>> {
+        self.network()
+            .validators()
+            .iter()
+            .filter(|v| v != &self.us())
+            .take(validators_num as usize)
+            .map(|validator| {
+                let (public_key, private_key) = validator.service_keypair();
+                Message::sign_transaction(
+                    TxFundingProposal {
+                        validator: validator.validator_id().unwrap(),
+                        transaction: transaction.clone(),
+                        output,
+                    },
+                    BTC_ANCHORING_SERVICE_ID,
+                    *public_key,
+                    &private_key,
+                )
+            })
+            .collect()
+    }
+
     /// Creates a configuration change proposal which excludes
     /// one of validators from the consensus.
     pub fn drop_validator_proposal(&mut self) -> TestNetworkConfiguration {
@@ -409,6 +541,16 @@ impl AnchoringTestKit {
             .unwrap()
     }
 
+    /// Returns the local audit report of the anchoring chain.
+    pub fn audit_report(&self) -> AuditReport {
+        self.api().audit(()).unwrap()
+    }
+
+    /// Returns the summary of the anchoring health at the current node.
+    pub fn status(&self) -> AnchoringStatus {
+        self.api().status(()).unwrap()
+    }
+
     fn get_local_cfg(&self, node: &TestNode) -> LocalConfig {
         self.node_configs[node.validator_id().unwrap().0 as usize].clone()
     }
@@ -436,11 +578,60 @@ impl PublicApi for TestKitApi {
             .get("v1/transaction")
     }
 
+    fn transactions(
+        &self,
+        query: TransactionsQuery,
+    ) -> Result<Option<TransactionsPage>, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .query(&query)
+            .get("v1/transactions")
+    }
+
+    fn anchored_block_proof(
+        &self,
+        query: HeightQuery,
+    ) -> Result<Option<AnchoredBlockProof>, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .query(&query)
+            .get("v1/anchored_block_proof")
+    }
+
+    fn batch_proof(&self, query: HeightQuery) -> Result<Option<BatchProof>, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .query(&query)
+            .get("v1/batch_proof")
+    }
+
+    fn pending_proposal(&self, _query: ()) -> Result<Option<PendingProposal>, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .get("v1/proposal")
+    }
+
+    fn proposal_psbt(&self, _query: ()) -> Result<Option<btc::Psbt>, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .get("v1/proposal/psbt")
+    }
+
     fn block_header_proof(&self, query: HeightQuery) -> Result<BlockHeaderProof, Self::Error> {
         self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
             .query(&query)
             .get("v1/block_header_proof")
     }
+
+    fn status(&self, _query: ()) -> Result<AnchoringStatus, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .get("v1/status")
+    }
+
+    fn audit(&self, _query: ()) -> Result<AuditReport, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .get("v1/audit")
+    }
+
+    fn sync_state(&self, _query: ()) -> Result<Option<SyncState>, Self::Error> {
+        self.public(ApiKind::Service(BTC_ANCHORING_SERVICE_NAME))
+            .get("v1/sync")
+    }
 }
 
 fn validate_table_proof(
@@ -520,3 +711,185 @@ impl ValidateProof for BlockHeaderProof {
         Ok((values[0].0, *values[0].1))
     }
 }
+
+impl ValidateProof for TransactionsPage {
+    type Output = Vec<(u64, btc::Transaction)>;
+
+    fn validate(self, actual_config: &StoredConfiguration) -> Result<Self::Output, failure::Error> {
+        let proof_entry =
+            validate_table_proof(actual_config, &self.latest_authorized_block, self.to_table)?;
+        let table_location = Blockchain::service_table_unique_key(BTC_ANCHORING_SERVICE_ID, 0);
+        ensure!(proof_entry.0 == table_location, "Invalid table location");
+        // Validates values.
+        let values = self
+            .to_transactions
+            .validate(proof_entry.1, self.transactions_count)
+            .map_err(|e| format_err!("An error occurred {:?}", e))?;
+        ensure!(
+            values.len() == self.transactions.len(),
+            "Invalid values count"
+        );
+        for ((index, transaction), info) in values.iter().zip(&self.transactions) {
+            ensure!(
+                *index == info.index && **transaction == info.transaction,
+                "Transaction doesn't match the proof"
+            );
+        }
+
+        Ok(values
+            .into_iter()
+            .map(|(index, transaction)| (index, transaction.clone()))
+            .collect())
+    }
+}
+
+impl ValidateProof for AnchoredBlockProof {
+    /// Hash of the requested block and the anchoring transaction which covers it.
+    ///
+    /// The Bitcoin proof is not checked here, use
+    /// `AnchoredBlockProof::validate_in_bitcoin_block` for this.
+    type Output = (Hash, btc::Transaction);
+
+    fn validate(self, actual_config: &StoredConfiguration) -> Result<Self::Output, failure::Error> {
+        // Validates the anchoring transaction.
+        let proof_entry = validate_table_proof(
+            actual_config,
+            &self.latest_authorized_block,
+            self.to_transactions_table,
+        )?;
+        let table_location = Blockchain::service_table_unique_key(BTC_ANCHORING_SERVICE_ID, 0);
+        ensure!(proof_entry.0 == table_location, "Invalid table location");
+        let transactions = self
+            .to_transaction
+            .validate(proof_entry.1, self.transactions_count)
+            .map_err(|e| format_err!("An error occurred {:?}", e))?;
+        ensure!(transactions.len() == 1, "Invalid transactions count");
+        let transaction = transactions[0].1.clone();
+        let payload = transaction
+            .anchoring_payload()
+            .ok_or_else(|| format_err!("Anchoring transaction doesn't contain payload"))?;
+
+        // Validates the block headers.
+        let proof_entry = validate_table_proof(
+            actual_config,
+            &self.latest_authorized_block,
+            self.to_block_headers_table,
+        )?;
+        let table_location = Blockchain::service_table_unique_key(BTC_ANCHORING_SERVICE_ID, 3);
+        ensure!(proof_entry.0 == table_location, "Invalid table location");
+        let block_headers = self
+            .to_block_headers
+            .validate(proof_entry.1, self.latest_authorized_block.block.height().0)
+            .map_err(|e| format_err!("An error occurred {:?}", e))?;
+
+        // Checks that the headers range ends with the anchored block.
+        let (first_height, block_hash) = *block_headers
+            .first()
+            .ok_or_else(|| format_err!("Block headers are absent"))?;
+        let (last_height, anchored_block_hash) = *block_headers.last().unwrap();
+        ensure!(
+            last_height == payload.block_height.0 && *anchored_block_hash == payload.block_hash,
+            "Anchoring transaction doesn't cover the requested block"
+        );
+        ensure!(
+            block_headers.len() as u64 == last_height - first_height + 1,
+            "Block headers range is incomplete"
+        );
+
+        Ok((*block_hash, transaction))
+    }
+}
+
+impl AnchoredBlockProof {
+    /// Validates the proof and checks that the anchoring transaction is included
+    /// in the Bitcoin block with the given hash, which should be obtained from
+    /// a trusted source, such as the own Bitcoin node.
+    pub fn validate_in_bitcoin_block(
+        mut self,
+        actual_config: &StoredConfiguration,
+        bitcoin_block_hash: &Hash,
+    ) -> Result<(Hash, btc::Transaction), failure::Error> {
+        let bitcoin_proof = self.bitcoin_proof.take();
+        let (block_hash, transaction) = self.validate(actual_config)?;
+        validate_bitcoin_proof(bitcoin_proof, &transaction, bitcoin_block_hash)?;
+        Ok((block_hash, transaction))
+    }
+}
+
+impl ValidateProof for BatchProof {
+    /// Height and hash of the requested block and the anchoring transaction which commits
+    /// to the batch.
+    ///
+    /// The Bitcoin proof is not checked here, use `BatchProof::validate_in_bitcoin_block`
+    /// for this.
+    type Output = (Height, Hash, btc::Transaction);
+
+    fn validate(self, actual_config: &StoredConfiguration) -> Result<Self::Output, failure::Error> {
+        // Validates the anchoring transaction.
+        let proof_entry = validate_table_proof(
+            actual_config,
+            &self.latest_authorized_block,
+            self.to_transactions_table,
+        )?;
+        let table_location = Blockchain::service_table_unique_key(BTC_ANCHORING_SERVICE_ID, 0);
+        ensure!(proof_entry.0 == table_location, "Invalid table location");
+        let transactions = self
+            .to_transaction
+            .validate(proof_entry.1, self.transactions_count)
+            .map_err(|e| format_err!("An error occurred {:?}", e))?;
+        ensure!(transactions.len() == 1, "Invalid transactions count");
+        let transaction = transactions[0].1.clone();
+        let payload = transaction
+            .anchoring_payload()
+            .ok_or_else(|| format_err!("Anchoring transaction doesn't contain payload"))?;
+        let batch_root = payload
+            .batch_root
+            .ok_or_else(|| format_err!("Anchoring transaction doesn't commit to the batch"))?;
+
+        // Validates the block hash against the batch root.
+        ensure!(
+            self.blocks_count > 0
+                && self.first_height.0 + self.blocks_count - 1 == payload.block_height.0,
+            "Batch doesn't end with the anchored block"
+        );
+        let block_hashes = self
+            .to_block_hash
+            .validate(batch_root, self.blocks_count)
+            .map_err(|e| format_err!("An error occurred {:?}", e))?;
+        ensure!(block_hashes.len() == 1, "Invalid block hashes count");
+        let (index, block_hash) = block_hashes[0];
+
+        Ok((
+            Height(self.first_height.0 + index),
+            *block_hash,
+            transaction,
+        ))
+    }
+}
+
+impl BatchProof {
+    /// Validates the proof and checks that the anchoring transaction is included
+    /// in the Bitcoin block with the given hash, which should be obtained from
+    /// a trusted source, such as the own Bitcoin node.
+    pub fn validate_in_bitcoin_block(
+        mut self,
+        actual_config: &StoredConfiguration,
+        bitcoin_block_hash: &Hash,
+    ) -> Result<(Height, Hash, btc::Transaction), failure::Error> {
+        let bitcoin_proof = self.bitcoin_proof.take();
+        let (height, block_hash, transaction) = self.validate(actual_config)?;
+        validate_bitcoin_proof(bitcoin_proof, &transaction, bitcoin_block_hash)?;
+        Ok((height, block_hash, transaction))
+    }
+}
+
+/// Checks the inclusion of the anchoring transaction in the trusted Bitcoin block.
+fn validate_bitcoin_proof(
+    proof: Option<btc::MerkleProof>,
+    transaction: &btc::Transaction,
+    bitcoin_block_hash: &Hash,
+) -> Result<(), failure::Error> {
+    let proof = proof.ok_or_else(|| format_err!("Bitcoin proof is absent"))?;
+    proof.verify_in_block(&transaction.id(), bitcoin_block_hash)?;
+    Ok(())
+}
@@ -19,14 +19,16 @@ extern crate exonum_btc_anchoring;
 extern crate exonum_testkit;
 extern crate serde_json;
 
-use exonum::{helpers::Height, storage::Snapshot};
+use bitcoin::{blockdata::block::BlockHeader, util::hash::Sha256dHash};
+use btc_transaction_utils::{p2wsh, TxInRef};
+use exonum::{crypto::Hash, helpers::Height, messages::Message, storage::Snapshot};
 use exonum_btc_anchoring::{
-    api::{FindTransactionQuery, HeightQuery, PublicApi},
-    blockchain::BtcAnchoringSchema,
+    api::{AnchoringStateKind, FindTransactionQuery, HeightQuery, PublicApi, TransactionsQuery},
+    blockchain::{transactions::TxSignature, BtcAnchoringSchema},
     btc,
     config::GlobalConfig,
     test_helpers::testkit::{AnchoringTestKit, ValidateProof},
-    BTC_ANCHORING_SERVICE_NAME,
+    BTC_ANCHORING_SERVICE_ID, BTC_ANCHORING_SERVICE_NAME,
 };
 
 const NULL_QUERY: () = ();
@@ -305,3 +307,383 @@ fn block_header_proof() {
     assert_eq!(value.0, 4);
     assert_eq!(value.1, anchoring_testkit.block_hash_on_height(Height(4)));
 }
+
+// Gets the anchoring transactions chain page by page.
+#[test]
+fn transactions_page() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+    // Creates a few anchoring transactions
+    for _ in 0..5 {
+        let signatures = anchoring_testkit
+            .create_signature_tx_for_validators(2)
+            .unwrap();
+        anchoring_testkit.create_block_with_transactions(signatures);
+
+        let next_anchoring_height = anchoring_testkit
+            .actual_anchoring_configuration()
+            .following_anchoring_height(anchoring_testkit.height());
+        anchoring_testkit.create_blocks_until(next_anchoring_height);
+    }
+
+    let api = anchoring_testkit.api();
+    let cfg = anchoring_testkit.actual_configuration();
+    let anchoring_schema = btc_anchoring_schema(&anchoring_testkit);
+    let tx_chain = anchoring_schema.anchoring_transactions_chain();
+    assert_eq!(tx_chain.len(), 5);
+
+    // Checks the page in the middle of the chain.
+    let page = api
+        .transactions(TransactionsQuery { from: 1, count: 3 })
+        .unwrap()
+        .unwrap();
+    assert_eq!(page.transactions_count, 5);
+    let infos = page
+        .transactions
+        .iter()
+        .map(|info| (info.index, info.txid, info.payload.clone(), info.value))
+        .collect::<Vec<_>>();
+    let expected_infos = (1..4)
+        .map(|index| {
+            let tx = tx_chain.get(index).unwrap();
+            (index, tx.id(), tx.anchoring_payload(), tx.unspent_value())
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(infos, expected_infos);
+    let values = page.validate(&cfg).unwrap();
+    assert_eq!(
+        values,
+        (1..4)
+            .map(|index| (index, tx_chain.get(index).unwrap()))
+            .collect::<Vec<_>>()
+    );
+
+    // Checks the page which is truncated by the end of the chain.
+    let page = api
+        .transactions(TransactionsQuery { from: 3, count: 10 })
+        .unwrap()
+        .unwrap();
+    assert_eq!(page.validate(&cfg).unwrap().len(), 2);
+    // Checks the page outside of the chain.
+    assert!(api
+        .transactions(TransactionsQuery { from: 5, count: 1 })
+        .unwrap()
+        .is_none());
+    // Checks the empty page request.
+    assert!(api
+        .transactions(TransactionsQuery { from: 0, count: 0 })
+        .is_err());
+}
+
+#[test]
+fn status() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let status = anchoring_testkit.status();
+    assert_eq!(status.state, AnchoringStateKind::Regular);
+    assert_eq!(status.latest_anchored_height, None);
+    assert_eq!(status.next_anchoring_height, Height(0));
+    assert_eq!(status.balance, None);
+    assert_eq!(status.latest_confirmations, None);
+    // Synchronization with the Bitcoin network is not performed without the relay.
+    assert_eq!(anchoring_testkit.api().sync_state(()).unwrap(), None);
+    assert!(anchoring_testkit.api().audit(()).unwrap().is_ok());
+
+    // Two of three required signatures are collected.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let proposal = btc_anchoring_schema(&anchoring_testkit)
+        .actual_proposed_anchoring_transaction()
+        .unwrap()
+        .unwrap()
+        .0;
+    let status = anchoring_testkit.status();
+    let progress = status.proposal.unwrap();
+    assert_eq!(progress.txid, proposal.id());
+    assert_eq!(progress.collected_signatures, vec![2]);
+    assert_eq!(status.proposal_error, None);
+
+    // The remaining signature finalizes the anchoring transaction.
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    let status = anchoring_testkit.status();
+    assert_eq!(status.latest_anchored_height, Some(Height(0)));
+    assert_eq!(status.next_anchoring_height, Height(4));
+    assert_eq!(status.balance, tx0.unspent_value());
+    assert_eq!(status.proposal, None);
+}
+
+#[test]
+fn pending_proposal() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let (proposal, _) = btc_anchoring_schema(&anchoring_testkit)
+        .actual_proposed_anchoring_transaction()
+        .unwrap()
+        .unwrap();
+    let pending_proposal = anchoring_testkit
+        .api()
+        .pending_proposal(NULL_QUERY)
+        .unwrap()
+        .unwrap();
+    assert_eq!(pending_proposal.txid, proposal.id());
+    assert_eq!(pending_proposal.transaction, proposal);
+    assert_eq!(pending_proposal.quorum, 3);
+    assert_eq!(pending_proposal.inputs.len(), 1);
+
+    // Our node hasn't signed the proposal yet.
+    let us = anchoring_testkit.us().validator_id().unwrap();
+    let input = &pending_proposal.inputs[0];
+    assert_eq!(input.prev_txid, proposal.prev_tx_id());
+    assert_eq!(input.signed_by.len(), 2);
+    assert_eq!(input.missing.len(), 2);
+    assert!(!input.signed_by.contains(&us));
+    assert!(input.missing.contains(&us));
+
+    // There is no proposal after the anchoring transaction is finalized.
+    anchoring_testkit.create_blocks_until(Height(2));
+    assert!(anchoring_testkit
+        .api()
+        .pending_proposal(NULL_QUERY)
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn proposal_psbt() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let (proposal, proposal_inputs) = btc_anchoring_schema(&anchoring_testkit)
+        .actual_proposed_anchoring_transaction()
+        .unwrap()
+        .unwrap();
+    let psbt = anchoring_testkit
+        .api()
+        .proposal_psbt(NULL_QUERY)
+        .unwrap()
+        .unwrap();
+    assert_eq!(psbt.unsigned_tx, proposal);
+    assert_eq!(psbt.inputs.len(), 1);
+    assert_eq!(
+        psbt.inputs[0].non_witness_utxo.as_ref(),
+        Some(&proposal_inputs[0])
+    );
+
+    // Signs the proposal offline by the quorum of validators.
+    let redeem_script = anchoring_testkit.redeem_script();
+    let address = anchoring_testkit.anchoring_address();
+    let public_keys = anchoring_testkit
+        .actual_anchoring_configuration()
+        .public_keys;
+    let mut signed_psbt = psbt.clone();
+    for (validator_id, node_config) in anchoring_testkit.node_configs.iter().enumerate().take(3) {
+        let signature = p2wsh::InputSigner::new(redeem_script.clone())
+            .sign_input(
+                TxInRef::new(proposal.as_ref(), 0),
+                proposal_inputs[0].as_ref(),
+                node_config.private_keys[&address].0.secret_key(),
+            )
+            .unwrap();
+        signed_psbt.inputs[0]
+            .partial_sigs
+            .push((public_keys[validator_id], signature.into()));
+    }
+    let signed_psbt: btc::Psbt = signed_psbt.to_string().parse().unwrap();
+
+    // Submits the signatures from the PSBT on behalf of the validators.
+    let signatures = anchoring_testkit
+        .network()
+        .validators()
+        .iter()
+        .take(3)
+        .flat_map(|validator| {
+            let validator_id = validator.validator_id().unwrap();
+            let (public_key, secret_key) = validator.service_keypair();
+            signed_psbt
+                .input_signatures(&public_keys[validator_id.0 as usize])
+                .into_iter()
+                .map(|(input, input_signature)| {
+                    Message::sign_transaction(
+                        TxSignature {
+                            validator: validator_id,
+                            transaction: signed_psbt.unsigned_tx.clone(),
+                            input,
+                            input_signature,
+                        },
+                        BTC_ANCHORING_SERVICE_ID,
+                        *public_key,
+                        &secret_key,
+                    )
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+    assert_eq!(signatures.len(), 3);
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    assert_eq!(
+        anchoring_testkit.last_anchoring_tx().unwrap().id(),
+        proposal.id()
+    );
+    assert!(anchoring_testkit
+        .api()
+        .proposal_psbt(NULL_QUERY)
+        .unwrap()
+        .is_none());
+}
+
+// Links the Exonum blocks to the anchoring transactions which cover them.
+#[test]
+fn anchored_block_proof() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+    // Creates a few anchoring transactions
+    for _ in 0..3 {
+        let signatures = anchoring_testkit
+            .create_signature_tx_for_validators(2)
+            .unwrap();
+        anchoring_testkit.create_block_with_transactions(signatures);
+
+        let next_anchoring_height = anchoring_testkit
+            .actual_anchoring_configuration()
+            .following_anchoring_height(anchoring_testkit.height());
+        anchoring_testkit.create_blocks_until(next_anchoring_height);
+    }
+
+    let api = anchoring_testkit.api();
+    let cfg = anchoring_testkit.actual_configuration();
+    let anchoring_schema = btc_anchoring_schema(&anchoring_testkit);
+    let tx_chain = anchoring_schema.anchoring_transactions_chain();
+
+    // Checks the anchored block itself.
+    let proof = api
+        .anchored_block_proof(HeightQuery { height: 4 })
+        .unwrap()
+        .unwrap();
+    let (block_hash, tx) = proof.validate(&cfg).unwrap();
+    assert_eq!(
+        block_hash,
+        anchoring_testkit.block_hash_on_height(Height(4))
+    );
+    assert_eq!(tx, tx_chain.get(1).unwrap());
+    // Checks the block between the anchored ones.
+    let proof_for_height_5 = || {
+        api.anchored_block_proof(HeightQuery { height: 5 })
+            .unwrap()
+            .unwrap()
+    };
+    let (block_hash, tx) = proof_for_height_5().validate(&cfg).unwrap();
+    assert_eq!(
+        block_hash,
+        anchoring_testkit.block_hash_on_height(Height(5))
+    );
+    assert_eq!(tx, tx_chain.get(2).unwrap());
+    // There is no Bitcoin relay to prove the transaction inclusion.
+    assert!(proof_for_height_5()
+        .validate_in_bitcoin_block(&cfg, &Hash::zero())
+        .is_err());
+    // Checks the Bitcoin proof against the trusted block hash.
+    let mut block_header = BlockHeader {
+        version: 1,
+        prev_blockhash: Sha256dHash::default(),
+        merkle_root: tx.0.txid(),
+        time: 0,
+        bits: 0x207f_ffff,
+        nonce: 0,
+    };
+    let bitcoin_proof = btc::MerkleProof::new(block_header, &[tx.id()], 0);
+    let bitcoin_block_hash = bitcoin_proof.block_hash();
+    let mut proof = proof_for_height_5();
+    proof.bitcoin_proof = Some(bitcoin_proof.clone());
+    let (block_hash, _) = proof
+        .validate_in_bitcoin_block(&cfg, &bitcoin_block_hash)
+        .unwrap();
+    assert_eq!(
+        block_hash,
+        anchoring_testkit.block_hash_on_height(Height(5))
+    );
+    // The proof is rejected for the other Bitcoin block.
+    block_header.nonce += 1;
+    let other_block_hash = btc::MerkleProof::new(block_header, &[tx.id()], 0).block_hash();
+    let mut proof = proof_for_height_5();
+    proof.bitcoin_proof = Some(bitcoin_proof);
+    assert!(proof
+        .validate_in_bitcoin_block(&cfg, &other_block_hash)
+        .is_err());
+    // The block after the latest anchored one is not covered yet.
+    assert!(api
+        .anchored_block_proof(HeightQuery { height: 9 })
+        .unwrap()
+        .is_none());
+}
+
+#[test]
+fn batch_proof() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    // Enables the batch anchoring.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        batch_anchoring: true,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(4));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(6));
+
+    let api = anchoring_testkit.api();
+    let cfg = anchoring_testkit.actual_configuration();
+    let tx = anchoring_testkit.last_anchoring_tx().unwrap();
+    let payload = tx.anchoring_payload().unwrap();
+    assert_eq!(payload.block_height, Height(4));
+    assert!(payload.batch_root.is_some());
+
+    // Each block since the previous anchored one is linked to the batch root.
+    for height in 1..=4 {
+        let proof = api.batch_proof(HeightQuery { height }).unwrap().unwrap();
+        assert_eq!(proof.first_height, Height(1));
+        assert_eq!(proof.blocks_count, 4);
+        let (block_height, block_hash, batch_tx) = proof.validate(&cfg).unwrap();
+        assert_eq!(block_height, Height(height));
+        assert_eq!(
+            block_hash,
+            anchoring_testkit.block_hash_on_height(Height(height))
+        );
+        assert_eq!(batch_tx, tx);
+    }
+    // The first anchoring transaction is a regular one.
+    assert!(api
+        .batch_proof(HeightQuery { height: 0 })
+        .unwrap()
+        .is_none());
+    // The block after the latest anchored one is not covered yet.
+    assert!(api
+        .batch_proof(HeightQuery { height: 5 })
+        .unwrap()
+        .is_none());
+}
@@ -26,6 +26,7 @@ use hex::FromHex;
 
 use exonum::crypto::Hash;
 use exonum::helpers::Height;
+use exonum_btc_anchoring::audit::Discrepancy;
 use exonum_btc_anchoring::blockchain::BtcAnchoringSchema;
 use exonum_btc_anchoring::btc::Transaction;
 use exonum_btc_anchoring::rpc::TransactionInfo as BtcTransactionInfo;
@@ -289,3 +290,70 @@ fn several_unsynced() {
     anchoring_testkit.create_block_with_transactions(signatures);
     anchoring_testkit.create_blocks_until(Height(11));
 }
+
+#[test]
+fn auditor_missing_in_bitcoin() {
+    let mut anchoring_testkit = AnchoringTestKit::new_auditor_with_fake_rpc(4);
+    let requests = anchoring_testkit.requests();
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    // The relay is unavailable during the synchronization and the audit.
+    let unavailable = || {
+        vec![
+            (
+                funding_tx_request().0,
+                FakeRelayResponse::TransactionInfo(Err(
+                    bitcoin_rpc::Error::Memory(String::new()).into()
+                )),
+            ),
+            (
+                FakeRelayRequest::TransactionInfo { id: tx0.id() },
+                FakeRelayResponse::TransactionInfo(Err(
+                    bitcoin_rpc::Error::Memory(String::new()).into()
+                )),
+            ),
+        ]
+    };
+    requests.expect(unavailable());
+    requests.expect(unavailable());
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    // The first anchoring transaction is absent in the Bitcoin blockchain.
+    requests.expect(vec![
+        (
+            FakeRelayRequest::TransactionInfo { id: tx0.id() },
+            FakeRelayResponse::TransactionInfo(Ok(None)),
+        ),
+        (
+            funding_tx_request().0,
+            FakeRelayResponse::TransactionInfo(Err(
+                bitcoin_rpc::Error::Memory(String::new()).into()
+            )),
+        ),
+        (
+            FakeRelayRequest::TransactionInfo { id: tx0.id() },
+            FakeRelayResponse::TransactionInfo(Ok(None)),
+        ),
+    ]);
+    anchoring_testkit.create_blocks_until(Height(6));
+
+    let report = anchoring_testkit.audit_report();
+    assert_eq!(report.confirmed_transactions, 0);
+    assert_eq!(
+        report.discrepancies,
+        vec![Discrepancy::MissingInBitcoin {
+            index: 0,
+            txid: tx0.id(),
+        }]
+    );
+}
@@ -27,15 +27,25 @@ extern crate matches;
 
 extern crate btc_transaction_utils;
 
-use exonum::blockchain::TransactionErrorType;
+use btc_transaction_utils::{p2wsh::InputSigner, TxInRef};
+
+use exonum::blockchain::{Schema as CoreSchema, TransactionErrorType};
+use exonum::crypto::{CryptoHash, Hash};
 use exonum::explorer::BlockWithTransactions;
-use exonum::helpers::Height;
+use exonum::helpers::{Height, ValidatorId};
+use exonum::messages::Message;
 use exonum_btc_anchoring::{
-    blockchain::errors::ErrorCode,
-    btc::BuilderError,
-    config::GlobalConfig,
+    audit::Discrepancy,
+    blockchain::{
+        data_layout::TxOutputId,
+        errors::ErrorCode,
+        transactions::{TxFundingProposal, TxSignature},
+        BtcAnchoringSchema,
+    },
+    btc::{self, BuilderError, ChainId, Payload, PayloadVersion},
+    config::{ConfigError, FeeBumpConfig, GlobalConfig},
     test_helpers::testkit::{create_fake_funding_transaction, AnchoringTestKit},
-    BTC_ANCHORING_SERVICE_NAME,
+    BTC_ANCHORING_SERVICE_ID, BTC_ANCHORING_SERVICE_NAME,
 };
 
 fn assert_tx_error(block: BlockWithTransactions, e: ErrorCode) {
@@ -45,6 +55,18 @@ fn assert_tx_error(block: BlockWithTransactions, e: ErrorCode) {
     );
 }
 
+/// Appends the given transaction to the anchoring chain bypassing the transactions.
+fn push_anchoring_tx(anchoring_testkit: &mut AnchoringTestKit, tx: btc::Transaction) {
+    let mut fork = anchoring_testkit.blockchain_mut().fork();
+    BtcAnchoringSchema::new(&mut fork)
+        .anchoring_transactions_chain_mut()
+        .push(tx);
+    anchoring_testkit
+        .blockchain_mut()
+        .merge(fork.into_patch())
+        .unwrap();
+}
+
 #[test]
 fn simple() {
     let validators_num = 4;
@@ -102,7 +124,7 @@ fn additional_funding() {
 
     let mut proposal = anchoring_testkit.configuration_change_proposal();
     let service_configuration = GlobalConfig {
-        funding_transaction: Some(new_funding_tx),
+        funding_transactions: vec![new_funding_tx],
         ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
     };
 
@@ -127,6 +149,56 @@ fn additional_funding() {
     assert!(output_val1 > initial_sum);
 }
 
+#[test]
+fn payload_v2() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx0.anchoring_payload().unwrap().state_commitment, None);
+
+    // Switches the payload version.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        payload_version: PayloadVersion::V2,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(4));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(6));
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.prev_tx_id(), tx0.id());
+    let payload = tx1.anchoring_payload().unwrap();
+    assert_eq!(payload.block_height, Height(4));
+
+    // The state commitment can be checked against the Exonum block.
+    let snapshot = anchoring_testkit.snapshot();
+    let core_schema = CoreSchema::new(&snapshot);
+    let block = core_schema.blocks().get(&payload.block_hash).unwrap();
+    let config_hash = core_schema.configuration_by_height(Height(4)).hash();
+    assert_eq!(
+        payload.state_commitment,
+        Some(Payload::state_commitment_for(
+            block.state_hash(),
+            &config_hash
+        ))
+    );
+}
+
 #[test]
 fn address_changed() {
     let validators_num = 5;
@@ -216,7 +288,7 @@ fn address_changed_and_new_funding_tx() {
     let new_address = service_config.anchoring_address();
     let new_funding_tx = create_fake_funding_transaction(&new_address, initial_sum);
 
-    service_config.funding_transaction = Some(new_funding_tx);
+    service_config.funding_transactions = vec![new_funding_tx];
     proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_config);
     proposal.set_actual_from(Height(16));
     anchoring_testkit.commit_configuration_change(proposal);
@@ -365,6 +437,48 @@ fn wrong_signature_tx() {
     assert_tx_error(block, ErrorCode::Unexpected);
 }
 
+#[test]
+fn signature_with_unexpected_author() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    let (proposal, proposal_inputs) = schema
+        .actual_proposed_anchoring_transaction()
+        .unwrap()
+        .unwrap();
+
+    // Signs the anchoring transaction input with the bitcoin key of the second validator.
+    let address = anchoring_testkit.anchoring_address();
+    let privkey = &anchoring_testkit.node_configs[1].private_keys[&address];
+    let mut signer = InputSigner::new(anchoring_testkit.redeem_script());
+    let signature = signer
+        .sign_input(
+            TxInRef::new(proposal.as_ref(), 0),
+            proposal_inputs[0].as_ref(),
+            privkey.0.secret_key(),
+        )
+        .unwrap();
+
+    // But sends it on behalf of the third validator.
+    let validators = anchoring_testkit.network().validators().to_vec();
+    let (public_key, private_key) = validators[2].service_keypair();
+    let tx = Message::sign_transaction(
+        TxSignature {
+            validator: ValidatorId(1),
+            transaction: proposal.clone(),
+            input: 0,
+            input_signature: signature.into(),
+        },
+        BTC_ANCHORING_SERVICE_ID,
+        *public_key,
+        &private_key,
+    );
+
+    let block = anchoring_testkit.create_block_with_transactions(vec![tx]);
+    assert_tx_error(block, ErrorCode::UnexpectedAuthor);
+}
+
 #[test]
 fn broken_anchoring_recovery() {
     let validators_num = 5;
@@ -402,7 +516,7 @@ fn broken_anchoring_recovery() {
 
     let mut proposal = anchoring_testkit.configuration_change_proposal();
     let service_configuration = GlobalConfig {
-        funding_transaction: Some(new_funding_tx),
+        funding_transactions: vec![new_funding_tx],
         ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
     };
 
@@ -455,3 +569,598 @@ fn broken_anchoring_recovery() {
             > recovery_tx.anchoring_payload().unwrap().block_height
     );
 }
+
+#[test]
+fn auditor_normal_operation() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_auditor_without_rpc(validators_num, 70000, 4);
+
+    for _ in 0..3 {
+        let signatures = anchoring_testkit
+            .create_signature_tx_for_validators(3)
+            .unwrap();
+        anchoring_testkit.create_block_with_transactions(signatures);
+
+        let next_anchoring_height = anchoring_testkit
+            .actual_anchoring_configuration()
+            .following_anchoring_height(anchoring_testkit.height());
+        anchoring_testkit.create_blocks_until(next_anchoring_height);
+    }
+
+    let report = anchoring_testkit.audit_report();
+    assert!(report.is_ok());
+    assert_eq!(report.checked_transactions, 3);
+}
+
+#[test]
+fn auditor_changed_proposal() {
+    let validators_num = 4;
+    let initial_sum = 50000;
+    let mut anchoring_testkit =
+        AnchoringTestKit::new_auditor_without_rpc(validators_num, initial_sum, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    // The following proposal changes after the funding transaction output approval.
+    let address = anchoring_testkit.anchoring_address();
+    let funding_tx = create_fake_funding_transaction(&address, initial_sum);
+    let votes = anchoring_testkit.create_funding_proposal_tx_for_validators(&funding_tx, 0, 3);
+    anchoring_testkit.create_block_with_transactions(votes);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_block();
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.spent_outputs()[1], (funding_tx.id(), 0));
+
+    let report = anchoring_testkit.audit_report();
+    assert!(report.is_ok());
+    assert_eq!(report.checked_transactions, 2);
+}
+
+#[test]
+fn auditor_broken_chain() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_auditor_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    // The same transaction does not spend itself.
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    push_anchoring_tx(&mut anchoring_testkit, tx0.clone());
+    anchoring_testkit.create_block();
+
+    let report = anchoring_testkit.audit_report();
+    assert_eq!(report.checked_transactions, 2);
+    assert_eq!(
+        report.discrepancies,
+        vec![Discrepancy::BrokenChain {
+            index: 1,
+            txid: tx0.id(),
+            prev_txid: tx0.id(),
+        }]
+    );
+}
+
+#[test]
+fn auditor_payload_mismatch() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_auditor_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(3)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    let (proposal, _) = schema
+        .actual_proposed_anchoring_transaction()
+        .unwrap()
+        .unwrap();
+    let payload = proposal.anchoring_payload().unwrap();
+
+    // Replaces the anchored block hash in the payload script.
+    let mut tx = proposal.clone();
+    let mut script = tx.0.output[1].script_pubkey.to_bytes();
+    let pos = script
+        .windows(32)
+        .position(|window| window == payload.block_hash.as_ref())
+        .unwrap();
+    script[pos..pos + 32].copy_from_slice(Hash::zero().as_ref());
+    tx.0.output[1].script_pubkey = bitcoin::Script::from(script);
+    assert_eq!(tx.anchoring_payload().unwrap().block_hash, Hash::zero());
+
+    push_anchoring_tx(&mut anchoring_testkit, tx.clone());
+    anchoring_testkit.create_block();
+
+    let report = anchoring_testkit.audit_report();
+    assert_eq!(report.checked_transactions, 2);
+    assert_eq!(
+        report.discrepancies,
+        vec![Discrepancy::PayloadMismatch {
+            index: 1,
+            txid: tx.id(),
+            block_height: payload.block_height,
+            block_hash: Hash::zero(),
+        }]
+    );
+}
+
+#[test]
+fn fee_bump_replaces_stuck_transaction() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    // Enables the opt-in Replace-By-Fee.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        replace_by_fee: true,
+        fee_bump: Some(FeeBumpConfig {
+            stuck_after: 2,
+            fee_increment: 10,
+        }),
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(2));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert!(tx0.signals_rbf());
+
+    // Validators agree that the anchoring transaction is stuck.
+    let votes = anchoring_testkit.create_fee_bump_tx_for_validators(3);
+    anchoring_testkit.create_block_with_transactions(votes);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(8));
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    let replacement = schema.anchoring_transactions_chain().last().unwrap();
+    assert_eq!(schema.anchoring_transactions_chain().len(), 1);
+    assert_ne!(replacement.id(), tx0.id());
+    assert_eq!(replacement.input_tx_ids(), tx0.input_tx_ids());
+    assert_eq!(replacement.anchoring_payload(), tx0.anchoring_payload());
+    assert!(replacement.unspent_value().unwrap() < tx0.unspent_value().unwrap());
+
+    // Anchoring continues from the replacement.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(12));
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.prev_tx_id(), replacement.id());
+}
+
+/// Creates the anchoring testkit with the stuck anchoring transaction approved for the fee bump
+/// with the given fee increment.
+fn anchoring_testkit_with_fee_bump_votes(fee_increment: u64) -> AnchoringTestKit {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        replace_by_fee: true,
+        fee_bump: Some(FeeBumpConfig {
+            stuck_after: 2,
+            fee_increment,
+        }),
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(2));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let votes = anchoring_testkit.create_fee_bump_tx_for_validators(3);
+    anchoring_testkit.create_block_with_transactions(votes);
+    anchoring_testkit
+}
+
+#[test]
+fn fee_bump_cancelled() {
+    let mut anchoring_testkit = anchoring_testkit_with_fee_bump_votes(10);
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert_eq!(schema.pending_fee_bump(), Some(tx0.clone()));
+
+    // The stuck transaction has been confirmed, so the validator withdraws its vote.
+    let votes = anchoring_testkit.create_fee_bump_cancel_tx_for_validators(1);
+    anchoring_testkit.create_block_with_transactions(votes);
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert_eq!(schema.pending_fee_bump(), None);
+
+    // Anchoring continues from the confirmed transaction.
+    anchoring_testkit.create_blocks_until(Height(8));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert_eq!(schema.anchoring_transactions_chain().len(), 2);
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.prev_tx_id(), tx0.id());
+}
+
+#[test]
+fn fee_bump_insufficient_funds() {
+    // The replacement cannot pay the increased fee.
+    let mut anchoring_testkit = anchoring_testkit_with_fee_bump_votes(1_000_000);
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert_eq!(schema.pending_fee_bump(), Some(tx0.clone()));
+    assert_matches!(
+        schema.proposed_fee_bump(&schema.actual_state()),
+        Some(Err(BuilderError::InsufficientFunds { .. }))
+    );
+
+    // Anchoring continues from the stuck transaction instead.
+    anchoring_testkit.create_blocks_until(Height(8));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert_eq!(schema.anchoring_transactions_chain().len(), 2);
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.prev_tx_id(), tx0.id());
+}
+
+#[test]
+fn fee_bump_disabled() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    let votes = anchoring_testkit.create_fee_bump_tx_for_validators(1);
+    let block = anchoring_testkit.create_block_with_transactions(votes);
+    assert_tx_error(block, ErrorCode::FeeBumpDisabled);
+}
+
+#[test]
+fn multiple_funding_transactions() {
+    let validators_num = 4;
+    let initial_sum = 50000;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, initial_sum, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    // Tops up the anchoring wallet with two funding transactions at once.
+    let address = anchoring_testkit.anchoring_address();
+    let funding_tx1 = create_fake_funding_transaction(&address, initial_sum);
+    let funding_tx2 = create_fake_funding_transaction(&address, initial_sum * 2);
+
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let mut service_configuration: GlobalConfig =
+        proposal.service_config(BTC_ANCHORING_SERVICE_NAME);
+    service_configuration
+        .funding_transactions
+        .extend(vec![funding_tx1.clone(), funding_tx2.clone()]);
+
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(6));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(6));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    // Both funding transactions are consumed in the configuration order.
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(
+        tx1.input_tx_ids(),
+        vec![tx0.id(), funding_tx1.id(), funding_tx2.id()]
+    );
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert!(schema
+        .spent_funding_outputs()
+        .contains(&TxOutputId::new(funding_tx1.id(), 0)));
+    assert!(schema
+        .spent_funding_outputs()
+        .contains(&TxOutputId::new(funding_tx2.id(), 0)));
+    assert!(schema.unspent_funding_transactions().is_empty());
+}
+
+#[test]
+fn funding_proposal() {
+    let validators_num = 4;
+    let initial_sum = 50000;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, initial_sum, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    let address = anchoring_testkit.anchoring_address();
+    let funding_tx = create_fake_funding_transaction(&address, initial_sum);
+
+    // Output with the wrong index is rejected.
+    let votes = anchoring_testkit.create_funding_proposal_tx_for_validators(&funding_tx, 1, 1);
+    let block = anchoring_testkit.create_block_with_transactions(votes);
+    assert_tx_error(block, ErrorCode::UnsuitableFundingOutput);
+
+    // The quorum of validators approves the funding transaction output.
+    let votes = anchoring_testkit.create_funding_proposal_tx_for_validators(&funding_tx, 0, 3);
+    anchoring_testkit.create_block_with_transactions(votes);
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert_eq!(schema.approved_funding_proposals().len(), 1);
+    assert_eq!(schema.unspent_funding_proposals().len(), 1);
+
+    anchoring_testkit.create_blocks_until(Height(5));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.input_tx_ids(), vec![tx0.id(), funding_tx.id()]);
+    assert!(tx1.unspent_value().unwrap() > tx0.unspent_value().unwrap());
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert!(schema.unspent_funding_proposals().is_empty());
+    assert!(schema
+        .spent_funding_outputs()
+        .contains(&TxOutputId::new(funding_tx.id(), 0)));
+
+    // Spent output cannot be proposed again, even by the validator that has not voted yet.
+    let us = anchoring_testkit.us().clone();
+    let (public_key, private_key) = us.service_keypair();
+    let tx = Message::sign_transaction(
+        TxFundingProposal {
+            validator: us.validator_id().unwrap(),
+            transaction: funding_tx.clone(),
+            output: 0,
+        },
+        BTC_ANCHORING_SERVICE_ID,
+        *public_key,
+        &private_key,
+    );
+    let block = anchoring_testkit.create_block_with_transactions(vec![tx]);
+    assert_tx_error(block, ErrorCode::FundingAlreadySpent);
+}
+
+#[test]
+fn funding_proposal_several_outputs() {
+    let validators_num = 4;
+    let initial_sum = 50000;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, initial_sum, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    // Funding transaction with the two outputs to the anchoring address.
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    let address = anchoring_testkit.anchoring_address();
+    let mut funding_tx = create_fake_funding_transaction(&address, initial_sum);
+    let output = funding_tx.0.output[0].clone();
+    funding_tx.0.output.push(output);
+
+    for output in 0..2 {
+        let votes =
+            anchoring_testkit.create_funding_proposal_tx_for_validators(&funding_tx, output, 3);
+        anchoring_testkit.create_block_with_transactions(votes);
+    }
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert_eq!(schema.unspent_funding_proposals().len(), 2);
+
+    anchoring_testkit.create_blocks_until(Height(5));
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(
+        tx1.spent_outputs(),
+        vec![(tx0.id(), 0), (funding_tx.id(), 0), (funding_tx.id(), 1)]
+    );
+
+    let schema = BtcAnchoringSchema::new(anchoring_testkit.snapshot());
+    assert!(schema.unspent_funding_proposals().is_empty());
+    for output in 0..2 {
+        assert!(schema
+            .spent_funding_outputs()
+            .contains(&TxOutputId::new(funding_tx.id(), output)));
+    }
+}
+
+#[test]
+fn payload_chain_id() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(2));
+
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx0.anchoring_payload().unwrap().chain_id, None);
+
+    // Enables the chain identifier.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        payload_version: PayloadVersion::V2,
+        include_chain_id: true,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(4));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(6));
+
+    let tx1 = anchoring_testkit.last_anchoring_tx().unwrap();
+    assert_eq!(tx1.prev_tx_id(), tx0.id());
+    let payload = tx1.anchoring_payload().unwrap();
+    assert_eq!(payload.block_height, Height(4));
+
+    let chain_id = ChainId::from_genesis_hash(&anchoring_testkit.block_hash_on_height(Height(0)));
+    assert_eq!(payload.chain_id, Some(chain_id));
+    assert_eq!(payload.state_commitment, None);
+    assert!(!payload.matches_chain(&ChainId([0; 8])));
+    // Auditor finds no discrepancies in the identified payloads.
+    assert!(anchoring_testkit.audit_report().discrepancies.is_empty());
+}
+
+#[test]
+fn invalid_configuration_is_not_signed() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(2));
+    let tx0 = anchoring_testkit.last_anchoring_tx().unwrap();
+
+    // The payload v.1 has no room for the chain identifier, but the configuration service
+    // installs such configuration anyway.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        include_chain_id: true,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    assert_eq!(
+        service_configuration.validate(),
+        Err(ConfigError::ChainIdNotSupported)
+    );
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(4));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    // Our node refuses to sign the proposal, thus the other signatures are not enough.
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(6));
+    assert_eq!(anchoring_testkit.last_anchoring_tx(), Some(tx0));
+}
+
+#[test]
+fn payload_v3() {
+    let validators_num = 4;
+    let mut anchoring_testkit = AnchoringTestKit::new_without_rpc(validators_num, 70000, 4);
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+
+    // Switches the payload version.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        payload_version: PayloadVersion::V3,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(4));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(4));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(6));
+
+    let payload = anchoring_testkit
+        .last_anchoring_tx()
+        .unwrap()
+        .anchoring_payload()
+        .unwrap();
+    assert_eq!(payload.block_height, Height(4));
+    assert!(payload.state_commitment.is_some());
+    assert!(payload.unknown_records.is_empty());
+
+    // The chain identifier replaces the state commitment.
+    let mut proposal = anchoring_testkit.configuration_change_proposal();
+    let service_configuration = GlobalConfig {
+        include_chain_id: true,
+        ..proposal.service_config(BTC_ANCHORING_SERVICE_NAME)
+    };
+    assert_eq!(service_configuration.validate(), Ok(()));
+    proposal.set_service_config(BTC_ANCHORING_SERVICE_NAME, service_configuration);
+    proposal.set_actual_from(Height(8));
+    anchoring_testkit.commit_configuration_change(proposal);
+    anchoring_testkit.create_blocks_until(Height(8));
+
+    let signatures = anchoring_testkit
+        .create_signature_tx_for_validators(2)
+        .unwrap();
+    anchoring_testkit.create_block_with_transactions(signatures);
+    anchoring_testkit.create_blocks_until(Height(10));
+
+    let payload = anchoring_testkit
+        .last_anchoring_tx()
+        .unwrap()
+        .anchoring_payload()
+        .unwrap();
+    assert_eq!(payload.block_height, Height(8));
+    assert_eq!(payload.state_commitment, None);
+    let chain_id = ChainId::from_genesis_hash(&anchoring_testkit.block_hash_on_height(Height(0)));
+    assert_eq!(payload.chain_id, Some(chain_id));
+}